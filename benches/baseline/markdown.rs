@@ -0,0 +1,311 @@
+//! The citation extraction of the baseline (commit c419e7e), kept verbatim
+//! as the reference the tokenizer benchmark compares against.
+//!
+//! Not maintained: do not use it outside of `benches/tokenizer.rs`.
+
+use regex::Regex;
+
+/// Extracts Pandoc-style grouped citations like `[@a; @b; @c]` or `[@a, p. 10; @b, ch. 3]`.
+///
+/// This function finds citations in the Pandoc multi-citation syntax where multiple
+/// citation items are separated by semicolons within a single bracket pair.
+///
+/// # Returns
+///
+/// A vector of `CitationCluster` structs, each containing multiple `CitationItem`s.
+fn extract_pandoc_grouped_citations(markdown: &str) -> Vec<CitationCluster> {
+    // Regex to match Pandoc grouped citations: [@id1; @id2; @id3] or [@id1, locator; @id2]
+    // This matches brackets containing multiple @-prefixed citations separated by semicolons
+    let pandoc_re = Regex::new(r"\[(@[^\]]+;[^\]]*)\]").unwrap();
+
+    let mut clusters: Vec<CitationCluster> = Vec::new();
+
+    for cap in pandoc_re.captures_iter(markdown) {
+        let full_match = cap.get(0).unwrap();
+        let inner = cap.get(1).unwrap().as_str();
+
+        // Split by semicolon and parse each citation item
+        let mut items: Vec<CitationItem> = Vec::new();
+
+        for part in inner.split(';') {
+            let part = part.trim();
+            if part.is_empty() {
+                continue;
+            }
+
+            // Each part should start with @ and may have a locator after comma
+            // Format: @id or @id, locator
+            if let Some(stripped) = part.strip_prefix('@') {
+                // Check if there's a locator (comma-separated)
+                let (id, locator, label) = if let Some(comma_pos) = stripped.find(',') {
+                    let id = stripped[..comma_pos].trim().to_string();
+                    let locator_str = stripped[comma_pos + 1..].trim();
+                    let (locator, label) = parse_locator(locator_str);
+                    (id, locator, label)
+                } else {
+                    (stripped.trim().to_string(), None, None)
+                };
+
+                items.push(CitationItem {
+                    id,
+                    locator,
+                    label,
+                    url: None, // Pandoc syntax doesn't support URLs
+                });
+            }
+        }
+
+        if !items.is_empty() {
+            clusters.push(CitationCluster {
+                items,
+                span: (full_match.start(), full_match.end()),
+            });
+        }
+    }
+
+    clusters
+}
+
+/// An individual citation element (a single @id).
+///
+/// This structure represents a single citation item within a cluster.
+/// Multiple `CitationItem`s can be grouped together in a `CitationCluster`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CitationItem {
+    /// The citation key (e.g., "item-1" or "pmid:12345")
+    pub id: String,
+    /// Optional locator value (e.g., "42" for page 42)
+    pub locator: Option<String>,
+    /// Optional locator label (e.g., "page", "chapter")
+    pub label: Option<String>,
+    /// Optional URL associated with the citation (preserved for reference, ignored in grouped rendering)
+    pub url: Option<String>,
+}
+
+/// A group of citations (one or more items in a single cluster).
+///
+/// Adjacent citations in Markdown (separated only by whitespace) are grouped
+/// into a single cluster for proper CSL formatting (e.g., "(1-3)" instead of "(1) (2) (3)").
+#[derive(Debug, Clone, PartialEq)]
+pub struct CitationCluster {
+    /// The citation items in this cluster
+    pub items: Vec<CitationItem>,
+    /// Start and end byte positions covering the entire cluster in the source text
+    pub span: (usize, usize),
+}
+
+/// Extracts citation clusters from the given Markdown text.
+///
+/// This function detects adjacent citations (separated only by whitespace)
+/// and groups them into clusters. It also supports Pandoc syntax `[@a; @b; @c]`.
+///
+/// # Arguments
+///
+/// * `markdown` - The Markdown text to parse
+///
+/// # Returns
+///
+/// A vector of `CitationCluster` structs representing all citation clusters found.
+///
+/// # Examples
+///
+/// ```
+/// use csl_tools::extract_citation_clusters;
+///
+/// // Adjacent citations are grouped
+/// let clusters = extract_citation_clusters("Studies [@a] [@b] show that...");
+/// assert_eq!(clusters.len(), 1);
+/// assert_eq!(clusters[0].items.len(), 2);
+///
+/// // Citations separated by text are not grouped
+/// let clusters = extract_citation_clusters("See [@a] and also [@b].");
+/// assert_eq!(clusters.len(), 2);
+/// ```
+pub fn extract_citation_clusters(markdown: &str) -> Vec<CitationCluster> {
+    // First, extract Pandoc-style grouped citations [@a; @b; @c]
+    let pandoc_clusters = extract_pandoc_grouped_citations(markdown);
+
+    // Collect spans covered by Pandoc citations to avoid duplicates
+    let pandoc_spans: Vec<(usize, usize)> = pandoc_clusters.iter().map(|c| c.span).collect();
+
+    // Helper function to check if a position is inside a Pandoc citation
+    let is_inside_pandoc = |pos: usize| -> bool {
+        pandoc_spans
+            .iter()
+            .any(|(start, end)| pos >= *start && pos < *end)
+    };
+
+    // Extract all individual citations using the existing function
+    let citations = extract_citations(markdown);
+
+    // Filter out citations that are inside Pandoc grouped citations
+    let simple_citations: Vec<Citation> = citations
+        .into_iter()
+        .filter(|c| !is_inside_pandoc(c.span.0))
+        .collect();
+
+    // Group adjacent simple citations into clusters
+    let mut simple_clusters: Vec<CitationCluster> = Vec::new();
+    let mut current_items: Vec<CitationItem> = Vec::new();
+    let mut cluster_start: usize = 0;
+    let mut last_end: usize = 0;
+
+    for citation in simple_citations {
+        let item = CitationItem {
+            id: citation.id,
+            locator: citation.locator,
+            label: citation.label,
+            url: citation.url,
+        };
+
+        if current_items.is_empty() {
+            // First item in a potential cluster
+            cluster_start = citation.span.0;
+            last_end = citation.span.1;
+            current_items.push(item);
+        } else {
+            // Check if this citation is adjacent to the previous one
+            // "Adjacent" means only whitespace (spaces, tabs) between them
+            let between = &markdown[last_end..citation.span.0];
+            let is_adjacent = between.chars().all(|c| c == ' ' || c == '\t');
+
+            if is_adjacent {
+                // Add to current cluster
+                last_end = citation.span.1;
+                current_items.push(item);
+            } else {
+                // Start a new cluster - save the current one first
+                simple_clusters.push(CitationCluster {
+                    items: current_items,
+                    span: (cluster_start, last_end),
+                });
+
+                // Start new cluster
+                current_items = vec![item];
+                cluster_start = citation.span.0;
+                last_end = citation.span.1;
+            }
+        }
+    }
+
+    // Don't forget the last cluster
+    if !current_items.is_empty() {
+        simple_clusters.push(CitationCluster {
+            items: current_items,
+            span: (cluster_start, last_end),
+        });
+    }
+
+    // Merge Pandoc clusters and simple clusters, sorted by position
+    let mut all_clusters: Vec<CitationCluster> = pandoc_clusters;
+    all_clusters.extend(simple_clusters);
+    all_clusters.sort_by_key(|c| c.span.0);
+
+    all_clusters
+}
+
+/// Represents a citation found in the Markdown text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    /// The citation key (e.g., "item-1" or "pmid:12345")
+    pub id: String,
+    /// Optional locator value (e.g., "42" for page 42)
+    pub locator: Option<String>,
+    /// Optional locator label (e.g., "page", "chapter")
+    pub label: Option<String>,
+    /// Optional URL associated with the citation
+    pub url: Option<String>,
+    /// Start and end byte positions in the original text
+    pub span: (usize, usize),
+}
+
+/// Extracts all citations from the given Markdown text.
+///
+/// # Arguments
+///
+/// * `markdown` - The Markdown text to parse
+///
+/// # Returns
+///
+/// A vector of `Citation` structs representing all citations found.
+///
+/// # Examples
+///
+/// ```
+/// use csl_tools::extract_citations;
+///
+/// let citations = extract_citations("See [@item-1] for details.");
+/// assert_eq!(citations.len(), 1);
+/// assert_eq!(citations[0].id, "item-1");
+/// ```
+pub fn extract_citations(markdown: &str) -> Vec<Citation> {
+    // Regex for citation: [@id], [@id, locator], [@id](url), or [@id, locator](url)
+    // Group 1: id (required)
+    // Group 2: locator part after comma (optional)
+    // Group 3: url (optional)
+    let re = Regex::new(r"\[@([^\]\[,]+)(?:,\s*([^\]]+))?\](?:\(([^)]+)\))?").unwrap();
+
+    re.captures_iter(markdown)
+        .map(|cap| {
+            let full_match = cap.get(0).unwrap();
+            let id = cap.get(1).unwrap().as_str().trim().to_string();
+
+            // Parse the optional locator part
+            let (locator, label) = if let Some(locator_match) = cap.get(2) {
+                parse_locator(locator_match.as_str())
+            } else {
+                (None, None)
+            };
+
+            // Parse the optional URL
+            let url = cap.get(3).map(|m| m.as_str().to_string());
+
+            Citation {
+                id,
+                locator,
+                label,
+                url,
+                span: (full_match.start(), full_match.end()),
+            }
+        })
+        .collect()
+}
+
+/// Parses a locator string like "p. 42", "pp. 10-20", "ch. 3", "sec. 4.2"
+/// or full labels like "page 15", "pages 5-10", "chapter 7", "section 2.1"
+///
+/// Returns (locator_value, label) tuple.
+fn parse_locator(locator_str: &str) -> (Option<String>, Option<String>) {
+    let locator_str = locator_str.trim();
+
+    // Define patterns for different locator types
+    // Order matters: check longer prefixes before shorter ones to avoid partial matches
+    let patterns = [
+        // Abbreviations (pp. before p.)
+        ("pp.", "page"),
+        ("p.", "page"),
+        ("ch.", "chapter"),
+        ("sec.", "section"),
+        // Full words (pages before page)
+        ("pages", "page"),
+        ("page", "page"),
+        ("chapter", "chapter"),
+        ("section", "section"),
+    ];
+
+    for (prefix, label) in patterns {
+        if let Some(stripped) = locator_str.strip_prefix(prefix) {
+            let value = stripped.trim().to_string();
+            if !value.is_empty() {
+                return (Some(value), Some(label.to_string()));
+            }
+        }
+    }
+
+    // If no recognized label, return the raw locator with no label
+    if !locator_str.is_empty() {
+        (Some(locator_str.to_string()), None)
+    } else {
+        (None, None)
+    }
+}
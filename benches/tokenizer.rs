@@ -0,0 +1,89 @@
+//! Citation extraction benchmark on large documents.
+//!
+//! Compares the single-pass tokenizer (`scan_citations`) with the regex-based
+//! extraction of the baseline, kept in `baseline/markdown.rs`. The baseline
+//! found fewer citation forms (no narrative citations, no masking of code),
+//! so the comparison only favours it.
+//!
+//! Run with `cargo bench --bench tokenizer`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use csl_tools::{scan_citations, GroupingPolicy};
+
+#[path = "baseline/markdown.rs"]
+mod baseline;
+
+/// Document sizes, in bytes
+const SIZES: [usize; 3] = [512 * 1024, 1024 * 1024, 2 * 1024 * 1024];
+
+/// Number of timed runs per size (the best one is reported)
+const RUNS: usize = 5;
+
+/// A chapter mixing every citation form with ordinary prose, links and code.
+const CHAPTER: &str = "\
+## Chapter
+
+Early studies [@smith2020; @jones2019, p. 4] found that the effect holds,
+as @doe2021 [chap. 2] showed in detail. Later work [see @lee2018, pp. 3-5 and
+passim] [@kim2022] confirmed it, although [-@park2017] disagrees.
+Contact author@example.org or read [the notes](https://example.org/@notes).
+
+```python
+print(\"[@not_a_citation]\")
+```
+
+Some text with [brackets] and a link to https://example.org/path, then
+more prose about the results, their limits and future work [@smith2020].
+
+";
+
+/// Builds a document of about `size` bytes.
+fn document(size: usize) -> String {
+    CHAPTER.repeat(size / CHAPTER.len() + 1)
+}
+
+/// The baseline extraction, as its `process` command ran it: the clusters,
+/// then the citations again for the bibliography.
+fn baseline_extract(markdown: &str) -> usize {
+    let clusters = baseline::extract_citation_clusters(markdown);
+    let citations = baseline::extract_citations(markdown);
+    clusters.len() + citations.len()
+}
+
+/// Returns the best time of `RUNS` runs of `f`.
+fn best_time(mut f: impl FnMut() -> usize) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    println!(
+        "{:>10} {:>14} {:>14} {:>9}",
+        "size", "baseline", "tokenizer", "speedup"
+    );
+    for size in SIZES {
+        let markdown = document(size);
+
+        let legacy = best_time(|| baseline_extract(&markdown));
+        let tokenizer = best_time(|| {
+            let scan = scan_citations(&markdown, GroupingPolicy::Whitespace);
+            scan.clusters.len() + scan.citations.len()
+        });
+
+        println!(
+            "{:>8}KB {:>12.1}ms {:>12.1}ms {:>8.1}x",
+            markdown.len() / 1024,
+            legacy.as_secs_f64() * 1000.0,
+            tokenizer.as_secs_f64() * 1000.0,
+            legacy.as_secs_f64() / tokenizer.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Citation key aliases.
+//!
+//! When the references are re-keyed (e.g. from `pmid:12345` to `smith2020`),
+//! an alias file maps the old keys to the new ids so that older manuscripts
+//! still resolve. The file is either JSON or TOML, a flat table of strings:
+//!
+//! ```toml
+//! "pmid:12345" = "smith2020"
+//! doe2019a = "doe2019"
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+/// Errors that can occur when loading an alias file.
+#[derive(Error, Debug)]
+pub enum AliasError {
+    #[error("Failed to read file: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Invalid JSON: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("Invalid aliases: {0}")]
+    Invalid(String),
+}
+
+/// A map from old citation keys to reference ids.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyAliases {
+    map: HashMap<String, String>,
+}
+
+impl KeyAliases {
+    /// Loads aliases from a file, read as TOML if its extension is `.toml`
+    /// and as JSON otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the alias file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a table of strings.
+    pub fn load(path: &Path) -> Result<Self, AliasError> {
+        let content = fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::parse_toml(&content),
+            _ => Self::parse_json(&content),
+        }
+    }
+
+    /// Parses aliases from a JSON object of strings.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use csl_tools::aliases::KeyAliases;
+    ///
+    /// let aliases = KeyAliases::parse_json(r#"{"pmid:123": "smith2020"}"#).unwrap();
+    /// assert_eq!(aliases.get("pmid:123"), Some("smith2020"));
+    /// ```
+    pub fn parse_json(json: &str) -> Result<Self, AliasError> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let object = value
+            .as_object()
+            .ok_or_else(|| AliasError::Invalid("aliases must be a JSON object".to_string()))?;
+        let mut map = HashMap::with_capacity(object.len());
+        for (alias, id) in object {
+            let id = id.as_str().ok_or_else(|| {
+                AliasError::Invalid(format!("the alias '{}' must map to a string", alias))
+            })?;
+            map.insert(alias.clone(), id.to_string());
+        }
+        Ok(KeyAliases { map })
+    }
+
+    /// Parses aliases from a TOML table of strings.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use csl_tools::aliases::KeyAliases;
+    ///
+    /// let aliases = KeyAliases::parse_toml("\"pmid:123\" = \"smith2020\"\n").unwrap();
+    /// assert_eq!(aliases.get("pmid:123"), Some("smith2020"));
+    /// ```
+    pub fn parse_toml(toml: &str) -> Result<Self, AliasError> {
+        let map: HashMap<String, String> =
+            toml::from_str(toml).map_err(|e| AliasError::Invalid(e.to_string()))?;
+        Ok(KeyAliases { map })
+    }
+
+    /// Returns the reference id an old key stands for, if it is an alias.
+    pub fn get(&self, alias: &str) -> Option<&str> {
+        self.map.get(alias).map(String::as_str)
+    }
+
+    /// Returns `true` if there are no aliases.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Resolves a citation key to a reference id.
+    ///
+    /// A key that is a reference id is kept, even if it is also an alias;
+    /// otherwise the id it is an alias of is returned. Unknown keys are kept,
+    /// to be reported as missing.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The citation key
+    /// * `ids` - The ids of the references
+    ///
+    /// # Returns
+    ///
+    /// The id to cite for the key.
+    pub fn resolve<'a>(&'a self, key: &'a str, ids: &HashSet<&str>) -> &'a str {
+        if ids.contains(key) {
+            return key;
+        }
+        match self.get(key) {
+            Some(id) if ids.contains(id) => id,
+            _ => key,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_toml_aliases() {
+        // Given: quoted and bare keys, comments and both kinds of strings
+        let toml = r#"
+# Renamed in 2024
+"pmid:12345" = "smith2020"  # Smith's trial
+doe2019a = 'doe2019'
+"#;
+
+        // When: parsing the aliases
+        let aliases = KeyAliases::parse_toml(toml).unwrap();
+
+        // Then: every key maps to its new id
+        assert_eq!(aliases.get("pmid:12345"), Some("smith2020"));
+        assert_eq!(aliases.get("doe2019a"), Some("doe2019"));
+        assert_eq!(aliases.get("smith2020"), None);
+    }
+
+    #[test]
+    fn test_parse_toml_errors() {
+        // Given: a value that is not a string and a key given twice
+        let not_string = "\"pmid:1\" = 1\n";
+        let duplicate = "a = \"x\"\na = \"y\"\n";
+
+        // When/Then: both are rejected
+        assert!(matches!(
+            KeyAliases::parse_toml(not_string),
+            Err(AliasError::Invalid(_))
+        ));
+        assert!(matches!(
+            KeyAliases::parse_toml(duplicate),
+            Err(AliasError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_json_aliases() {
+        // Given: a JSON object, and one whose value is not a string
+        let json = r#"{"pmid:12345": "smith2020", "doe2019a": "doe2019"}"#;
+        let invalid = r#"{"pmid:12345": 1}"#;
+
+        // When: parsing them
+        let aliases = KeyAliases::parse_json(json).unwrap();
+
+        // Then: the object is read, the other rejected
+        assert_eq!(aliases.get("pmid:12345"), Some("smith2020"));
+        assert_eq!(aliases.get("doe2019a"), Some("doe2019"));
+        assert!(matches!(
+            KeyAliases::parse_json(invalid),
+            Err(AliasError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_prefers_reference_ids() {
+        // Given: an alias that is also the id of another reference
+        let aliases =
+            KeyAliases::parse_json(r#"{"pmid:1": "smith2020", "doe2019": "smith2020"}"#).unwrap();
+        let ids: HashSet<&str> = ["smith2020", "doe2019"].into();
+
+        // When/Then: ids are kept, aliases resolved, unknown keys kept
+        assert_eq!(aliases.resolve("doe2019", &ids), "doe2019");
+        assert_eq!(aliases.resolve("pmid:1", &ids), "smith2020");
+        assert_eq!(aliases.resolve("pmid:2", &ids), "pmid:2");
+    }
+}
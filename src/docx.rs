@@ -0,0 +1,713 @@
+//! Word documents (DOCX).
+//!
+//! The text of `word/document.xml` is read paragraph by paragraph, runs
+//! joined, so that a `[@key]` marker split across runs (by Word's spelling
+//! checker, or a formatting change) is still found. Replacements go into the
+//! run where each marker starts, which keeps its formatting; the other parts
+//! of the package are copied as they are.
+
+use crate::output::{bibliography_entries, convert_html, decode_entity};
+use crate::processor::ProcessedCitation;
+use std::io::{Cursor, Read, Write};
+use std::ops::Range;
+use thiserror::Error;
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Path of the main document part in the package.
+const DOCUMENT_PART: &str = "word/document.xml";
+
+/// Errors that can occur while reading a DOCX package.
+#[derive(Error, Debug)]
+pub enum DocxError {
+    #[error("Invalid DOCX package: {0}")]
+    Package(#[from] ZipError),
+
+    #[error("Invalid DOCX package: no {DOCUMENT_PART}")]
+    MissingDocument,
+
+    #[error("Invalid DOCX package: {DOCUMENT_PART} is not UTF-8")]
+    Encoding,
+}
+
+/// The text of a `<w:t>` element.
+#[derive(Debug, Clone, PartialEq)]
+struct TextPiece {
+    /// The `<w:t ...>` start tag, in the XML
+    tag: Range<usize>,
+    /// The element content, in the XML
+    content: Range<usize>,
+    /// The decoded content, in the document text
+    text: Range<usize>,
+}
+
+/// A `<w:p>` element.
+#[derive(Debug, Clone, PartialEq)]
+struct Paragraph {
+    /// The whole element, in the XML
+    xml: Range<usize>,
+    /// Its text, in the document text
+    text: Range<usize>,
+}
+
+/// An opened DOCX package.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Docx {
+    /// The package, as read
+    archive: Vec<u8>,
+    /// The content of `word/document.xml`
+    xml: String,
+    /// The text of the document, paragraphs separated by blank lines
+    text: String,
+    pieces: Vec<TextPiece>,
+    paragraphs: Vec<Paragraph>,
+}
+
+impl Docx {
+    /// Opens a DOCX package and reads the text of its main document.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive` - The bytes of the .docx file
+    ///
+    /// # Returns
+    ///
+    /// The document, or an error if the package cannot be read.
+    pub fn open(archive: Vec<u8>) -> Result<Self, DocxError> {
+        let xml = read_document(&archive)?;
+
+        let mut docx = Docx {
+            archive,
+            xml,
+            text: String::new(),
+            pieces: Vec::new(),
+            paragraphs: Vec::new(),
+        };
+        docx.read_text();
+        Ok(docx)
+    }
+
+    /// Returns the text of the document: the text of each paragraph, its
+    /// runs joined, followed by a blank line.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the number of the paragraph holding a position of `text()`,
+    /// counting the paragraphs of the document from 1.
+    pub fn paragraph_number(&self, offset: usize) -> usize {
+        self.paragraphs
+            .iter()
+            .filter(|paragraph| paragraph.text.end <= offset)
+            .count()
+            + 1
+    }
+
+    /// Writes `word/document.xml` with the citations replaced and the
+    /// bibliography added.
+    ///
+    /// Each replacement goes into the run where its citation starts, and the
+    /// rest of the citation is removed from the following runs. The
+    /// bibliography, one paragraph per entry, replaces a paragraph holding
+    /// only a `<!-- bibliography -->` or `::: {#refs}` marker; without one,
+    /// it is appended to the body under a `bib_header` heading.
+    ///
+    /// # Arguments
+    ///
+    /// * `replacements` - The replacements (plain text), with spans in `text()`
+    /// * `bibliography` - The bibliography rendered by csl_proc (HTML), if any
+    /// * `bib_header` - The heading of an appended bibliography
+    ///
+    /// # Returns
+    ///
+    /// The XML of the main document.
+    pub fn render_document(
+        &self,
+        replacements: &[ProcessedCitation],
+        bibliography: Option<&str>,
+        bib_header: &str,
+    ) -> String {
+        let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+
+        for piece in &self.pieces {
+            let text = self.replaced_text(piece, replacements);
+            if text == self.text[piece.text.clone()] {
+                continue;
+            }
+            let tag = &self.xml[piece.tag.clone()];
+            if !tag.contains("xml:space") {
+                edits.push((
+                    piece.tag.clone(),
+                    format!("{} xml:space=\"preserve\">", &tag[..tag.len() - 1]),
+                ));
+            }
+            edits.push((piece.content.clone(), escape_xml(&text)));
+        }
+
+        if let Some(html) = bibliography.filter(|bib| !bib.is_empty()) {
+            let paragraphs = bibliography_paragraphs(html);
+            let marker = self
+                .paragraphs
+                .iter()
+                .find(|p| is_marker(&self.text[p.text.clone()]));
+            match marker {
+                Some(paragraph) => {
+                    let range = paragraph.xml.clone();
+                    edits.retain(|(edit, _)| edit.end <= range.start || edit.start >= range.end);
+                    edits.push((range, paragraphs));
+                }
+                None => {
+                    let at = self.body_end();
+                    edits.push((
+                        at..at,
+                        format!("{}{}", heading_paragraph(bib_header), paragraphs),
+                    ));
+                }
+            }
+        }
+
+        edits.sort_by_key(|(range, _)| range.start);
+        let mut xml = String::with_capacity(self.xml.len());
+        let mut copied = 0;
+        for (range, replacement) in edits {
+            xml.push_str(&self.xml[copied..range.start]);
+            xml.push_str(&replacement);
+            copied = range.end;
+        }
+        xml.push_str(&self.xml[copied..]);
+        xml
+    }
+
+    /// Writes the package with a new main document; the other parts are
+    /// copied byte for byte.
+    ///
+    /// # Arguments
+    ///
+    /// * `document_xml` - The XML of the main document (see `render_document`)
+    ///
+    /// # Returns
+    ///
+    /// The bytes of the .docx file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package cannot be read again.
+    pub fn package(&self, document_xml: &str) -> Result<Vec<u8>, DocxError> {
+        let mut zip = ZipArchive::new(Cursor::new(self.archive.as_slice()))?;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..zip.len() {
+            let entry = zip.by_index_raw(i)?;
+            if entry.name() == DOCUMENT_PART {
+                let options =
+                    FileOptions::default().compression_method(CompressionMethod::Deflated);
+                writer.start_file(DOCUMENT_PART, options)?;
+                writer
+                    .write_all(document_xml.as_bytes())
+                    .map_err(ZipError::from)?;
+            } else {
+                writer.raw_copy_file(entry)?;
+            }
+        }
+        Ok(writer.finish()?.into_inner())
+    }
+
+    /// Reads the text of the paragraphs, and where it comes from.
+    fn read_text(&mut self) {
+        let xml = &self.xml;
+        let mut open_paragraphs: Vec<(usize, usize)> = Vec::new();
+        let mut pos = 0;
+
+        while let Some(offset) = xml[pos..].find('<') {
+            let start = pos + offset;
+            let Some(len) = xml[start..].find('>') else {
+                break;
+            };
+            let end = start + len + 1;
+            let tag = &xml[start + 1..end - 1];
+            pos = end;
+
+            let (closing, tag) = match tag.strip_prefix('/') {
+                Some(tag) => (true, tag),
+                None => (false, tag),
+            };
+            let self_closing = tag.ends_with('/');
+            let name = tag
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or("");
+
+            match (name, closing) {
+                ("w:p", false) if self_closing => {
+                    let at = self.text.len();
+                    self.paragraphs.push(Paragraph {
+                        xml: start..end,
+                        text: at..at,
+                    });
+                    self.text.push_str("\n\n");
+                }
+                ("w:p", false) => open_paragraphs.push((start, self.text.len())),
+                ("w:p", true) => {
+                    if let Some((xml_start, text_start)) = open_paragraphs.pop() {
+                        self.paragraphs.push(Paragraph {
+                            xml: xml_start..end,
+                            text: text_start..self.text.len(),
+                        });
+                    }
+                    self.text.push_str("\n\n");
+                }
+                ("w:t", false) if !self_closing => {
+                    let Some(len) = xml[end..].find("</w:t>") else {
+                        break;
+                    };
+                    let content = end..end + len;
+                    let text_start = self.text.len();
+                    self.text.push_str(&decode_xml(&xml[content.clone()]));
+                    self.pieces.push(TextPiece {
+                        tag: start..end,
+                        content: content.clone(),
+                        text: text_start..self.text.len(),
+                    });
+                    pos = content.end + "</w:t>".len();
+                }
+                // Fallbacks repeat the content of their alternative (for
+                // older versions of Word)
+                ("mc:Fallback", false) if !self_closing => {
+                    pos = xml[end..]
+                        .find("</mc:Fallback>")
+                        .map_or(xml.len(), |len| end + len);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the text of a piece with the replacements applied: each one
+    /// goes where its citation starts, and covered text is dropped.
+    fn replaced_text(&self, piece: &TextPiece, replacements: &[ProcessedCitation]) -> String {
+        let Range { start, end } = piece.text;
+        let mut text = String::new();
+        let mut pos = start;
+        for replacement in replacements {
+            let (r_start, r_end) = replacement.original_span;
+            if r_end <= start || r_start >= end {
+                continue;
+            }
+            if r_start >= start {
+                text.push_str(&self.text[pos..r_start]);
+                text.push_str(&replacement.formatted);
+            }
+            pos = pos.max(r_end.min(end));
+        }
+        text.push_str(&self.text[pos..end]);
+        text
+    }
+
+    /// Returns where paragraphs can be appended to the body: before its
+    /// final section properties, or before `</w:body>`.
+    fn body_end(&self) -> usize {
+        let body_end = self.xml.rfind("</w:body>").unwrap_or(self.xml.len());
+        match self.xml[..body_end].rfind("<w:sectPr") {
+            Some(pos) if !self.xml[pos..body_end].contains("</w:p>") => pos,
+            _ => body_end,
+        }
+    }
+}
+
+/// Reads `word/document.xml` from a package.
+///
+/// At most one byte past the declared size of the entry is inflated, so that
+/// a crafted package cannot exhaust memory; reading a complete entry checks
+/// its CRC.
+fn read_document(archive: &[u8]) -> Result<String, DocxError> {
+    let mut zip = ZipArchive::new(Cursor::new(archive))?;
+    let mut document = match zip.by_name(DOCUMENT_PART) {
+        Ok(document) => document,
+        Err(ZipError::FileNotFound) => return Err(DocxError::MissingDocument),
+        Err(e) => return Err(e.into()),
+    };
+    let size = document.size();
+    let mut contents = Vec::new();
+    (&mut document)
+        .take(size + 1)
+        .read_to_end(&mut contents)
+        .map_err(ZipError::from)?;
+    if contents.len() as u64 > size {
+        return Err(ZipError::InvalidArchive("entry larger than its declared size").into());
+    }
+    String::from_utf8(contents).map_err(|_| DocxError::Encoding)
+}
+
+/// Converts formatted text (csl_proc HTML) to plain text, for a run.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::docx::html_to_text;
+///
+/// assert_eq!(html_to_text("(<i>Nature</i>, 2020 &amp; 2021)"), "(Nature, 2020 & 2021)");
+/// ```
+pub fn html_to_text(html: &str) -> String {
+    convert_html(html, |_| ("", ""), |c, text| text.push(c))
+}
+
+/// Run properties, in schema order, and the characters that switch them on
+/// and off while converting HTML.
+const RUN_PROPERTIES: [(&str, &str, &str); 5] = [
+    ("<w:b/>", "\u{1}", "\u{2}"),
+    ("<w:i/>", "\u{3}", "\u{4}"),
+    ("<w:smallCaps/>", "\u{5}", "\u{6}"),
+    ("<w:vertAlign w:val=\"superscript\"/>", "\u{7}", "\u{8}"),
+    ("<w:vertAlign w:val=\"subscript\"/>", "\u{b}", "\u{c}"),
+];
+
+/// Returns the characters that open and close an HTML tag (given without
+/// its angle brackets) in `html_to_runs`.
+fn run_property(tag: &str) -> (&'static str, &'static str) {
+    let name = tag.split_whitespace().next().unwrap_or("");
+    let index = match name {
+        "b" | "strong" => 0,
+        "i" | "em" => 1,
+        "span" if tag.contains("small-caps") => 2,
+        "sup" => 3,
+        "sub" => 4,
+        // Nested blocks (csl-left-margin, csl-right-inline) are separated by a space
+        "div" => return ("", " "),
+        _ => return ("", ""),
+    };
+    (RUN_PROPERTIES[index].1, RUN_PROPERTIES[index].2)
+}
+
+/// Converts formatted text (csl_proc HTML) to runs: italic, bold, small caps,
+/// superscripts and subscripts become run properties. Whitespace collapses
+/// as in HTML.
+fn html_to_runs(html: &str) -> String {
+    let marked = convert_html(html, run_property, |c, text| match c {
+        '\u{a0}' => text.push(c),
+        c if c.is_whitespace() => text.push(' '),
+        c => text.push(c),
+    });
+
+    let mut runs = String::new();
+    let mut active = [0i32; RUN_PROPERTIES.len()];
+    let mut text = String::new();
+    let flush = |text: &mut String, active: &[i32], runs: &mut String| {
+        if text.is_empty() {
+            return;
+        }
+        let properties: String = RUN_PROPERTIES
+            .iter()
+            .zip(active)
+            .filter(|(_, &count)| count > 0)
+            .map(|(property, _)| property.0)
+            .collect();
+        runs.push_str("<w:r>");
+        if !properties.is_empty() {
+            runs.push_str(&format!("<w:rPr>{}</w:rPr>", properties));
+        }
+        runs.push_str(&format!(
+            "<w:t xml:space=\"preserve\">{}</w:t></w:r>",
+            escape_xml(text)
+        ));
+        text.clear();
+    };
+
+    // Spaces collapse, across tags too
+    let mut last = '\0';
+    for c in marked.trim().chars() {
+        let switch = RUN_PROPERTIES.iter().enumerate().find_map(|(i, property)| {
+            if property.1.starts_with(c) {
+                Some((i, 1))
+            } else if property.2.starts_with(c) {
+                Some((i, -1))
+            } else {
+                None
+            }
+        });
+        match switch {
+            Some((i, change)) => {
+                flush(&mut text, &active, &mut runs);
+                active[i] += change;
+            }
+            None if c == ' ' && last == ' ' => {}
+            None => {
+                text.push(c);
+                last = c;
+            }
+        }
+    }
+    flush(&mut text, &active, &mut runs);
+    runs
+}
+
+/// Returns the bibliography (csl_proc HTML) as paragraphs, one per entry.
+fn bibliography_paragraphs(html: &str) -> String {
+    let mut entries = bibliography_entries(html);
+    if entries.is_empty() {
+        entries.push(html);
+    }
+    entries
+        .into_iter()
+        .map(|entry| {
+            format!(
+                "<w:p><w:pPr><w:pStyle w:val=\"Bibliography\"/></w:pPr>{}</w:p>",
+                html_to_runs(entry)
+            )
+        })
+        .collect()
+}
+
+/// Whether a paragraph holds only a bibliography marker, a
+/// `<!-- bibliography -->` comment or a `::: {#refs}` div fence.
+///
+/// Markdown code-block rules do not apply: the text of a Word paragraph is
+/// matched as it is, whatever its indentation or the paragraphs before it.
+fn is_marker(text: &str) -> bool {
+    let text = text.trim();
+    if let Some(comment) = text
+        .strip_prefix("<!--")
+        .and_then(|t| t.strip_suffix("-->"))
+    {
+        return comment.trim() == "bibliography";
+    }
+    let fence = text.trim_start_matches(':');
+    let attributes = fence
+        .trim_start()
+        .strip_prefix("{#refs")
+        .and_then(|t| t.strip_suffix('}'));
+    text.len() - fence.len() >= 3
+        && matches!(attributes, Some(a) if a.is_empty() || a.starts_with(char::is_whitespace))
+}
+
+/// Returns a heading paragraph.
+fn heading_paragraph(heading: &str) -> String {
+    format!(
+        "<w:p><w:pPr><w:pStyle w:val=\"Heading1\"/></w:pPr>\
+         <w:r><w:t xml:space=\"preserve\">{}</w:t></w:r></w:p>",
+        escape_xml(heading)
+    )
+}
+
+/// Decodes the entities of XML character data.
+fn decode_xml(xml: &str) -> String {
+    let mut text = String::with_capacity(xml.len());
+    let mut rest = xml;
+    while let Some(c) = rest.chars().next() {
+        let (decoded, len) = if c == '&' {
+            decode_entity(rest).unwrap_or((c, 1))
+        } else {
+            (c, c.len_utf8())
+        };
+        text.push(decoded);
+        rest = &rest[len..];
+    }
+    text
+}
+
+/// Escapes text for XML character data.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a package whose main document has this body.
+    fn package(body: &str) -> Vec<u8> {
+        let xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+             <w:document xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\">\
+             <w:body>{}<w:sectPr><w:pgSz w:w=\"11906\" w:h=\"16838\"/></w:sectPr></w:body></w:document>",
+            body
+        );
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in [("[Content_Types].xml", "<Types/>"), (DOCUMENT_PART, &xml)] {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// Returns the replacement of `marker` (in `text`) by `formatted`.
+    fn replace(text: &str, marker: &str, formatted: &str) -> ProcessedCitation {
+        let start = text.find(marker).unwrap();
+        ProcessedCitation {
+            original_span: (start, start + marker.len()),
+            formatted: formatted.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_open_joins_runs() {
+        // Given: A citation split across runs, and an escaped ampersand
+        let docx = Docx::open(package(
+            "<w:p><w:r><w:t xml:space=\"preserve\">See [@smi</w:t></w:r><w:proofErr w:type=\"spellStart\"/>\
+             <w:r><w:rPr><w:i/></w:rPr><w:t>th2020]</w:t></w:r></w:p>\
+             <w:p><w:r><w:t>R&amp;D</w:t></w:r></w:p>",
+        ))
+        .unwrap();
+
+        // Then: The text joins the runs, paragraph by paragraph
+        assert_eq!(docx.text(), "See [@smith2020]\n\nR&D\n\n");
+    }
+
+    #[test]
+    fn test_open_rejects_entry_larger_than_declared() {
+        // Given: A package whose main document claims to be 10 bytes long
+        let mut archive = package("<w:p><w:r><w:t>Text</w:t></w:r></w:p>");
+        let central = archive
+            .windows(4)
+            .rposition(|bytes| bytes == b"PK\x01\x02")
+            .unwrap();
+        archive[central + 24..central + 28].copy_from_slice(&10u32.to_le_bytes());
+
+        // When: We open it
+        let result = Docx::open(archive);
+
+        // Then: It is rejected rather than read past the declared size
+        assert!(matches!(result, Err(DocxError::Package(_))));
+    }
+
+    #[test]
+    fn test_paragraph_number() {
+        // Given: A citation in the third paragraph, after an empty one
+        let docx = Docx::open(package(
+            "<w:p><w:r><w:t>Intro.</w:t></w:r></w:p><w:p/>\
+             <w:p><w:r><w:t>See [@smith2020].</w:t></w:r></w:p>",
+        ))
+        .unwrap();
+
+        // When: We look up the paragraph of the citation
+        let offset = docx.text().find("[@").unwrap();
+
+        // Then: It is the third one
+        assert_eq!(docx.paragraph_number(0), 1);
+        assert_eq!(docx.paragraph_number(offset), 3);
+    }
+
+    #[test]
+    fn test_render_keeps_run_formatting() {
+        // Given: A citation split across a bold and an italic run
+        let docx = Docx::open(package(
+            "<w:p><w:r><w:rPr><w:b/></w:rPr><w:t>See [@smi</w:t></w:r>\
+             <w:r><w:rPr><w:i/></w:rPr><w:t>th2020] now</w:t></w:r></w:p>",
+        ))
+        .unwrap();
+        let replacements = vec![replace(docx.text(), "[@smith2020]", "(Smith & Lee)")];
+
+        // When: We render the document
+        let xml = docx.render_document(&replacements, None, "References");
+
+        // Then: The citation is replaced in its first run, the rest of it
+        // removed from the second run, and both runs keep their formatting
+        assert!(xml.contains(
+            "<w:r><w:rPr><w:b/></w:rPr><w:t xml:space=\"preserve\">See (Smith &amp; Lee)</w:t></w:r>\
+             <w:r><w:rPr><w:i/></w:rPr><w:t xml:space=\"preserve\"> now</w:t></w:r>"
+        ));
+    }
+
+    #[test]
+    fn test_render_bibliography() {
+        // Given: A document without a bibliography marker
+        let docx = Docx::open(package("<w:p><w:r><w:t>Text [@a]</w:t></w:r></w:p>")).unwrap();
+        let bibliography = "<div class=\"csl-bib-body\">\n  \
+            <div class=\"csl-entry\">Doe, J. <i>A Title</i>.</div>\n</div>";
+
+        // When: We render it with a bibliography
+        let xml = docx.render_document(&[], Some(bibliography), "References");
+
+        // Then: A heading and the entries are appended before the section
+        // properties, formatting as run properties
+        assert!(xml.contains(
+            "<w:p><w:pPr><w:pStyle w:val=\"Heading1\"/></w:pPr><w:r><w:t xml:space=\"preserve\">References</w:t></w:r></w:p>\
+             <w:p><w:pPr><w:pStyle w:val=\"Bibliography\"/></w:pPr>\
+             <w:r><w:t xml:space=\"preserve\">Doe, J. </w:t></w:r>\
+             <w:r><w:rPr><w:i/></w:rPr><w:t xml:space=\"preserve\">A Title</w:t></w:r>\
+             <w:r><w:t xml:space=\"preserve\">.</w:t></w:r></w:p><w:sectPr>"
+        ));
+    }
+
+    #[test]
+    fn test_render_bibliography_at_marker_paragraph() {
+        // Given: A paragraph holding only a bibliography marker
+        let docx = Docx::open(package(
+            "<w:p><w:r><w:t>Text</w:t></w:r></w:p>\
+             <w:p><w:pPr><w:jc w:val=\"left\"/></w:pPr><w:r><w:t>&lt;!-- bibliography --&gt;</w:t></w:r></w:p>",
+        ))
+        .unwrap();
+
+        // When: We render it with a bibliography
+        let xml = docx.render_document(
+            &[],
+            Some("<div class=\"csl-entry\">Entry</div>"),
+            "References",
+        );
+
+        // Then: The marker paragraph is replaced, without a heading
+        assert!(xml.contains(
+            "<w:p><w:r><w:t>Text</w:t></w:r></w:p><w:p><w:pPr><w:pStyle w:val=\"Bibliography\"/></w:pPr>\
+             <w:r><w:t xml:space=\"preserve\">Entry</w:t></w:r></w:p><w:sectPr>"
+        ));
+        assert!(!xml.contains("Heading1"));
+    }
+
+    #[test]
+    fn test_is_marker() {
+        // Given/When/Then: Both marker forms are found whatever their
+        // indentation, other text is not a marker
+        assert!(is_marker("<!-- bibliography -->"));
+        assert!(is_marker("      <!--bibliography-->  "));
+        assert!(is_marker("::: {#refs}"));
+        assert!(is_marker("    :::: {#refs .hanging}"));
+        assert!(!is_marker(":: {#refs}"));
+        assert!(!is_marker("::: {#refsection}"));
+        assert!(!is_marker("See <!-- bibliography -->"));
+    }
+
+    #[test]
+    fn test_render_bibliography_at_marker_after_backticks() {
+        // Given: A marker paragraph after a paragraph of backticks, which
+        // would open a code block in Markdown
+        let docx = Docx::open(package(
+            "<w:p><w:r><w:t>```</w:t></w:r></w:p>\
+             <w:p><w:r><w:t>&lt;!-- bibliography --&gt;</w:t></w:r></w:p>",
+        ))
+        .unwrap();
+
+        // When: We render it with a bibliography
+        let xml = docx.render_document(
+            &[],
+            Some("<div class=\"csl-entry\">Entry</div>"),
+            "References",
+        );
+
+        // Then: The marker paragraph is replaced
+        assert!(!xml.contains("bibliography --"));
+        assert!(!xml.contains("Heading1"));
+    }
+
+    #[test]
+    fn test_package_copies_other_parts() {
+        // Given: An opened package
+        let docx = Docx::open(package("<w:p><w:r><w:t>[@a]</w:t></w:r></w:p>")).unwrap();
+
+        // When: We write it with a new main document
+        let replacements = vec![replace(docx.text(), "[@a]", "(1)")];
+        let written = docx
+            .package(&docx.render_document(&replacements, None, "References"))
+            .unwrap();
+
+        // Then: It opens again, with the new text and the other parts
+        let reopened = Docx::open(written.clone()).unwrap();
+        assert_eq!(reopened.text(), "(1)\n\n");
+        let mut zip = ZipArchive::new(Cursor::new(written)).unwrap();
+        let mut types = String::new();
+        let mut entry = zip.by_index(0).unwrap();
+        assert_eq!(entry.name(), "[Content_Types].xml");
+        entry.read_to_string(&mut types).unwrap();
+        assert_eq!(types, "<Types/>");
+    }
+}
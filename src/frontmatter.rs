@@ -0,0 +1,289 @@
+//! YAML front matter.
+//!
+//! A Markdown document may start with a YAML block, as in Pandoc:
+//!
+//! ```text
+//! ---
+//! bibliography: refs.json
+//! csl: apa.csl
+//! ---
+//! ```
+//!
+//! Only the keys used by csl-tools are read (`bibliography`, `csl`, `lang`,
+//! `link-citations`, `nocite`, `reference-section-title`); other keys are
+//! ignored, but the whole block must be valid YAML.
+
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Errors that can occur while reading the front matter.
+#[derive(Error, Debug)]
+pub enum FrontMatterError {
+    #[error("Invalid YAML front matter: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+}
+
+/// Settings read from the YAML front matter of a document.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrontMatter {
+    /// Bibliography files (`bibliography:`, a path or a list of paths)
+    pub bibliography: Vec<String>,
+    /// CSL style: path or builtin name (`csl:`)
+    pub csl: Option<String>,
+    /// Locale of the document, e.g. "fr-FR" (`lang:`)
+    pub lang: Option<String>,
+    /// Whether citations link to their bibliography entry (`link-citations:`)
+    pub link_citations: Option<bool>,
+    /// Entries of `nocite:`, as written (e.g., "@a, @b" or "@*")
+    pub nocite: Vec<String>,
+    /// Title of the bibliography section (`reference-section-title:`)
+    pub reference_section_title: Option<String>,
+    /// Start and end byte positions of the whole block, delimiters included
+    pub span: (usize, usize),
+}
+
+/// The front matter keys, as written.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct RawFrontMatter {
+    bibliography: OneOrMany,
+    csl: Option<String>,
+    lang: Option<String>,
+    link_citations: Option<bool>,
+    nocite: OneOrMany,
+    reference_section_title: Option<String>,
+}
+
+/// A key given a single value or a list of values.
+#[derive(Debug, Default, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    #[default]
+    None,
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<OneOrMany> for Vec<String> {
+    fn from(value: OneOrMany) -> Self {
+        match value {
+            OneOrMany::None => Vec::new(),
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+/// Parses the YAML front matter at the start of `markdown`.
+///
+/// # Arguments
+///
+/// * `markdown` - The Markdown document
+///
+/// # Returns
+///
+/// The front matter settings, or `None` if the document does not start with
+/// a YAML block.
+///
+/// # Errors
+///
+/// Returns an error if the block is not valid YAML, or if a key read by
+/// csl-tools has a value of the wrong type.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::frontmatter::parse_front_matter;
+///
+/// let markdown = "---\nbibliography: refs.json\ncsl: apa.csl\n---\n# Title\n";
+/// let front = parse_front_matter(markdown).unwrap().unwrap();
+/// assert_eq!(front.bibliography, vec!["refs.json"]);
+/// assert_eq!(front.csl.as_deref(), Some("apa.csl"));
+/// assert_eq!(&markdown[front.span.1..], "# Title\n");
+/// ```
+pub fn parse_front_matter(markdown: &str) -> Result<Option<FrontMatter>, FrontMatterError> {
+    let Some((span, yaml)) = front_matter_block(markdown) else {
+        return Ok(None);
+    };
+    let raw: RawFrontMatter = serde_yaml::from_str(yaml)?;
+    Ok(Some(FrontMatter {
+        bibliography: raw.bibliography.into(),
+        csl: raw.csl,
+        lang: raw.lang,
+        link_citations: raw.link_citations,
+        nocite: raw.nocite.into(),
+        reference_section_title: raw.reference_section_title,
+        span,
+    }))
+}
+
+/// Returns the span of the front matter block at the start of `markdown`,
+/// without parsing it.
+pub fn front_matter_span(markdown: &str) -> Option<(usize, usize)> {
+    front_matter_block(markdown).map(|(span, _)| span)
+}
+
+/// Finds the front matter block: a `---` first line, YAML starting with a
+/// `key:` line, and a closing `---` or `...` line.
+///
+/// Returns the span of the block (closing line and its newline included) and
+/// the YAML text between the delimiters.
+fn front_matter_block(markdown: &str) -> Option<((usize, usize), &str)> {
+    let start = if markdown.starts_with('\u{feff}') {
+        3
+    } else {
+        0
+    };
+    let mut lines = markdown[start..].split_inclusive('\n');
+    let first = lines.next()?;
+    if first.trim_end() != "---" {
+        return None;
+    }
+
+    let yaml_start = start + first.len();
+    let mut offset = yaml_start;
+    for line in lines {
+        let line_start = offset;
+        offset += line.len();
+        if matches!(line.trim_end(), "---" | "...") {
+            let yaml = &markdown[yaml_start..line_start];
+            // A thematic break followed by text is not front matter
+            let first_entry = yaml
+                .lines()
+                .find(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#'))?;
+            if !is_mapping_entry(first_entry) {
+                return None;
+            }
+            return Some(((0, offset), yaml));
+        }
+    }
+
+    None
+}
+
+/// Tells whether a line starts a `key: value` entry, with a plain,
+/// single-quoted or double-quoted key.
+fn is_mapping_entry(line: &str) -> bool {
+    let rest = match line.chars().next() {
+        Some(quote @ ('"' | '\'')) => match line[1..].find(quote) {
+            Some(end) => &line[end + 2..],
+            None => return false,
+        },
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            line.trim_start_matches(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        }
+        _ => return false,
+    };
+    rest.strip_prefix(':')
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t', '\r', '\n']))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_all_keys() {
+        // Given: A front matter using every supported key
+        let markdown = "---
+title: \"A title: with colon\"
+bibliography: refs.json
+csl: 'apa.csl' # comment
+lang: fr-FR
+link-citations: true
+nocite: |
+  @a, @b
+reference-section-title: Bibliographie
+---
+Body";
+
+        // When: We parse it
+        let front = parse_front_matter(markdown).unwrap().unwrap();
+
+        // Then: Every value is read
+        assert_eq!(front.bibliography, vec!["refs.json"]);
+        assert_eq!(front.csl.as_deref(), Some("apa.csl"));
+        assert_eq!(front.lang.as_deref(), Some("fr-FR"));
+        assert_eq!(front.link_citations, Some(true));
+        assert_eq!(front.nocite, vec!["@a, @b"]);
+        assert_eq!(
+            front.reference_section_title.as_deref(),
+            Some("Bibliographie")
+        );
+        assert_eq!(&markdown[front.span.1..], "Body");
+    }
+
+    #[test]
+    fn test_parse_lists() {
+        // Given: Block and flow lists
+        let markdown = "---
+bibliography:
+  - a.json
+  - \"b c.json\"
+nocite: [\"@x\", '@y']
+author:
+  name: Someone
+...
+";
+
+        // When: We parse it
+        let front = parse_front_matter(markdown).unwrap().unwrap();
+
+        // Then: Both list forms are read, mappings are ignored
+        assert_eq!(front.bibliography, vec!["a.json", "b c.json"]);
+        assert_eq!(front.nocite, vec!["@x", "@y"]);
+        assert_eq!(front.span, (0, markdown.len()));
+    }
+
+    #[test]
+    fn test_no_front_matter() {
+        // Given: Documents that do not start with a YAML block
+        let thematic_break = "---\nSome text\n---\n";
+        let unclosed = "---\ncsl: apa.csl\n# Title\n";
+        let later = "# Title\n---\ncsl: apa.csl\n---\n";
+
+        // Then: No front matter is found
+        assert_eq!(parse_front_matter(thematic_break).unwrap(), None);
+        assert_eq!(parse_front_matter(unclosed).unwrap(), None);
+        assert_eq!(parse_front_matter(later).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_full_yaml_syntax() {
+        // Given: A multi-line flow list, quoted keys, escapes and an alias
+        let markdown = "---
+\"csl\": \"caf\\u00e9\\tstyle.csl\"
+refs: &refs
+  - a.json
+  - b.json
+bibliography: *refs
+'lang': fr-FR
+nocite: [
+  \"@x\",
+  \"@y\"
+]
+---
+";
+
+        // When: We parse it
+        let front = parse_front_matter(markdown).unwrap().unwrap();
+
+        // Then: Every value is read as YAML defines it
+        assert_eq!(front.bibliography, vec!["a.json", "b.json"]);
+        assert_eq!(front.csl.as_deref(), Some("caf\u{e9}\tstyle.csl"));
+        assert_eq!(front.lang.as_deref(), Some("fr-FR"));
+        assert_eq!(front.nocite, vec!["@x", "@y"]);
+    }
+
+    #[test]
+    fn test_invalid_yaml_is_an_error() {
+        // Given: A front matter block that is not valid YAML
+        let markdown = "---\ncsl: [apa.csl\n---\nBody";
+
+        // When: We parse it
+        let result = parse_front_matter(markdown);
+
+        // Then: The error is reported
+        assert!(matches!(result, Err(FrontMatterError::Yaml(_))));
+    }
+}
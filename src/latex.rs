@@ -0,0 +1,480 @@
+//! LaTeX input.
+//!
+//! Recognises the citation commands of biblatex and natbib, so that a LaTeX
+//! document can be formatted with a CSL style instead of BibLaTeX:
+//!
+//! ```text
+//! \cite{a}  \parencite[see][p.~4]{a,b}  \textcite{a}  \citep[ch.~2]{a}
+//! \citet{a}  \autocite{a}
+//! ```
+//!
+//! Each command is a citation cluster, one item per key. A single optional
+//! argument is the postnote (a locator such as `p.~4`), two are the prenote
+//! and the postnote. `\textcite` and `\citet` are narrative citations; the
+//! others are parenthetical. Starred and capitalised variants (`\citet*`,
+//! `\Textcite`) are recognised too.
+//!
+//! The bibliography replaces `\printbibliography`. Comments and verbatim
+//! environments are left untouched.
+
+use crate::markdown::{
+    parse_locator, Citation, CitationCluster, CitationItem, CitationMode, CitationScan, LineIndex,
+};
+use crate::output::{bibliography_entries, convert_html};
+
+/// Citation commands and their mode.
+const CITE_COMMANDS: &[(&str, CitationMode)] = &[
+    ("cite", CitationMode::Normal),
+    ("parencite", CitationMode::Normal),
+    ("autocite", CitationMode::Normal),
+    ("citep", CitationMode::Normal),
+    ("textcite", CitationMode::AuthorInText),
+    ("citet", CitationMode::AuthorInText),
+];
+
+/// Environments whose content is not LaTeX prose.
+const VERBATIM_ENVIRONMENTS: &[&str] = &["verbatim", "Verbatim", "lstlisting", "minted", "comment"];
+
+/// Finds the citation commands of a LaTeX document.
+///
+/// # Arguments
+///
+/// * `tex` - The LaTeX source
+///
+/// # Returns
+///
+/// One cluster per citation command, and one citation per key (spanning the
+/// whole command), in document order.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::latex::scan_latex_citations;
+///
+/// let scan = scan_latex_citations(r"As \textcite{a} showed \parencite[see][p.~4]{b,c}.");
+/// assert_eq!(scan.clusters.len(), 2);
+/// assert_eq!(scan.clusters[1].items[0].prefix.as_deref(), Some("see"));
+/// assert_eq!(scan.clusters[1].items[1].locator.as_deref(), Some("4"));
+/// ```
+pub fn scan_latex_citations(tex: &str) -> CitationScan {
+    let prose = mask_non_prose(tex);
+    let mut index = LineIndex::new(tex);
+    let mut scan = CitationScan::default();
+    let mut i = 0;
+
+    while let Some(offset) = prose[i..].find('\\') {
+        let start = i + offset;
+        match parse_cite_command(&prose, start) {
+            Some((mut items, end)) => {
+                let position = index.position((start, end));
+                for item in &mut items {
+                    item.position = position;
+                }
+                scan.citations.extend(items.iter().map(|item| Citation {
+                    id: item.id.clone(),
+                    locator: item.locator.clone(),
+                    label: item.label.clone(),
+                    mode: item.mode,
+                    prefix: item.prefix.clone(),
+                    suffix: item.suffix.clone(),
+                    span: (start, end),
+                    position,
+                    ..Default::default()
+                }));
+                scan.clusters.push(CitationCluster {
+                    items,
+                    span: (start, end),
+                });
+                i = end;
+            }
+            // Skip the backslash and the character it escapes (`\\`, `\%`)
+            None => i = start + 1 + prose[start + 1..].chars().next().map_or(0, char::len_utf8),
+        }
+    }
+
+    scan
+}
+
+/// Parses the citation command starting at the backslash at `start`.
+///
+/// Returns its items and end position, or `None` if there is no citation
+/// command there.
+fn parse_cite_command(prose: &str, start: usize) -> Option<(Vec<CitationItem>, usize)> {
+    let name_len = prose[start + 1..]
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(prose.len() - start - 1);
+    let name = &prose[start + 1..start + 1 + name_len];
+    // Capitalised variants start a sentence: \Textcite, \Citet
+    let lowercase = name.get(..1)?.to_ascii_lowercase() + &name[1..];
+    let &(_, mode) = CITE_COMMANDS
+        .iter()
+        .find(|(command, _)| *command == lowercase)?;
+
+    let mut pos = start + 1 + name_len;
+    if prose[pos..].starts_with('*') {
+        pos += 1;
+    }
+
+    // Up to two optional arguments, then the keys
+    let mut notes = Vec::new();
+    loop {
+        pos += prose[pos..].len() - prose[pos..].trim_start().len();
+        match prose.as_bytes().get(pos) {
+            Some(b'[') if notes.len() < 2 => {
+                let (note, end) = group_argument(prose, pos, b']')?;
+                notes.push(note);
+                pos = end;
+            }
+            Some(b'{') => break,
+            _ => return None,
+        }
+    }
+    let (keys, end) = group_argument(prose, pos, b'}')?;
+
+    let ids: Vec<&str> = keys.split(',').map(str::trim).collect();
+    if ids.iter().any(|id| id.is_empty()) {
+        return None;
+    }
+
+    let (prenote, postnote) = match notes.as_slice() {
+        [postnote] => (None, Some(*postnote)),
+        [prenote, postnote] => (Some(*prenote), Some(*postnote)),
+        _ => (None, None),
+    };
+
+    let mut items: Vec<CitationItem> = ids
+        .into_iter()
+        .map(|id| CitationItem {
+            id: id.to_string(),
+            mode,
+            ..Default::default()
+        })
+        .collect();
+
+    // The prenote opens the cluster, the postnote closes it
+    if let Some(prenote) = prenote.map(latex_text).filter(|p| !p.is_empty()) {
+        items[0].prefix = Some(prenote);
+    }
+    if let Some(postnote) = postnote.map(latex_text).filter(|p| !p.is_empty()) {
+        let last = items
+            .last_mut()
+            .expect("a citation command has at least one key");
+        (last.locator, last.label, last.suffix) = match parse_locator(&postnote) {
+            (None, _, _) => (None, None, Some(postnote)),
+            parsed => parsed,
+        };
+    }
+
+    Some((items, end))
+}
+
+/// Reads the argument at `pos`, an opening delimiter, up to `close`. Nested
+/// braces must be balanced, and a paragraph break ends the search.
+///
+/// Returns the text between the delimiters and the end position.
+fn group_argument(prose: &str, pos: usize, close: u8) -> Option<(&str, usize)> {
+    let bytes = prose.as_bytes();
+    let mut depth = 0usize;
+    let mut i = pos + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            // An escaped character, such as `\}`
+            b'\\' => i += 1,
+            b'{' => depth += 1,
+            b'}' if depth > 0 => depth -= 1,
+            b if b == close && depth == 0 => return Some((&prose[pos + 1..i], i + 1)),
+            b'\n' if bytes[i - 1] == b'\n' => return None,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Turns a prenote or postnote into plain text: `p.~4` becomes "p. 4".
+fn latex_text(note: &str) -> String {
+    note.replace('~', " ")
+        .replace(['{', '}'], "")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Finds the `\printbibliography` command (and its optional argument), where
+/// the bibliography goes.
+///
+/// # Arguments
+///
+/// * `tex` - The LaTeX source
+///
+/// # Returns
+///
+/// The span of the first command outside comments, if any.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::latex::find_print_bibliography;
+///
+/// let tex = "Text.\n\\printbibliography[title=Sources]\n";
+/// assert_eq!(find_print_bibliography(tex), Some((6, 39)));
+/// ```
+pub fn find_print_bibliography(tex: &str) -> Option<(usize, usize)> {
+    const COMMAND: &str = "\\printbibliography";
+    let prose = mask_non_prose(tex);
+    let start = prose
+        .match_indices(COMMAND)
+        .map(|(i, _)| i)
+        .find(|&i| !prose[i + COMMAND.len()..].starts_with(|c: char| c.is_ascii_alphabetic()))?;
+
+    let mut end = start + COMMAND.len();
+    if prose[end..].starts_with('[') {
+        end = group_argument(&prose, end, b']').map_or(end, |(_, e)| e);
+    }
+    Some((start, end))
+}
+
+/// Converts a bibliography rendered by csl_proc (HTML) to LaTeX: one
+/// paragraph per entry.
+///
+/// # Arguments
+///
+/// * `html` - The formatted bibliography
+///
+/// # Returns
+///
+/// The entries as LaTeX paragraphs.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::latex::latex_bibliography;
+///
+/// let html = r#"<div class="csl-bib-body">
+///   <div class="csl-entry">Smith, <i>Title</i> &amp; more.</div>
+/// </div>"#;
+/// assert_eq!(latex_bibliography(html), r"Smith, \textit{Title} \& more.");
+/// ```
+pub fn latex_bibliography(html: &str) -> String {
+    bibliography_entries(html)
+        .into_iter()
+        .map(|entry| {
+            html_to_latex(entry)
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Converts formatted text (csl_proc HTML) to LaTeX.
+///
+/// Italic, bold, small caps, superscripts and subscripts become the matching
+/// LaTeX commands; other tags are dropped. Entities are decoded and LaTeX
+/// special characters escaped.
+///
+/// # Arguments
+///
+/// * `html` - The formatted text
+///
+/// # Returns
+///
+/// The same text in LaTeX.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::latex::html_to_latex;
+///
+/// assert_eq!(html_to_latex("<i>Nature</i> 5% &amp; more"), r"\textit{Nature} 5\% \& more");
+/// ```
+pub fn html_to_latex(html: &str) -> String {
+    convert_html(html, latex_command, |c, latex| match c {
+        '\\' => latex.push_str("\\textbackslash{}"),
+        '~' => latex.push_str("\\textasciitilde{}"),
+        '^' => latex.push_str("\\textasciicircum{}"),
+        '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+            latex.push('\\');
+            latex.push(c);
+        }
+        '\u{a0}' => latex.push('~'),
+        _ => latex.push(c),
+    })
+}
+
+/// Returns the LaTeX that opens and closes an HTML tag (given without its
+/// angle brackets).
+fn latex_command(tag: &str) -> (&'static str, &'static str) {
+    let name = tag.split_whitespace().next().unwrap_or("");
+    match name {
+        "i" | "em" => ("\\textit{", "}"),
+        "b" | "strong" => ("\\textbf{", "}"),
+        "sup" => ("\\textsuperscript{", "}"),
+        "sub" => ("\\textsubscript{", "}"),
+        "span" if tag.contains("small-caps") => ("\\textsc{", "}"),
+        "span" if tag.contains("font-style:normal") => ("\\textup{", "}"),
+        // Nested blocks (csl-left-margin, csl-right-inline) are separated by a space
+        "div" => ("", " "),
+        _ => ("", ""),
+    }
+}
+
+/// Generates the output LaTeX document.
+///
+/// The bibliography, with `heading` above it, replaces `\printbibliography`.
+/// Without that command it goes before `\end{document}`, or at the end of a
+/// document fragment.
+///
+/// # Arguments
+///
+/// * `content` - The LaTeX source with citations already replaced
+/// * `bibliography` - The bibliography, in LaTeX (if any)
+/// * `heading` - The heading of the bibliography, e.g. `\section*{References}`
+///
+/// # Returns
+///
+/// The complete output document.
+pub fn generate_latex_output(content: &str, bibliography: Option<&str>, heading: &str) -> String {
+    let Some(bib) = bibliography.filter(|bib| !bib.is_empty()) else {
+        return content.to_string();
+    };
+    let block = format!("{}\n\n{}", heading, bib);
+
+    let mut output = content.to_string();
+    if let Some((start, end)) = find_print_bibliography(content) {
+        output.replace_range(start..end, &block);
+    } else if let Some(end_document) = mask_non_prose(content).rfind("\\end{document}") {
+        output.insert_str(end_document, &format!("{}\n\n", block));
+    } else {
+        output = format!("{}\n\n{}\n", content.trim_end(), block);
+    }
+    output
+}
+
+/// Blanks out comments and verbatim environments, keeping byte offsets.
+fn mask_non_prose(tex: &str) -> String {
+    let mut masked = tex.as_bytes().to_vec();
+    let mut blank = |start: usize, end: usize| {
+        for byte in &mut masked[start..end] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+    };
+
+    let bytes = tex.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            // An escaped character, such as `\%`
+            b'\\' if bytes.get(i + 1).is_some_and(|b| !b.is_ascii_alphabetic()) => i += 2,
+            b'\\' => {
+                let rest = &tex[i..];
+                let environment = VERBATIM_ENVIRONMENTS
+                    .iter()
+                    .find(|env| rest.starts_with(&format!("\\begin{{{}}}", env)));
+                match environment {
+                    Some(env) => {
+                        let end_tag = format!("\\end{{{}}}", env);
+                        let end = rest
+                            .find(&end_tag)
+                            .map_or(tex.len(), |e| i + e + end_tag.len());
+                        blank(i, end);
+                        i = end;
+                    }
+                    None => i += 1,
+                }
+            }
+            b'%' => {
+                let end = tex[i..].find('\n').map_or(tex.len(), |e| i + e);
+                blank(i, end);
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+
+    // Masked ranges start and end on ASCII characters, so whole characters
+    // are replaced and the buffer stays valid UTF-8
+    String::from_utf8(masked).expect("masking whole characters keeps valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cite_commands_and_modes() {
+        // Given: Every citation command
+        let tex = r"\cite{a} \parencite{b} \autocite*{c} \citep{d} \textcite{e} \Citet{f}";
+
+        // When: We scan the document
+        let scan = scan_latex_citations(tex);
+
+        // Then: Each command is a cluster with the mode of the command
+        let modes: Vec<CitationMode> = scan.clusters.iter().map(|c| c.items[0].mode).collect();
+        assert_eq!(scan.clusters.len(), 6);
+        assert_eq!(modes[..4], [CitationMode::Normal; 4]);
+        assert_eq!(modes[4..], [CitationMode::AuthorInText; 2]);
+        assert_eq!(
+            &tex[scan.clusters[2].span.0..scan.clusters[2].span.1],
+            r"\autocite*{c}"
+        );
+    }
+
+    #[test]
+    fn test_prenote_and_postnote() {
+        // Given: Commands with one and two optional arguments
+        let tex = r"\citep[ch.~2]{a} \parencite[see][and passim]{b, c}";
+
+        // When: We scan the document
+        let scan = scan_latex_citations(tex);
+
+        // Then: One argument is the postnote, two are the prenote and the postnote
+        let first = &scan.clusters[0].items[0];
+        assert_eq!(first.locator.as_deref(), Some("2"));
+        assert_eq!(first.label.as_deref(), Some("chapter"));
+
+        let items = &scan.clusters[1].items;
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].prefix.as_deref(), Some("see"));
+        assert_eq!(items[1].suffix.as_deref(), Some("and passim"));
+        assert_eq!(scan.citations.len(), 3);
+    }
+
+    #[test]
+    fn test_comments_and_verbatim_ignored() {
+        // Given: Citations in a comment, a verbatim environment and after an escaped %
+        let tex = "5\\% \\cite{a} % \\cite{b}\n\\begin{verbatim}\n\\cite{c}\n\\end{verbatim}\n\\citefield{d}";
+
+        // When: We scan the document
+        let scan = scan_latex_citations(tex);
+
+        // Then: Only the citation in prose is found
+        let ids: Vec<&str> = scan.citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["a"]);
+    }
+
+    #[test]
+    fn test_generate_latex_output() {
+        // Given: A document with and without \printbibliography
+        let with_marker = "Text.\n\\printbibliography\n\\end{document}\n";
+        let without_marker = "Text.\n\\end{document}\n";
+
+        // When: We add a bibliography
+        let heading = r"\section*{References}";
+        let placed = generate_latex_output(with_marker, Some("Entry."), heading);
+        let appended = generate_latex_output(without_marker, Some("Entry."), heading);
+
+        // Then: It replaces the command, or goes before \end{document}
+        assert_eq!(
+            placed,
+            "Text.\n\\section*{References}\n\nEntry.\n\\end{document}\n"
+        );
+        assert_eq!(
+            appended,
+            "Text.\n\\section*{References}\n\nEntry.\n\n\\end{document}\n"
+        );
+    }
+}
@@ -6,18 +6,30 @@
 //! - Format citations and bibliographies using csl_proc
 //! - Generate output with formatted citations
 
+pub mod aliases;
+pub mod docx;
+pub mod frontmatter;
+pub mod latex;
+pub mod locator;
 pub mod markdown;
+pub mod notebook;
+pub mod notes;
+pub mod org;
 pub mod output;
 pub mod processor;
 pub mod refs;
 pub mod style;
+pub mod typst;
 
 pub use markdown::{
-    extract_citation_clusters, extract_citations, Citation, CitationCluster, CitationItem,
+    extract_citation_clusters, extract_citation_clusters_with, extract_citations,
+    find_citation_escapes, scan_citations, scan_citations_with, Citation, CitationCluster,
+    CitationItem, CitationMode, CitationScan, GroupingPolicy, SourcePosition, SyntaxExtensions,
 };
 pub use output::{generate_output, replace_citations};
 pub use processor::{
-    format_bibliography, format_citations, format_citations_clusters, ProcessedCitation,
+    format_bibliography, format_citations, format_citations_clusters,
+    format_citations_clusters_with, ProcessedCitation,
 };
 pub use refs::load_refs;
-pub use style::{builtin_style, builtin_style_names, load_style};
+pub use style::{builtin_style, builtin_style_names, is_note_style, load_style};
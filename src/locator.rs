@@ -0,0 +1,255 @@
+//! CSL locator labels.
+//!
+//! Maps the terms authors write in front of a locator ("p.", "chap.", "Bd.",
+//! "sub verbo"...) to the CSL locator label passed to csl_proc ("page",
+//! "chapter", "volume", "sub-verbo"...). The table follows the locator terms of
+//! the CSL locale files, in their long and short forms, singular and plural.
+
+/// The locator terms of one CSL label in one locale.
+struct LocatorTerm {
+    /// CSL locator label (e.g., "page")
+    label: &'static str,
+    /// Long forms, singular and plural (e.g., "page", "pages")
+    long: &'static [&'static str],
+    /// Short forms and symbols, singular and plural (e.g., "p.", "pp.")
+    short: &'static [&'static str],
+}
+
+/// Shorthand to keep the locale tables on one line per label.
+const fn term(
+    label: &'static str,
+    long: &'static [&'static str],
+    short: &'static [&'static str],
+) -> LocatorTerm {
+    LocatorTerm { label, long, short }
+}
+
+/// Locator terms from `locales-en-US.xml`, plus "ch." for backward compatibility.
+const EN_US: &[LocatorTerm] = &[
+    term("book", &["book", "books"], &["bk.", "bks."]),
+    term(
+        "chapter",
+        &["chapter", "chapters"],
+        &["chap.", "chaps.", "ch."],
+    ),
+    term("column", &["column", "columns"], &["col.", "cols."]),
+    term("figure", &["figure", "figures"], &["fig.", "figs."]),
+    term("folio", &["folio", "folios"], &["fol.", "fols."]),
+    term(
+        "issue",
+        &["number", "numbers", "issue", "issues"],
+        &["no.", "nos."],
+    ),
+    term("line", &["line", "lines"], &["l.", "ll."]),
+    term("note", &["note", "notes"], &["n.", "nn."]),
+    term("opus", &["opus", "opera"], &["op.", "opp."]),
+    term("page", &["page", "pages"], &["p.", "pp."]),
+    term(
+        "paragraph",
+        &["paragraph", "paragraphs"],
+        &["para.", "paras.", "¶", "¶¶"],
+    ),
+    term("part", &["part", "parts"], &["pt.", "pts."]),
+    term(
+        "section",
+        &["section", "sections"],
+        &["sec.", "secs.", "§", "§§"],
+    ),
+    term(
+        "sub-verbo",
+        &["sub verbo", "sub verbis"],
+        &["s.v.", "s.vv."],
+    ),
+    term("verse", &["verse", "verses"], &["v.", "vv."]),
+    term("volume", &["volume", "volumes"], &["vol.", "vols."]),
+];
+
+/// Locator terms from `locales-fr-FR.xml`.
+const FR_FR: &[LocatorTerm] = &[
+    term("book", &["livre", "livres"], &["liv."]),
+    term("chapter", &["chapitre", "chapitres"], &["chap."]),
+    term("column", &["colonne", "colonnes"], &["col."]),
+    term("figure", &["figure", "figures"], &["fig."]),
+    term("folio", &["folio", "folios"], &["fᵒ", "fᵒˢ"]),
+    term("issue", &["numéro", "numéros"], &["nᵒ", "nᵒˢ", "n°"]),
+    term("line", &["ligne", "lignes"], &["l."]),
+    term("note", &["note", "notes"], &["n."]),
+    term("opus", &["opus"], &["op."]),
+    term("page", &["page", "pages"], &["p."]),
+    term("paragraph", &["paragraphe", "paragraphes"], &["paragr."]),
+    term("part", &["partie", "parties"], &["part."]),
+    term("section", &["section", "sections"], &["sect."]),
+    term("sub-verbo", &["sub verbo", "sub verbis"], &["s.v."]),
+    term("verse", &["verset", "versets"], &["v."]),
+    term("volume", &["volume", "volumes"], &["vol."]),
+];
+
+/// Locator terms from `locales-de-DE.xml`.
+const DE_DE: &[LocatorTerm] = &[
+    term("book", &["Buch", "Bücher"], &["B."]),
+    term("chapter", &["Kapitel"], &["Kap."]),
+    term("column", &["Spalte", "Spalten"], &["Sp."]),
+    term("figure", &["Abbildung", "Abbildungen"], &["Abb."]),
+    term("folio", &["Blatt", "Blätter"], &["Fol."]),
+    term("issue", &["Nummer", "Nummern"], &["Nr."]),
+    term("line", &["Zeile", "Zeilen"], &["Z."]),
+    term("note", &["Fußnote", "Fußnoten"], &["Fn."]),
+    term("opus", &["Opus", "Opera"], &["op."]),
+    term("page", &["Seite", "Seiten"], &["S."]),
+    term("paragraph", &["Absatz", "Absätze"], &["Abs."]),
+    term("part", &["Teil", "Teile"], &[]),
+    term("section", &["Abschnitt", "Abschnitte"], &["Abschn."]),
+    term("sub-verbo", &["sub verbo", "sub verbis"], &["s. v."]),
+    term("verse", &["Vers", "Verse"], &["V."]),
+    term("volume", &["Band", "Bände"], &["Bd."]),
+];
+
+/// Locator terms from `locales-es-ES.xml`.
+const ES_ES: &[LocatorTerm] = &[
+    term("book", &["libro", "libros"], &["lib."]),
+    term("chapter", &["capítulo", "capítulos"], &["cap."]),
+    term("column", &["columna", "columnas"], &["col."]),
+    term("figure", &["figura", "figuras"], &["fig."]),
+    term("folio", &["folio", "folios"], &["f."]),
+    term("issue", &["número", "números"], &["n.º"]),
+    term("line", &["línea", "líneas"], &["l."]),
+    term("note", &["nota", "notas"], &["n."]),
+    term("opus", &["opus", "opera"], &["op."]),
+    term("page", &["página", "páginas"], &["p.", "pp."]),
+    term("paragraph", &["párrafo", "párrafos"], &["párr."]),
+    term("part", &["parte", "partes"], &["pt."]),
+    term("section", &["sección", "secciones"], &["sec."]),
+    term("sub-verbo", &["sub voce", "sub vocibus"], &["s. v."]),
+    term("verse", &["verso", "versos"], &["v.", "vv."]),
+    term("volume", &["volumen", "volúmenes"], &["vol.", "vols."]),
+];
+
+/// All locales whose terms are recognised, in lookup order.
+const LOCALES: &[&[LocatorTerm]] = &[EN_US, FR_FR, DE_DE, ES_ES];
+
+/// Splits a leading locator term off `text`.
+///
+/// The longest matching term wins, so "pp. 3" is read as "pp." rather than
+/// "p.". Long forms are matched case-insensitively ("Chapter 3") and must end
+/// at a word boundary ("partial" is not "part"); short forms and symbols are
+/// matched exactly ("S." is German for page, "s." is nothing).
+///
+/// # Arguments
+///
+/// * `text` - The locator text, starting with the term (e.g., "Bd. 2")
+///
+/// # Returns
+///
+/// The CSL label and the text following the term, or `None` if `text` does
+/// not start with a known term.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::locator::split_label;
+///
+/// assert_eq!(split_label("chap. 3"), Some(("chapter", " 3")));
+/// assert_eq!(split_label("Bd. 2"), Some(("volume", " 2")));
+/// assert_eq!(split_label("42"), None);
+/// ```
+pub fn split_label(text: &str) -> Option<(&'static str, &str)> {
+    let mut best: Option<(&'static str, usize)> = None;
+
+    for term in LOCALES.iter().flat_map(|locale| locale.iter()) {
+        let long = term
+            .long
+            .iter()
+            .filter(|form| starts_with_word(text, form))
+            .map(|form| form.len());
+        let short = term
+            .short
+            .iter()
+            .filter(|form| text.starts_with(*form))
+            .map(|form| form.len());
+
+        for len in long.chain(short) {
+            if !matches!(best, Some((_, best_len)) if best_len >= len) {
+                best = Some((term.label, len));
+            }
+        }
+    }
+
+    best.map(|(label, len)| (label, &text[len..]))
+}
+
+/// Returns true if `text` starts with the word `form`, ignoring case.
+fn starts_with_word(text: &str, form: &str) -> bool {
+    let Some(head) = text.get(..form.len()) else {
+        return false;
+    };
+    head.to_lowercase() == form.to_lowercase()
+        && !text[form.len()..].starts_with(char::is_alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_csl_label_is_recognised() {
+        // Given: The long form of every CSL locator label
+        let cases = [
+            ("book 2", "book"),
+            ("chapter 2", "chapter"),
+            ("column 2", "column"),
+            ("figure 2", "figure"),
+            ("folio 2", "folio"),
+            ("issue 2", "issue"),
+            ("line 2", "line"),
+            ("note 2", "note"),
+            ("opus 2", "opus"),
+            ("page 2", "page"),
+            ("paragraph 2", "paragraph"),
+            ("part 2", "part"),
+            ("section 2", "section"),
+            ("sub verbo 2", "sub-verbo"),
+            ("verse 2", "verse"),
+            ("volume 2", "volume"),
+        ];
+
+        for (text, label) in cases {
+            // When: We split the label off
+            let result = split_label(text);
+
+            // Then: The CSL label is found and the value remains
+            assert_eq!(result, Some((label, " 2")), "for {:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_short_forms_and_plurals() {
+        // Given/When/Then: Abbreviations and symbols map to their label
+        assert_eq!(split_label("pp. 3-5"), Some(("page", " 3-5")));
+        assert_eq!(split_label("vols. 1-2"), Some(("volume", " 1-2")));
+        assert_eq!(split_label("s.v. word"), Some(("sub-verbo", " word")));
+        assert_eq!(split_label("§ 4"), Some(("section", " 4")));
+        assert_eq!(split_label("¶¶ 4-5"), Some(("paragraph", " 4-5")));
+        assert_eq!(split_label("ch. 3"), Some(("chapter", " 3")));
+    }
+
+    #[test]
+    fn test_localized_terms() {
+        // Given/When/Then: French, German and Spanish terms are recognised
+        assert_eq!(split_label("chap. 3"), Some(("chapter", " 3")));
+        assert_eq!(split_label("fig. 2"), Some(("figure", " 2")));
+        assert_eq!(split_label("S. 12"), Some(("page", " 12")));
+        assert_eq!(split_label("Bd. 2"), Some(("volume", " 2")));
+        assert_eq!(split_label("Kapitel 4"), Some(("chapter", " 4")));
+        assert_eq!(split_label("chapitre 4"), Some(("chapter", " 4")));
+        assert_eq!(split_label("página 9"), Some(("page", " 9")));
+    }
+
+    #[test]
+    fn test_long_forms_ignore_case_and_need_word_boundary() {
+        // Given/When/Then: Case does not matter for words, boundaries do
+        assert_eq!(split_label("Chapter 3"), Some(("chapter", " 3")));
+        assert_eq!(split_label("partial 3"), None);
+        assert_eq!(split_label("s. 3"), None);
+        assert_eq!(split_label("42"), None);
+    }
+}
@@ -1,17 +1,37 @@
 //! CLI for csl-tools - Format citations and bibliographies in Markdown documents.
 
+use std::collections::HashSet;
 use std::fmt;
 use std::fs;
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 use csl_tools::{
-    builtin_style, extract_citation_clusters, extract_citations, format_bibliography,
-    format_citations_clusters, generate_output, load_refs, load_style,
-    processor::ProcessorError, replace_citations, style::builtin_style_names,
+    aliases::KeyAliases,
+    builtin_style,
+    docx::{html_to_text, Docx},
+    format_bibliography, format_citations_clusters_with,
+    frontmatter::{parse_front_matter, FrontMatter},
+    generate_output, is_note_style,
+    latex::{generate_latex_output, html_to_latex, latex_bibliography, scan_latex_citations},
+    load_refs, load_style,
+    markdown::{find_citation_escapes_with, parse_nocite_keys, refsection_ranges},
+    notebook::Notebook,
+    notes::{footnote_citations, note_style_clusters, order_by_notes},
+    org::{generate_org_output, html_to_org, org_bibliography, scan_org_citations},
+    processor::{
+        format_bibliography_continued, link_bibliography_entries, reference_anchor, reference_ids,
+        resolve_citation_keys, resolve_nocite, short_author_names, ProcessedCitation,
+        ProcessorError,
+    },
+    refs::merge_refs,
+    replace_citations, scan_citations_with,
+    style::{builtin_style_names, sorts_bibliography, with_default_locale},
+    typst::{generate_typst_output, html_to_typst, scan_typst_citations, typst_bibliography},
+    Citation, CitationCluster, GroupingPolicy, SyntaxExtensions,
 };
 
 // ---------------------------------------------------------------------------
@@ -35,41 +55,177 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Process a Markdown file with citations
+    /// Process a Markdown, LaTeX, Org, Typst, Jupyter notebook or DOCX file with citations
     #[command(after_help = "\
 Examples:
   csl-tools process paper.md --bib refs.json --csl minimal
   csl-tools process paper.md -b refs.json -c ieee.csl -o paper.html
   csl-tools process paper.md -b refs.json -c minimal --no-bib
+  csl-tools process paper.md    (bibliography and csl from the YAML front matter)
+  csl-tools process report.qmd    (Quarto/R Markdown: @fig-x and other cross-references are kept)
+  csl-tools process paper.tex -b refs.json -c minimal    (\\cite commands, bibliography at \\printbibliography)
+  csl-tools process notes.org -b refs.json -c minimal    ([cite:@key], bibliography at #+print_bibliography:)
+  csl-tools process paper.typ -b refs.json -c minimal    (@key, bibliography at #bibliography(...))
+  csl-tools process analysis.ipynb -b refs.json -c minimal -o out.ipynb    (Markdown cells only)
+  csl-tools process draft.docx -b refs.json -c minimal -o final.docx
 
-Citation syntax: [@key], [@key](url), [@key, p. 42], [@a; @b; @c]")]
-    Process {
-        /// Input Markdown file (use '-' for stdin)
-        input: PathBuf,
+Citation syntax: [@key], [@key](url), [@key, p. 42], [@a; @b; @c], [see @key, p. 3 and passim], [-@key], @key (narrative)")]
+    Process(Box<ProcessArgs>),
 
-        /// Bibliography file (CSL-JSON array or JSONL)
-        #[arg(short, long)]
-        bib: PathBuf,
+    /// List available builtin CSL styles
+    Styles,
+}
 
-        /// CSL style: path to a .csl file, or builtin name (see 'styles' command)
-        #[arg(short, long)]
-        csl: String,
+#[derive(Args)]
+struct ProcessArgs {
+    /// Input Markdown, LaTeX, Org, Typst, notebook or DOCX file (use '-' for stdin)
+    input: PathBuf,
+
+    /// Input format [default: from the file extension (.qmd and .Rmd are
+    /// Quarto, .tex is LaTeX, .org is Org, .typ is Typst, .ipynb is a Jupyter
+    /// notebook, .docx is Word), otherwise Markdown]
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    from: Option<InputFormat>,
+
+    /// Bibliography file (CSL-JSON array or JSONL) [default: `bibliography`
+    /// of the YAML front matter]
+    #[arg(short, long)]
+    bib: Option<PathBuf>,
+
+    /// Alias file mapping old citation keys to reference ids (JSON object,
+    /// or TOML if it ends in .toml): "pmid:12345" = "smith2020"
+    #[arg(long, value_name = "FILE")]
+    aliases: Option<PathBuf>,
+
+    /// CSL style: path to a .csl file, or builtin name (see 'styles' command)
+    /// [default: `csl` of the YAML front matter]
+    #[arg(short, long)]
+    csl: Option<String>,
+
+    /// Locale of the style, e.g. fr-FR [default: `lang` of the YAML front matter]
+    #[arg(long)]
+    lang: Option<String>,
+
+    /// Link citations to their bibliography entry [default: `link-citations`
+    /// of the YAML front matter]
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    link_citations: Option<bool>,
+
+    /// Also list these references in the bibliography, without citing them
+    /// ("@a, @b", or "@*" for all); repeatable [default: `nocite` of the YAML
+    /// front matter]
+    #[arg(long, value_name = "KEYS")]
+    nocite: Vec<String>,
+
+    /// Remove the YAML front matter from the output
+    #[arg(long)]
+    strip_front_matter: bool,
+
+    /// Output file (default: stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Don't include bibliography
+    #[arg(long)]
+    no_bib: bool,
 
-        /// Output file (default: stdout)
-        #[arg(short, long)]
-        output: Option<PathBuf>,
+    /// Custom bibliography header, used when the document has no
+    /// `<!-- bibliography -->` or `::: {#refs}` marker [default: "## References",
+    /// or "## " followed by `reference-section-title` of the YAML front matter]
+    #[arg(long)]
+    bib_header: Option<String>,
 
-        /// Don't include bibliography
-        #[arg(long)]
-        no_bib: bool,
+    /// Which separators between citations group them into one cluster
+    #[arg(long, value_enum, default_value_t = Grouping::Whitespace)]
+    group: Grouping,
 
-        /// Custom bibliography header
-        #[arg(long, default_value = "## References")]
-        bib_header: String,
-    },
+    /// Also read Obsidian wikilink citations: [[@key]], [[@key|p. 4]]
+    #[arg(long)]
+    wikilinks: bool,
 
-    /// List available builtin CSL styles
-    Styles,
+    /// Start a new reference section, with its own bibliography, at each
+    /// heading of this level (`<!-- refsection -->` markers always do)
+    #[arg(long, value_name = "LEVEL")]
+    refsection_level: Option<usize>,
+
+    /// Citation numbering across reference sections
+    #[arg(long, value_enum, default_value_t = RefsectionNumbering::Restart)]
+    refsection_numbering: RefsectionNumbering,
+}
+
+/// Formats of the input document.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum InputFormat {
+    /// Markdown with Pandoc citations: [@key]
+    Markdown,
+    /// Quarto or R Markdown: Markdown whose @fig-x... are cross-references
+    Quarto,
+    /// LaTeX with biblatex/natbib commands: \cite{key}
+    Latex,
+    /// Org with org-cite citations: [cite:@key]
+    Org,
+    /// Typst: @key or #cite(<key>)
+    Typst,
+    /// Jupyter notebook, with Markdown cells
+    Ipynb,
+    /// Word document with [@key] markers
+    Docx,
+}
+
+impl InputFormat {
+    /// Guesses the format of a file from its extension.
+    fn detect(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("qmd" | "rmd" | "Rmd") => InputFormat::Quarto,
+            Some("tex" | "ltx") => InputFormat::Latex,
+            Some("org") => InputFormat::Org,
+            Some("typ") => InputFormat::Typst,
+            Some("ipynb") => InputFormat::Ipynb,
+            Some("docx") => InputFormat::Docx,
+            _ => InputFormat::Markdown,
+        }
+    }
+
+    /// Whether the citations are in Markdown (notebooks have Markdown cells).
+    fn is_markdown(self) -> bool {
+        matches!(
+            self,
+            InputFormat::Markdown | InputFormat::Quarto | InputFormat::Ipynb
+        )
+    }
+}
+
+/// How adjacent citations are grouped into clusters (see `GroupingPolicy`).
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Grouping {
+    /// Spaces and tabs only: [@a] [@b]
+    Whitespace,
+    /// Any whitespace within a paragraph, line breaks included
+    Newlines,
+    /// Spaces and tabs around at most one comma: [@a], [@b]
+    Comma,
+    /// Never group (Pandoc groups [@a; @b] stay whole)
+    Never,
+}
+
+impl From<Grouping> for GroupingPolicy {
+    fn from(grouping: Grouping) -> Self {
+        match grouping {
+            Grouping::Whitespace => GroupingPolicy::Whitespace,
+            Grouping::Newlines => GroupingPolicy::WhitespaceAndNewlines,
+            Grouping::Comma => GroupingPolicy::Comma,
+            Grouping::Never => GroupingPolicy::Never,
+        }
+    }
+}
+
+/// How citations are numbered when the document has several reference sections.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RefsectionNumbering {
+    /// Each section is numbered from 1
+    Restart,
+    /// Numbering continues from one section to the next
+    Continuous,
 }
 
 // ---------------------------------------------------------------------------
@@ -85,6 +241,8 @@ enum AppError {
     Style(String),
     /// Exit 13 — citation key not found in bibliography
     ReferenceNotFound(String),
+    /// Exit 13 — `doi:`/`pmid:` citation key matching several references
+    AmbiguousReference(String),
     /// Exit 14 — CSL processing engine error
     CslProcessing(String),
     /// Exit 15 — cannot write output file
@@ -97,7 +255,7 @@ impl AppError {
             AppError::InputFile(_) => 10,
             AppError::BibFile(_) => 11,
             AppError::Style(_) => 12,
-            AppError::ReferenceNotFound(_) => 13,
+            AppError::ReferenceNotFound(_) | AppError::AmbiguousReference(_) => 13,
             AppError::CslProcessing(_) => 14,
             AppError::OutputFile(_) => 15,
         }
@@ -132,6 +290,13 @@ impl fmt::Display for AppError {
                     msg
                 )
             }
+            AppError::AmbiguousReference(msg) => {
+                write!(
+                    f,
+                    "{}\n  hint: cite one of them by its id, or remove the duplicate from your bibliography file",
+                    msg
+                )
+            }
             AppError::CslProcessing(msg) => {
                 write!(f, "{}", msg)
             }
@@ -161,15 +326,8 @@ fn run() -> Result<(), AppError> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Process {
-            input,
-            bib,
-            csl,
-            output,
-            no_bib,
-            bib_header,
-        } => {
-            process_command(&input, &bib, &csl, output.as_deref(), no_bib, &bib_header)?;
+        Commands::Process(args) => {
+            process_command(&args)?;
         }
         Commands::Styles => {
             styles_command();
@@ -184,16 +342,14 @@ fn run() -> Result<(), AppError> {
 // ---------------------------------------------------------------------------
 
 /// Process a Markdown file with citations.
-fn process_command(
-    input: &Path,
-    bib: &Path,
-    csl: &str,
-    output: Option<&Path>,
-    no_bib: bool,
-    bib_header: &str,
-) -> Result<(), AppError> {
-    // 1. Read the Markdown file (support '-' for stdin)
-    let markdown = if input == Path::new("-") {
+fn process_command(args: &ProcessArgs) -> Result<(), AppError> {
+    let input = args.input.as_path();
+
+    // 1. Read the document (support '-' for stdin)
+    let format = args.from.unwrap_or_else(|| InputFormat::detect(input));
+    let text = if format == InputFormat::Docx {
+        String::new()
+    } else if input == Path::new("-") {
         let mut buf = String::new();
         io::stdin()
             .read_to_string(&mut buf)
@@ -204,16 +360,97 @@ fn process_command(
             AppError::InputFile(format!("'{}': {}", input.display(), e))
         })?
     };
+    // A Word document is a zip package, read as bytes
+    let docx = if format == InputFormat::Docx {
+        let package = if input == Path::new("-") {
+            let mut buf = Vec::new();
+            io::stdin()
+                .read_to_end(&mut buf)
+                .map_err(|e| AppError::InputFile(format!("failed to read from stdin: {}", e)))?;
+            buf
+        } else {
+            fs::read(input)
+                .map_err(|e| AppError::InputFile(format!("'{}': {}", input.display(), e)))?
+        };
+        Some(
+            Docx::open(package)
+                .map_err(|e| AppError::InputFile(format!("'{}': {}", input.display(), e)))?,
+        )
+    } else {
+        None
+    };
+    // Only the Markdown cells of a notebook are processed, as one document
+    let notebook = match format {
+        InputFormat::Ipynb => Some(
+            Notebook::parse(&text)
+                .map_err(|e| AppError::InputFile(format!("'{}': {}", input.display(), e)))?,
+        ),
+        _ => None,
+    };
+    let document = match (&notebook, &docx) {
+        (Some(notebook), _) => notebook.markdown(),
+        (_, Some(docx)) => docx.text(),
+        _ => text.as_str(),
+    };
 
-    // 2. Load references
-    let refs_json = load_refs(bib)
-        .map_err(|e| AppError::BibFile(format!("'{}': {}", bib.display(), e)))?;
+    // 2. Read the YAML front matter: its settings are defaults for the
+    // options, with paths relative to the document
+    let front = if format.is_markdown() {
+        parse_front_matter(document)
+            .map_err(|e| AppError::InputFile(format!("'{}': {}", input.display(), e)))?
+            .unwrap_or_default()
+    } else {
+        FrontMatter::default()
+    };
+    let base_dir = if input == Path::new("-") {
+        Path::new("")
+    } else {
+        input.parent().unwrap_or(Path::new(""))
+    };
 
-    // 3. Load style (builtin or file)
-    let style_csl = if let Some(builtin) = builtin_style(csl) {
+    // 3. Load references (several files are merged)
+    let bibs: Vec<PathBuf> = match &args.bib {
+        Some(bib) => vec![bib.clone()],
+        None => front
+            .bibliography
+            .iter()
+            .map(|b| base_dir.join(b))
+            .collect(),
+    };
+    if bibs.is_empty() {
+        return Err(AppError::BibFile(
+            "no bibliography given: use --bib, or `bibliography:` in the YAML front matter"
+                .to_string(),
+        ));
+    }
+    let mut refs_jsons = Vec::with_capacity(bibs.len());
+    for bib in &bibs {
+        refs_jsons.push(
+            load_refs(bib).map_err(|e| AppError::BibFile(format!("'{}': {}", bib.display(), e)))?,
+        );
+    }
+    let refs_json = merge_refs(&refs_jsons).map_err(|e| AppError::BibFile(e.to_string()))?;
+    let aliases = match &args.aliases {
+        Some(path) => KeyAliases::load(path)
+            .map_err(|e| AppError::BibFile(format!("'{}': {}", path.display(), e)))?,
+        None => KeyAliases::default(),
+    };
+    let ids = reference_ids(&refs_json).map_err(map_processor_error)?;
+    let ids: HashSet<&str> = ids.iter().map(String::as_str).collect();
+
+    // 4. Load style (builtin or file), in the requested locale
+    let (csl, style_path) = match (&args.csl, &front.csl) {
+        (Some(csl), _) => (csl.as_str(), PathBuf::from(csl)),
+        (None, Some(csl)) => (csl.as_str(), base_dir.join(csl)),
+        (None, None) => {
+            return Err(AppError::Style(
+                "no CSL style given: use --csl, or `csl:` in the YAML front matter".to_string(),
+            ))
+        }
+    };
+    let mut style_csl = if let Some(builtin) = builtin_style(csl) {
         builtin.to_string()
     } else {
-        let style_path = PathBuf::from(csl);
         load_style(&style_path).map_err(|e| {
             if style_path.exists() {
                 AppError::Style(format!("invalid CSL style '{}': {}", csl, e))
@@ -225,36 +462,300 @@ fn process_command(
             }
         })?
     };
+    if let Some(lang) = args.lang.as_deref().or(front.lang.as_deref()) {
+        style_csl =
+            with_default_locale(&style_csl, lang).map_err(|e| AppError::Style(e.to_string()))?;
+    }
 
-    // 4. Extract citation clusters (adjacent citations grouped)
-    let clusters = extract_citation_clusters(&markdown);
-
-    // 5. Format citation clusters via csl_proc
-    let processed =
-        format_citations_clusters(&clusters, &refs_json, &style_csl).map_err(map_processor_error)?;
+    // Citations link to bibliography entries only if those can be anchored,
+    // i.e. if the style keeps them in citation order (in Markdown)
+    let mut link_citations = format.is_markdown()
+        && !args.no_bib
+        && args
+            .link_citations
+            .or(front.link_citations)
+            .unwrap_or(false);
+    if link_citations && sorts_bibliography(&style_csl) {
+        eprintln!("warning: link-citations ignored, the style sorts its bibliography");
+        link_citations = false;
+    }
+    // Continuous numbering tells the entries of earlier sections apart by
+    // position, which needs a bibliography in citation order
+    if args.refsection_numbering == RefsectionNumbering::Continuous
+        && !args.no_bib
+        && sorts_bibliography(&style_csl)
+    {
+        return Err(AppError::Style(format!(
+            "--refsection-numbering continuous needs a style with its bibliography in citation order, but '{}' sorts it",
+            csl
+        )));
+    }
+    let bib_header = match (&args.bib_header, &front.reference_section_title) {
+        (Some(header), _) => header.clone(),
+        (None, Some(title)) => format!("## {}", title),
+        (None, None) => match format {
+            InputFormat::Markdown | InputFormat::Quarto | InputFormat::Ipynb => {
+                "## References".to_string()
+            }
+            InputFormat::Latex => "\\section*{References}".to_string(),
+            InputFormat::Org => "* References".to_string(),
+            InputFormat::Typst => "= References".to_string(),
+            InputFormat::Docx => "References".to_string(),
+        },
+    };
 
-    // 6. Replace citations in text
-    let content = replace_citations(&markdown, &processed);
+    // References listed without being cited: they follow the cited ones in
+    // the bibliography of the last section
+    let nocite_entries = if args.nocite.is_empty() {
+        &front.nocite
+    } else {
+        &args.nocite
+    };
+    let nocite_keys: Vec<String> = nocite_entries
+        .iter()
+        .flat_map(|entry| parse_nocite_keys(entry))
+        .map(|key| aliases.resolve(&key, &ids).to_string())
+        .collect();
+    let nocite = resolve_nocite(&nocite_keys, &refs_json).map_err(|e| match e {
+        ProcessorError::ReferenceNotFound(id) => {
+            AppError::ReferenceNotFound(format!("nocite: reference '{}' not found", id))
+        }
+        e => map_processor_error(e),
+    })?;
+
+    // 5. Split the document into reference sections (a single one unless it
+    // has refsection markers or --refsection-level is given)
+    let sections = match format {
+        InputFormat::Markdown | InputFormat::Quarto => {
+            refsection_ranges(document, args.refsection_level)
+        }
+        _ => vec![(0, document.len())],
+    };
+    let section_of = |pos: usize| sections.iter().position(|&(_, end)| pos < end);
+
+    // 6. Scan the citations and their clusters (grouped as --group says);
+    // note styles process clusters in note order so that ibid/subsequent
+    // follow the footnotes
+    let note_style = is_note_style(&style_csl);
+    let extensions = SyntaxExtensions {
+        wikilinks: args.wikilinks,
+        quarto: format == InputFormat::Quarto,
+    };
+    let mut scan = match format {
+        // A bare `@name` is a narrative citation only if the bibliography has
+        // that reference, so that mentions such as `@team` stay text
+        InputFormat::Markdown | InputFormat::Quarto | InputFormat::Ipynb | InputFormat::Docx => {
+            let mut scan = scan_citations_with(document, args.group.into(), extensions);
+            scan.retain_narrative(|name| ids.contains(aliases.resolve(name, &ids)));
+            scan
+        }
+        InputFormat::Latex => scan_latex_citations(document),
+        InputFormat::Org => scan_org_citations(document),
+        // `@name` is a citation only if the bibliography has that reference,
+        // under this id or an alias
+        InputFormat::Typst => {
+            scan_typst_citations(document, |name| ids.contains(aliases.resolve(name, &ids)))
+        }
+    };
+    // Old keys and doi:/pmid: keys cite their reference under its id, so
+    // that it is listed once
+    resolve_citation_keys(&mut scan, &refs_json, &aliases).map_err(map_processor_error)?;
+    let citations = scan.citations;
+    let mut clusters = scan.clusters;
+    if note_style && format.is_markdown() {
+        clusters = order_by_notes(document, &clusters);
+    }
 
-    // 7. Format bibliography
-    let citations = extract_citations(&markdown);
-    let bibliography = if no_bib {
-        None
+    // 7. Format citation clusters via csl_proc, once per section when the
+    // numbering restarts in each section; with footnotes, narrative citations
+    // get a regular note
+    let note_clusters = if note_style && format.is_markdown() {
+        note_style_clusters(document, &clusters)
     } else {
-        let bib_html =
-            format_bibliography(&citations, &refs_json, &style_csl).map_err(map_processor_error)?;
-        if bib_html.is_empty() {
-            None
-        } else {
-            Some(bib_html)
+        clusters.clone()
+    };
+    let mut processed = Vec::with_capacity(clusters.len());
+    let cluster_groups: Vec<Vec<CitationCluster>> = match args.refsection_numbering {
+        RefsectionNumbering::Restart => (0..sections.len())
+            .map(|i| {
+                note_clusters
+                    .iter()
+                    .filter(|c| section_of(c.span.0) == Some(i))
+                    .cloned()
+                    .collect()
+            })
+            .collect(),
+        RefsectionNumbering::Continuous => vec![note_clusters],
+    };
+    for group in &cluster_groups {
+        processed.extend(
+            format_citations_clusters_with(group, &refs_json, &style_csl, &aliases).map_err(
+                |e| locate_processor_error(e, input, group, notebook.as_ref(), docx.as_ref()),
+            )?,
+        );
+    }
+    // csl_proc renders HTML, which other formats translate to their markup
+    let markup: Option<fn(&str) -> String> = match format {
+        InputFormat::Markdown | InputFormat::Quarto | InputFormat::Ipynb => None,
+        InputFormat::Latex => Some(html_to_latex),
+        InputFormat::Org => Some(html_to_org),
+        InputFormat::Typst => Some(html_to_typst),
+        InputFormat::Docx => Some(html_to_text),
+    };
+    if let Some(markup) = markup {
+        for citation in &mut processed {
+            citation.formatted = markup(&citation.formatted);
+        }
+    }
+    if link_citations {
+        for (citation, cluster) in processed.iter_mut().zip(cluster_groups.iter().flatten()) {
+            if let [item] = cluster.items.as_slice() {
+                citation.formatted =
+                    format!("[{}](#{})", citation.formatted, reference_anchor(&item.id));
+            }
         }
+    }
+
+    // 8. Replace citations in text (or by footnotes with a note style),
+    // dropping the backslash of escaped ones and, if asked, the front matter
+    let (mut replacements, footnotes) = match format {
+        InputFormat::Markdown | InputFormat::Quarto | InputFormat::Ipynb if note_style => {
+            let authors = short_author_names(&refs_json).map_err(map_processor_error)?;
+            footnote_citations(document, &processed, &clusters, &authors)
+        }
+        InputFormat::Latex if note_style => (
+            inline_notes(&processed, |text| format!("\\footnote{{{}}}", text)),
+            String::new(),
+        ),
+        InputFormat::Org if note_style => (
+            inline_notes(&processed, |text| format!("[fn::{}]", text)),
+            String::new(),
+        ),
+        InputFormat::Typst if note_style => (
+            inline_notes(&processed, |text| format!("#footnote[{}]", text)),
+            String::new(),
+        ),
+        _ => (processed.clone(), String::new()),
     };
+    if format.is_markdown() {
+        replacements.extend(
+            find_citation_escapes_with(document, extensions)
+                .into_iter()
+                .map(|pos| ProcessedCitation {
+                    original_span: (pos, pos + 1),
+                    formatted: String::new(),
+                }),
+        );
+    }
+    if args.strip_front_matter && front.span.1 > 0 {
+        replacements.push(ProcessedCitation {
+            original_span: front.span,
+            formatted: String::new(),
+        });
+    }
 
-    // 8. Generate output
-    let result = generate_output(&content, bibliography.as_deref(), bib_header);
+    // 9. Generate output, section by section: each one gets its own
+    // bibliography, and the footnote definitions go at the very end
+    let mut earlier: Vec<Citation> = Vec::new();
+    let mut outputs = Vec::with_capacity(sections.len());
+    for (i, &(start, end)) in sections.iter().enumerate() {
+        let local_replacements: Vec<ProcessedCitation> = replacements
+            .iter()
+            .filter(|r| section_of(r.original_span.0) == Some(i))
+            .map(|r| ProcessedCitation {
+                original_span: (r.original_span.0 - start, r.original_span.1 - start),
+                formatted: r.formatted.clone(),
+            })
+            .collect();
+        let content = || {
+            let content = replace_citations(&document[start..end], &local_replacements);
+            if i + 1 == sections.len() && !footnotes.is_empty() {
+                format!("{}\n\n{}", content.trim_end(), footnotes)
+            } else {
+                content
+            }
+        };
+
+        let mut section_citations: Vec<Citation> = citations
+            .iter()
+            .filter(|c| section_of(c.span.0) == Some(i))
+            .cloned()
+            .collect();
+        if i + 1 == sections.len() {
+            section_citations.extend(
+                nocite
+                    .iter()
+                    .filter(|n| !citations.iter().any(|c| c.id == n.id))
+                    .cloned(),
+            );
+        }
+        let bibliography = if args.no_bib {
+            None
+        } else {
+            let bib_html = match args.refsection_numbering {
+                RefsectionNumbering::Restart => {
+                    format_bibliography(&section_citations, &refs_json, &style_csl)
+                }
+                RefsectionNumbering::Continuous => format_bibliography_continued(
+                    &earlier,
+                    &section_citations,
+                    &refs_json,
+                    &style_csl,
+                ),
+            }
+            .map_err(map_processor_error)?;
+            if bib_html.is_empty() {
+                None
+            } else if link_citations {
+                let ids = bibliography_ids(&earlier, &section_citations, args.refsection_numbering);
+                Some(link_bibliography_entries(&bib_html, &ids))
+            } else {
+                Some(bib_html)
+            }
+        };
+        earlier.extend(section_citations);
 
-    // 9. Write to file or stdout
-    if let Some(output_path) = output {
+        outputs.push(match format {
+            InputFormat::Markdown | InputFormat::Quarto => {
+                generate_output(&content(), bibliography.as_deref(), &bib_header)
+            }
+            InputFormat::Latex => generate_latex_output(
+                &content(),
+                bibliography.map(|bib| latex_bibliography(&bib)).as_deref(),
+                &bib_header,
+            ),
+            InputFormat::Org => generate_org_output(
+                &content(),
+                bibliography.map(|bib| org_bibliography(&bib)).as_deref(),
+                &bib_header,
+            ),
+            InputFormat::Typst => generate_typst_output(
+                &content(),
+                bibliography.map(|bib| typst_bibliography(&bib)).as_deref(),
+                &bib_header,
+            ),
+            InputFormat::Ipynb => notebook.as_ref().expect("parsed above").render(
+                &local_replacements,
+                bibliography.as_deref(),
+                &bib_header,
+                &footnotes,
+            ),
+            InputFormat::Docx => docx.as_ref().expect("opened above").render_document(
+                &local_replacements,
+                bibliography.as_deref(),
+                &bib_header,
+            ),
+        });
+    }
+    let result = match &docx {
+        Some(docx) => docx
+            .package(&outputs.concat())
+            .map_err(|e| AppError::OutputFile(format!("'{}': {}", input.display(), e)))?,
+        None => outputs.join("\n\n").into_bytes(),
+    };
+
+    // 10. Write to file or stdout
+    if let Some(output_path) = args.output.as_deref() {
         fs::write(output_path, &result).map_err(|e| {
             AppError::OutputFile(format!("'{}': {}", output_path.display(), e))
         })?;
@@ -266,7 +767,7 @@ fn process_command(
     } else {
         let stdout = io::stdout();
         let mut handle = stdout.lock();
-        write!(handle, "{}", result).map_err(|e| {
+        handle.write_all(&result).map_err(|e| {
             AppError::OutputFile(format!("stdout: {}", e))
         })?;
     }
@@ -274,14 +775,101 @@ fn process_command(
     Ok(())
 }
 
+/// Returns the ids of a section's bibliography entries, in citation order:
+/// the distinct references it cites, without those already listed by earlier
+/// sections when numbering is continuous.
+fn bibliography_ids<'a>(
+    earlier: &[Citation],
+    citations: &'a [Citation],
+    numbering: RefsectionNumbering,
+) -> Vec<&'a str> {
+    let mut ids: Vec<&str> = Vec::new();
+    for citation in citations {
+        let listed_before = numbering == RefsectionNumbering::Continuous
+            && earlier.iter().any(|c| c.id == citation.id);
+        if !listed_before && !ids.contains(&citation.id.as_str()) {
+            ids.push(&citation.id);
+        }
+    }
+    ids
+}
+
+/// Puts each formatted citation in a footnote, written in place by `note`.
+fn inline_notes(
+    processed: &[ProcessedCitation],
+    note: impl Fn(&str) -> String,
+) -> Vec<ProcessedCitation> {
+    processed
+        .iter()
+        .map(|citation| ProcessedCitation {
+            original_span: citation.original_span,
+            formatted: note(&citation.formatted),
+        })
+        .collect()
+}
+
 /// Maps a ProcessorError to an AppError using type-safe matching.
 fn map_processor_error(e: ProcessorError) -> AppError {
     match e {
         ProcessorError::ReferenceNotFound(_) => AppError::ReferenceNotFound(e.to_string()),
+        ProcessorError::AmbiguousReference { .. } => AppError::AmbiguousReference(e.to_string()),
         _ => AppError::CslProcessing(e.to_string()),
     }
 }
 
+/// Maps a ProcessorError raised while formatting clusters to an AppError,
+/// pointing a missing or ambiguous reference at its first occurrence in the
+/// input, in the style of compiler diagnostics: `article.md:42:17: reference
+/// 'smith2021' not found`.
+///
+/// Notebooks and Word documents are processed as a text of their own, whose
+/// lines are not those of the file: the occurrence is given by its cell
+/// (`analysis.ipynb: cell 4: ...`) or paragraph (`paper.docx: paragraph
+/// 12: ...`) instead.
+fn locate_processor_error(
+    e: ProcessorError,
+    input: &Path,
+    clusters: &[CitationCluster],
+    notebook: Option<&Notebook>,
+    docx: Option<&Docx>,
+) -> AppError {
+    let key = match &e {
+        ProcessorError::ReferenceNotFound(id) => id,
+        ProcessorError::AmbiguousReference { key, .. } => key,
+        _ => return map_processor_error(e),
+    };
+    let first_use = clusters
+        .iter()
+        .flat_map(|cluster| &cluster.items)
+        .find(|item| &item.id == key);
+    let Some(item) = first_use else {
+        return map_processor_error(e);
+    };
+    let source = if input == Path::new("-") {
+        "<stdin>".to_string()
+    } else {
+        input.display().to_string()
+    };
+    let offset = item.position.span.0;
+    let location = match (notebook, docx) {
+        (Some(notebook), _) => match notebook.cell_number(offset) {
+            Some(cell) => format!("{}: cell {}", source, cell),
+            None => source,
+        },
+        (_, Some(docx)) => format!("{}: paragraph {}", source, docx.paragraph_number(offset)),
+        _ => format!("{}:{}:{}", source, item.position.line, item.position.column),
+    };
+    match &e {
+        ProcessorError::AmbiguousReference { ids, .. } => AppError::AmbiguousReference(format!(
+            "{}: '{}' matches several references: {}",
+            location,
+            key,
+            ids.join(", ")
+        )),
+        _ => AppError::ReferenceNotFound(format!("{}: reference '{}' not found", location, key)),
+    }
+}
+
 /// List available builtin CSL styles.
 fn styles_command() {
     for name in builtin_style_names() {
@@ -1,78 +1,37 @@
 //! Markdown citation parser.
 //!
-//! Extracts citations in the format `[@id]`, `[@id](url)`, and `[@id, p. 42]`
-//! from Markdown text.
+//! Extracts citations in the format `[@id]`, `[@id](url)`, `[@id, p. 42]` and
+//! `[-@id]` (author suppressed) from Markdown text, as well as narrative
+//! citations written as a bare `@id` (optionally followed by a locator:
+//! `@id [p. 42]`). Each item may carry free text around it, Pandoc style:
+//! `[see @id, pp. 3-5 and passim]`. Keys follow Pandoc's grammar, with
+//! `@{braced keys}` for anything else.
 //!
 //! Also supports citation clustering for adjacent citations and Pandoc syntax.
+//!
+//! Only prose is scanned: citation-like text inside inline code spans, fenced
+//! or indented code blocks and HTML comments is left untouched.
 
+use crate::frontmatter::front_matter_span;
+use crate::locator::split_label;
 use regex::Regex;
-
-/// Extracts Pandoc-style grouped citations like `[@a; @b; @c]` or `[@a, p. 10; @b, ch. 3]`.
-///
-/// This function finds citations in the Pandoc multi-citation syntax where multiple
-/// citation items are separated by semicolons within a single bracket pair.
-///
-/// # Returns
-///
-/// A vector of `CitationCluster` structs, each containing multiple `CitationItem`s.
-fn extract_pandoc_grouped_citations(markdown: &str) -> Vec<CitationCluster> {
-    // Regex to match Pandoc grouped citations: [@id1; @id2; @id3] or [@id1, locator; @id2]
-    // This matches brackets containing multiple @-prefixed citations separated by semicolons
-    let pandoc_re = Regex::new(r"\[(@[^\]]+;[^\]]*)\]").unwrap();
-
-    let mut clusters: Vec<CitationCluster> = Vec::new();
-
-    for cap in pandoc_re.captures_iter(markdown) {
-        let full_match = cap.get(0).unwrap();
-        let inner = cap.get(1).unwrap().as_str();
-
-        // Split by semicolon and parse each citation item
-        let mut items: Vec<CitationItem> = Vec::new();
-
-        for part in inner.split(';') {
-            let part = part.trim();
-            if part.is_empty() {
-                continue;
-            }
-
-            // Each part should start with @ and may have a locator after comma
-            // Format: @id or @id, locator
-            if let Some(stripped) = part.strip_prefix('@') {
-                // Check if there's a locator (comma-separated)
-                let (id, locator, label) = if let Some(comma_pos) = stripped.find(',') {
-                    let id = stripped[..comma_pos].trim().to_string();
-                    let locator_str = stripped[comma_pos + 1..].trim();
-                    let (locator, label) = parse_locator(locator_str);
-                    (id, locator, label)
-                } else {
-                    (stripped.trim().to_string(), None, None)
-                };
-
-                items.push(CitationItem {
-                    id,
-                    locator,
-                    label,
-                    url: None, // Pandoc syntax doesn't support URLs
-                });
-            }
-        }
-
-        if !items.is_empty() {
-            clusters.push(CitationCluster {
-                items,
-                span: (full_match.start(), full_match.end()),
-            });
-        }
-    }
-
-    clusters
+use std::sync::OnceLock;
+
+/// How a citation relates to the surrounding sentence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CitationMode {
+    /// Parenthetical citation, e.g. "(Smith, 2020)" or "(1)"
+    #[default]
+    Normal,
+    /// Narrative citation where the author is part of the sentence, e.g. "Smith (2020)"
+    AuthorInText,
 }
 
 /// An individual citation element (a single @id).
 ///
 /// This structure represents a single citation item within a cluster.
 /// Multiple `CitationItem`s can be grouped together in a `CitationCluster`.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct CitationItem {
     /// The citation key (e.g., "item-1" or "pmid:12345")
     pub id: String,
@@ -82,6 +41,88 @@ pub struct CitationItem {
     pub label: Option<String>,
     /// Optional URL associated with the citation (preserved for reference, ignored in grouped rendering)
     pub url: Option<String>,
+    /// Parenthetical or narrative (`@id` without brackets)
+    pub mode: CitationMode,
+    /// Whether the author is omitted from the rendered citation (`[-@id]`)
+    pub suppress_author: bool,
+    /// Optional text before the citation (e.g., "see" in `[see @id]`)
+    pub prefix: Option<String>,
+    /// Optional text after the locator (e.g., "and passim" in `[@id, p. 3 and passim]`)
+    pub suffix: Option<String>,
+    /// Where the citation item appears in the source text
+    pub position: SourcePosition,
+}
+
+impl From<Citation> for CitationItem {
+    fn from(citation: Citation) -> Self {
+        CitationItem {
+            id: citation.id,
+            locator: citation.locator,
+            label: citation.label,
+            url: citation.url,
+            mode: citation.mode,
+            suppress_author: citation.suppress_author,
+            prefix: citation.prefix,
+            suffix: citation.suffix,
+            position: citation.position,
+        }
+    }
+}
+
+/// A location in the source text, for diagnostics such as
+/// `article.md:42:17: reference 'smith2021' not found`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourcePosition {
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Column number in characters, starting at 1
+    pub column: usize,
+    /// Start and end byte positions in the source text
+    pub span: (usize, usize),
+}
+
+/// Converts byte spans of a text into line/column source positions.
+///
+/// Positions are usually requested in document order: the column of the
+/// previous one is then reused, so that a long line is not counted again for
+/// each of its citations.
+pub(crate) struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+    /// Last position computed: (byte offset, line, column)
+    last: (usize, usize, usize),
+}
+
+impl<'a> LineIndex<'a> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        LineIndex {
+            text,
+            line_starts,
+            last: (0, 1, 1),
+        }
+    }
+
+    pub(crate) fn position(&mut self, span: (usize, usize)) -> SourcePosition {
+        let (last_offset, last_line, last_column) = self.last;
+        let past_line = self
+            .line_starts
+            .get(last_line)
+            .is_some_and(|&next_line_start| span.0 >= next_line_start);
+        let column = if span.0 >= last_offset && !past_line {
+            last_column + self.text[last_offset..span.0].chars().count()
+        } else {
+            let line = self.line_starts.partition_point(|&start| start <= span.0);
+            let line_start = self.line_starts[line - 1];
+            self.last = (line_start, line, 1);
+            self.text[line_start..span.0].chars().count() + 1
+        };
+        let line = self.last.1;
+        self.last = (span.0, line, column);
+        SourcePosition { line, column, span }
+    }
 }
 
 /// A group of citations (one or more items in a single cluster).
@@ -96,10 +137,78 @@ pub struct CitationCluster {
     pub span: (usize, usize),
 }
 
+/// Which text between two citations lets them share a cluster.
+///
+/// Narrative citations (`@key`) always stand alone, whatever the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GroupingPolicy {
+    /// Only spaces and tabs: `[@a] [@b]`
+    #[default]
+    Whitespace,
+    /// Any whitespace within a paragraph, line breaks included (not blank lines)
+    WhitespaceAndNewlines,
+    /// Spaces and tabs around at most one comma: `[@a], [@b]`
+    Comma,
+    /// Every citation is a cluster of its own (Pandoc groups stay whole)
+    Never,
+}
+
+impl GroupingPolicy {
+    /// Returns true if citations separated by `between` belong to one cluster.
+    fn allows(self, between: &str) -> bool {
+        let is_blank = |c: char| c == ' ' || c == '\t';
+        match self {
+            GroupingPolicy::Whitespace => between.chars().all(is_blank),
+            GroupingPolicy::WhitespaceAndNewlines => {
+                between.chars().all(char::is_whitespace) && between.matches('\n').count() <= 1
+            }
+            GroupingPolicy::Comma => {
+                between.chars().all(|c| is_blank(c) || c == ',')
+                    && between.matches(',').count() <= 1
+            }
+            GroupingPolicy::Never => false,
+        }
+    }
+}
+
+/// Citation syntaxes beyond Pandoc's, off by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SyntaxExtensions {
+    /// Obsidian wikilinks: `[[@key]]`, with a locator as alias: `[[@key|p. 4]]`
+    pub wikilinks: bool,
+    /// Quarto and R Markdown: cross-references (`@fig-x`, `[@tbl-x]`...) are
+    /// not citations, and bookdown's `\@ref(label)` keeps its backslash
+    pub quarto: bool,
+}
+
+/// Prefixes of Quarto cross-reference labels.
+const QUARTO_CROSSREF_PREFIXES: [&str; 15] = [
+    "fig-", "tbl-", "sec-", "eq-", "lst-", "thm-", "lem-", "cor-", "prp-", "cnj-", "def-", "exm-",
+    "exr-", "sol-", "rem-",
+];
+
+/// Returns true if `key` is a Quarto cross-reference label, such as `fig-plot`.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::markdown::is_quarto_crossref;
+///
+/// assert!(is_quarto_crossref("fig-plot"));
+/// assert!(!is_quarto_crossref("figueroa2020"));
+/// ```
+pub fn is_quarto_crossref(key: &str) -> bool {
+    QUARTO_CROSSREF_PREFIXES
+        .iter()
+        .any(|prefix| key.starts_with(prefix))
+}
+
 /// Extracts citation clusters from the given Markdown text.
 ///
 /// This function detects adjacent citations (separated only by whitespace)
-/// and groups them into clusters. It also supports Pandoc syntax `[@a; @b; @c]`.
+/// and groups them into clusters. It also supports Pandoc syntax `[@a; @b; @c]`,
+/// whose groups merge with adjacent citations in the same way. See
+/// `extract_citation_clusters_with` for other grouping rules.
 ///
 /// # Arguments
 ///
@@ -122,92 +231,195 @@ pub struct CitationCluster {
 /// // Citations separated by text are not grouped
 /// let clusters = extract_citation_clusters("See [@a] and also [@b].");
 /// assert_eq!(clusters.len(), 2);
+///
+/// // Pandoc groups merge with adjacent citations
+/// let clusters = extract_citation_clusters("Studies [@a; @b] [@c] show that...");
+/// assert_eq!(clusters.len(), 1);
+/// assert_eq!(clusters[0].items.len(), 3);
 /// ```
 pub fn extract_citation_clusters(markdown: &str) -> Vec<CitationCluster> {
-    // First, extract Pandoc-style grouped citations [@a; @b; @c]
-    let pandoc_clusters = extract_pandoc_grouped_citations(markdown);
-
-    // Collect spans covered by Pandoc citations to avoid duplicates
-    let pandoc_spans: Vec<(usize, usize)> = pandoc_clusters.iter().map(|c| c.span).collect();
-
-    // Helper function to check if a position is inside a Pandoc citation
-    let is_inside_pandoc = |pos: usize| -> bool {
-        pandoc_spans
-            .iter()
-            .any(|(start, end)| pos >= *start && pos < *end)
-    };
+    extract_citation_clusters_with(markdown, GroupingPolicy::default())
+}
 
-    // Extract all individual citations using the existing function
-    let citations = extract_citations(markdown);
+/// Extracts citation clusters, grouping citations according to `policy`.
+///
+/// # Arguments
+///
+/// * `markdown` - The Markdown text to parse
+/// * `policy` - Which separators between citations group them
+///
+/// # Returns
+///
+/// A vector of `CitationCluster` structs representing all citation clusters found.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::markdown::{extract_citation_clusters_with, GroupingPolicy};
+///
+/// let markdown = "Studies [@a], [@b] show that...";
+/// assert_eq!(extract_citation_clusters_with(markdown, GroupingPolicy::Comma).len(), 1);
+/// assert_eq!(extract_citation_clusters_with(markdown, GroupingPolicy::Whitespace).len(), 2);
+/// ```
+pub fn extract_citation_clusters_with(
+    markdown: &str,
+    policy: GroupingPolicy,
+) -> Vec<CitationCluster> {
+    scan_citations(markdown, policy).clusters
+}
 
-    // Filter out citations that are inside Pandoc grouped citations
-    let simple_citations: Vec<Citation> = citations
-        .into_iter()
-        .filter(|c| !is_inside_pandoc(c.span.0))
-        .collect();
+/// Citations and clusters found by a single scan of a document.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CitationScan {
+    /// The citation clusters, in document order
+    pub clusters: Vec<CitationCluster>,
+    /// Every citation, the items of Pandoc groups included, in document order
+    pub citations: Vec<Citation>,
+}
 
-    // Group adjacent simple citations into clusters
-    let mut simple_clusters: Vec<CitationCluster> = Vec::new();
-    let mut current_items: Vec<CitationItem> = Vec::new();
-    let mut cluster_start: usize = 0;
-    let mut last_end: usize = 0;
+impl CitationScan {
+    /// Keeps the narrative citations (a bare `@name`) only when `name` is a
+    /// reference: a mention such as "ping @team" is then left as text.
+    /// Bracketed citations are all kept, to be reported if they are missing.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_reference` - Whether a key is a reference of the bibliography
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use csl_tools::markdown::{scan_citations, GroupingPolicy};
+    ///
+    /// let mut scan = scan_citations("@a told @team [@b].", GroupingPolicy::Whitespace);
+    /// scan.retain_narrative(|key| key == "a");
+    /// let ids: Vec<&str> = scan.citations.iter().map(|c| c.id.as_str()).collect();
+    /// assert_eq!(ids, vec!["a", "b"]);
+    /// ```
+    pub fn retain_narrative(&mut self, is_reference: impl Fn(&str) -> bool) {
+        // Narrative citations stand alone in their clusters
+        self.clusters
+            .retain(|cluster| match cluster.items.as_slice() {
+                [item] if item.mode == CitationMode::AuthorInText => is_reference(&item.id),
+                _ => true,
+            });
+        self.citations.retain(|citation| {
+            citation.mode != CitationMode::AuthorInText || is_reference(&citation.id)
+        });
+    }
+}
 
-    for citation in simple_citations {
-        let item = CitationItem {
-            id: citation.id,
-            locator: citation.locator,
-            label: citation.label,
-            url: citation.url,
-        };
+/// Scans a document once for both its citation clusters and its citations.
+///
+/// This is what `extract_citation_clusters_with` and `extract_citations` do
+/// separately, for callers that need both (clusters to format, citations for
+/// the bibliography) without reading a large document twice. Unlike
+/// `extract_citations`, the citations include the items of Pandoc groups,
+/// each spanning its own text within the brackets.
+///
+/// # Arguments
+///
+/// * `markdown` - The Markdown text to parse
+/// * `policy` - Which separators between citations group them
+///
+/// # Returns
+///
+/// The clusters and the citations of the document.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::markdown::{scan_citations, GroupingPolicy};
+///
+/// let scan = scan_citations("As @a showed [@b; @c].", GroupingPolicy::Whitespace);
+/// assert_eq!(scan.clusters.len(), 2);
+/// assert_eq!(scan.citations.len(), 3);
+/// ```
+pub fn scan_citations(markdown: &str, policy: GroupingPolicy) -> CitationScan {
+    scan_citations_with(markdown, policy, SyntaxExtensions::default())
+}
 
-        if current_items.is_empty() {
-            // First item in a potential cluster
-            cluster_start = citation.span.0;
-            last_end = citation.span.1;
-            current_items.push(item);
-        } else {
-            // Check if this citation is adjacent to the previous one
-            // "Adjacent" means only whitespace (spaces, tabs) between them
-            let between = &markdown[last_end..citation.span.0];
-            let is_adjacent = between.chars().all(|c| c == ' ' || c == '\t');
-
-            if is_adjacent {
-                // Add to current cluster
-                last_end = citation.span.1;
-                current_items.push(item);
-            } else {
-                // Start a new cluster - save the current one first
-                simple_clusters.push(CitationCluster {
-                    items: current_items,
-                    span: (cluster_start, last_end),
-                });
+/// Scans a document for its citation clusters and citations, also
+/// recognizing the syntaxes that `extensions` turns on.
+///
+/// Wikilink citations are parenthetical, and cluster with adjacent
+/// citations as bracketed ones do. Other wikilinks (`[[Note]]`) and embeds
+/// (`![[@key]]`) are left alone.
+///
+/// # Arguments
+///
+/// * `markdown` - The Markdown text to parse
+/// * `policy` - Which separators between citations group them
+/// * `extensions` - The extra syntaxes to recognize
+///
+/// # Returns
+///
+/// The clusters and the citations of the document.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::markdown::{scan_citations_with, GroupingPolicy, SyntaxExtensions};
+///
+/// let extensions = SyntaxExtensions { wikilinks: true, ..Default::default() };
+/// let scan = scan_citations_with("See [[@a|p. 4]] [[@b]].", GroupingPolicy::Whitespace, extensions);
+/// assert_eq!(scan.clusters.len(), 1);
+/// assert_eq!(scan.clusters[0].items[0].locator.as_deref(), Some("4"));
+/// ```
+pub fn scan_citations_with(
+    markdown: &str,
+    policy: GroupingPolicy,
+    extensions: SyntaxExtensions,
+) -> CitationScan {
+    let tokens = tokenize(markdown, extensions);
+
+    let citations = tokens
+        .iter()
+        .flat_map(|token| {
+            token.items.iter().map(move |item| {
+                let span = if token.grouped {
+                    item.position.span
+                } else {
+                    token.span
+                };
+                citation_from_item(item.clone(), span)
+            })
+        })
+        .collect();
 
-                // Start new cluster
-                current_items = vec![item];
-                cluster_start = citation.span.0;
-                last_end = citation.span.1;
+    // Merge adjacent tokens into clusters, as the grouping policy defines "adjacent"
+    let mut clusters: Vec<CitationCluster> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        if let Some(current) = clusters.last_mut() {
+            let is_adjacent = policy.allows(&markdown[current.span.1..token.span.0]);
+
+            // Narrative citations are part of the sentence: they always stand alone
+            let is_parenthetical = current
+                .items
+                .iter()
+                .chain(&token.items)
+                .all(|i| i.mode == CitationMode::Normal);
+
+            if is_adjacent && is_parenthetical {
+                current.span.1 = token.span.1;
+                current.items.extend(token.items);
+                continue;
             }
         }
-    }
-
-    // Don't forget the last cluster
-    if !current_items.is_empty() {
-        simple_clusters.push(CitationCluster {
-            items: current_items,
-            span: (cluster_start, last_end),
+        clusters.push(CitationCluster {
+            items: token.items,
+            span: token.span,
         });
     }
 
-    // Merge Pandoc clusters and simple clusters, sorted by position
-    let mut all_clusters: Vec<CitationCluster> = pandoc_clusters;
-    all_clusters.extend(simple_clusters);
-    all_clusters.sort_by_key(|c| c.span.0);
-
-    all_clusters
+    CitationScan {
+        clusters,
+        citations,
+    }
 }
 
 /// Represents a citation found in the Markdown text.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Citation {
     /// The citation key (e.g., "item-1" or "pmid:12345")
     pub id: String,
@@ -217,12 +429,25 @@ pub struct Citation {
     pub label: Option<String>,
     /// Optional URL associated with the citation
     pub url: Option<String>,
+    /// Parenthetical or narrative (`@id` without brackets)
+    pub mode: CitationMode,
+    /// Whether the author is omitted from the rendered citation (`[-@id]`)
+    pub suppress_author: bool,
+    /// Optional text before the citation (e.g., "see" in `[see @id]`)
+    pub prefix: Option<String>,
+    /// Optional text after the locator (e.g., "and passim" in `[@id, p. 3 and passim]`)
+    pub suffix: Option<String>,
     /// Start and end byte positions in the original text
     pub span: (usize, usize),
+    /// Line and column of the citation in the original text
+    pub position: SourcePosition,
 }
 
 /// Extracts all citations from the given Markdown text.
 ///
+/// The items of Pandoc groups (`[@a; @b]`) are not included; use
+/// `scan_citations` to get them too.
+///
 /// # Arguments
 ///
 /// * `markdown` - The Markdown text to parse
@@ -241,423 +466,2463 @@ pub struct Citation {
 /// assert_eq!(citations[0].id, "item-1");
 /// ```
 pub fn extract_citations(markdown: &str) -> Vec<Citation> {
-    // Regex for citation: [@id], [@id, locator], [@id](url), or [@id, locator](url)
-    // Group 1: id (required)
-    // Group 2: locator part after comma (optional)
-    // Group 3: url (optional)
-    let re = Regex::new(r"\[@([^\]\[,]+)(?:,\s*([^\]]+))?\](?:\(([^)]+)\))?").unwrap();
-
-    re.captures_iter(markdown)
-        .map(|cap| {
-            let full_match = cap.get(0).unwrap();
-            let id = cap.get(1).unwrap().as_str().trim().to_string();
-
-            // Parse the optional locator part
-            let (locator, label) = if let Some(locator_match) = cap.get(2) {
-                parse_locator(locator_match.as_str())
-            } else {
-                (None, None)
-            };
-
-            // Parse the optional URL
-            let url = cap.get(3).map(|m| m.as_str().to_string());
-
-            Citation {
-                id,
-                locator,
-                label,
-                url,
-                span: (full_match.start(), full_match.end()),
-            }
+    // The items of Pandoc groups only make sense within their cluster
+    tokenize(markdown, SyntaxExtensions::default())
+        .into_iter()
+        .filter(|token| !token.grouped)
+        .filter_map(|token| {
+            let item = token.items.into_iter().next()?;
+            Some(citation_from_item(item, token.span))
         })
         .collect()
 }
 
-/// Parses a locator string like "p. 42", "pp. 10-20", "ch. 3", "sec. 4.2"
-/// or full labels like "page 15", "pages 5-10", "chapter 7", "section 2.1"
+/// Builds the citation of a single item found at `span`.
+fn citation_from_item(item: CitationItem, span: (usize, usize)) -> Citation {
+    Citation {
+        id: item.id,
+        locator: item.locator,
+        label: item.label,
+        url: item.url,
+        mode: item.mode,
+        suppress_author: item.suppress_author,
+        prefix: item.prefix,
+        suffix: item.suffix,
+        span,
+        position: item.position,
+    }
+}
+
+/// A citation found by the tokenizer: a bracketed citation, a Pandoc group
+/// or a narrative citation.
+struct Token {
+    /// The citation items (several for a Pandoc group)
+    items: Vec<CitationItem>,
+    /// Start and end byte positions, the `(url)` of a bracketed citation included
+    span: (usize, usize),
+    /// Whether this is a Pandoc group (`[@a; @b]`)
+    grouped: bool,
+}
+
+/// Finds every citation of `markdown` in a single left-to-right pass.
 ///
-/// Returns (locator_value, label) tuple.
-fn parse_locator(locator_str: &str) -> (Option<String>, Option<String>) {
-    let locator_str = locator_str.trim();
+/// - A bracket pair without nested brackets (except inside braced keys) is a
+///   citation `[see @a, p. 4]`, or a Pandoc group `[@a; -@b]` if it holds
+///   several `;`-separated items; every item must parse, otherwise the
+///   brackets are ordinary text. An optional `(url)` may follow.
+/// - An `@key` not preceded by a word character (or another `@`) is a
+///   narrative citation, unless it is inside brackets, a link destination or
+///   a URL.
+/// - With wikilinks on, `[[...]]` on a single line is a wikilink: a citation
+///   if it targets `@key`, otherwise ordinary text, brackets inside included.
+/// - In Quarto mode, citations of cross-reference labels are ordinary text,
+///   and cross-references mixed with citation items are kept as affixes (see
+///   `without_crossrefs`).
+///
+/// Each byte is examined a bounded number of times, so the scan is linear in
+/// the size of the document.
+fn tokenize(markdown: &str, extensions: SyntaxExtensions) -> Vec<Token> {
+    // Code and comments are blanked out, byte offsets are unchanged
+    let prose = mask_non_prose(markdown);
+    let bytes = prose.as_bytes();
+    // Line and column are counted on the original text: masking may replace
+    // a multi-byte character by several spaces
+    let mut index = LineIndex::new(markdown);
+    let mut closing_paren = NextByte::new(b')');
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let as_citation = |token: Token| {
+        if extensions.quarto {
+            without_crossrefs(token, &prose)
+        } else {
+            Some(token)
+        }
+    };
 
-    // Define patterns for different locator types
-    // Order matters: check longer prefixes before shorter ones to avoid partial matches
-    let patterns = [
-        // Abbreviations (pp. before p.)
-        ("pp.", "page"),
-        ("p.", "page"),
-        ("ch.", "chapter"),
-        ("sec.", "section"),
-        // Full words (pages before page)
-        ("pages", "page"),
-        ("page", "page"),
-        ("chapter", "chapter"),
-        ("section", "section"),
-    ];
-
-    for (prefix, label) in patterns {
-        if let Some(stripped) = locator_str.strip_prefix(prefix) {
-            let value = stripped.trim().to_string();
-            if !value.is_empty() {
-                return (Some(value), Some(label.to_string()));
+    while i < bytes.len() {
+        match bytes[i] {
+            b'[' if extensions.wikilinks && bytes.get(i + 1) == Some(&b'[') => {
+                let Some(close) = wikilink_end(bytes, i) else {
+                    i += 1;
+                    continue;
+                };
+                let end = close + 2;
+                let is_embed = i > 0 && bytes[i - 1] == b'!';
+                if let Some(token) = (!is_embed)
+                    .then(|| wikilink_token(&prose[i + 2..close], (i, end), &mut index))
+                    .flatten()
+                    .and_then(as_citation)
+                {
+                    tokens.push(token);
+                }
+                i = end;
             }
+            b'[' => {
+                let Some(close) = bracket_end(bytes, i) else {
+                    i += 1;
+                    continue;
+                };
+                let inner = &prose[i + 1..close];
+                if !inner.contains('@') {
+                    // Ordinary brackets, such as a link text
+                    i += 1;
+                    continue;
+                }
+
+                // Optional (url), with at least one character
+                let mut end = close + 1;
+                let mut url = None;
+                if bytes.get(end) == Some(&b'(') {
+                    if let Some(paren) = closing_paren.find(bytes, end + 1) {
+                        if paren > end + 1 {
+                            url = Some(&prose[end + 1..paren]);
+                            end = paren + 1;
+                        }
+                    }
+                }
+
+                match bracket_token(inner, i + 1, url, (i, end), &mut index).and_then(as_citation) {
+                    Some(token) => {
+                        tokens.push(token);
+                        i = end;
+                    }
+                    // Brackets holding an @ are never narrative citations;
+                    // resume on the closing bracket, which may open a link
+                    // destination
+                    None => i = close,
+                }
+            }
+            // Link destination: `](...)`
+            b']' if bytes.get(i + 1) == Some(&b'(') => {
+                i = match closing_paren.find(bytes, i + 2) {
+                    Some(paren) => paren + 1,
+                    None => i + 1,
+                };
+            }
+            // URL: `scheme://...`
+            b':' if bytes[i..].starts_with(b"://") && is_url_scheme(&prose[..i]) => {
+                let length: usize = prose[i + 3..]
+                    .chars()
+                    .take_while(|&c| !c.is_whitespace() && !matches!(c, '<' | '>' | ')'))
+                    .map(char::len_utf8)
+                    .sum();
+                i += if length > 0 { 3 + length } else { 1 };
+            }
+            b'@' => {
+                let starts_word = !prose[..i]
+                    .chars()
+                    .next_back()
+                    .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '@');
+                match starts_word
+                    .then(|| parse_citation_key(&prose[i + 1..]))
+                    .flatten()
+                {
+                    Some((id, key_len)) => {
+                        let (token, end) = narrative_token(&prose, i, id, key_len, &mut index);
+                        tokens.extend(as_citation(token));
+                        i = end;
+                    }
+                    None => i += 1,
+                }
+            }
+            _ => i += 1,
         }
     }
 
-    // If no recognized label, return the raw locator with no label
-    if !locator_str.is_empty() {
-        (Some(locator_str.to_string()), None)
-    } else {
-        (None, None)
-    }
+    tokens
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Takes the Quarto cross-references (`@fig-a`) out of a citation.
+///
+/// A citation of cross-references only is ordinary text. In one that mixes
+/// them with citation items, each cross-reference stays in the text as an
+/// affix of its neighbour item, for Quarto to resolve: `[@fig-a; @smith]` is
+/// cited as `(@fig-a; Smith 2020)` and `[@smith; @fig-a]` as
+/// `(Smith 2020; @fig-a)`.
+fn without_crossrefs(mut token: Token, prose: &str) -> Option<Token> {
+    if !token.items.iter().any(|item| is_quarto_crossref(&item.id)) {
+        return Some(token);
+    }
 
-    #[test]
-    fn test_empty_text() {
-        let citations = extract_citations("");
-        assert!(citations.is_empty());
+    let mut items: Vec<CitationItem> = Vec::new();
+    let mut leading = Vec::new();
+    for item in token.items {
+        if !is_quarto_crossref(&item.id) {
+            items.push(item);
+            continue;
+        }
+        // The item's own text, with its prefix and locator
+        let (start, end) = item.position.span;
+        let text = &prose[start..end];
+        match items.last_mut() {
+            Some(previous) => {
+                previous.suffix = Some(match previous.suffix.take() {
+                    Some(suffix) => format!("{}; {}", suffix, text),
+                    None => format!("; {}", text),
+                });
+            }
+            None => leading.push(text),
+        }
     }
 
-    #[test]
-    fn test_no_citations() {
-        let citations = extract_citations("This is plain text without citations.");
-        assert!(citations.is_empty());
+    let first = items.first_mut()?;
+    if !leading.is_empty() {
+        let leading = format!("{};", leading.join("; "));
+        first.prefix = Some(match first.prefix.take() {
+            Some(prefix) => format!("{} {}", leading, prefix),
+            None => leading,
+        });
     }
+    token.items = items;
+    Some(token)
+}
 
-    // Sub-phase 2.1: Basic citation parsing tests
+/// Finds the `]` closing the bracket opened at `open`, if the content has no
+/// nested brackets outside `{...}` groups and no stray braces. A `{...}`
+/// group holds no `]` and no line break.
+fn bracket_end(bytes: &[u8], open: usize) -> Option<usize> {
+    let mut i = open + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b']' => return Some(i),
+            b'[' | b'}' => return None,
+            // A braced key, closed before the next `]` or line break, so that
+            // an unclosed brace (inline LaTeX, a template) is not searched
+            // past its bracket
+            b'{' => {
+                let close = bytes[i + 1..]
+                    .iter()
+                    .position(|&b| matches!(b, b'{' | b'}' | b']' | b'\n'))?;
+                if bytes[i + 1 + close] != b'}' {
+                    return None;
+                }
+                i += close + 2;
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
 
-    #[test]
-    fn test_simple_citation() {
-        // Given: Markdown with a simple citation
-        let markdown = "Les résultats montrent [@item-1] que la méthode fonctionne.";
+/// Finds the `]]` closing the wikilink opened at `open`, on the same line
+/// and without nested brackets.
+fn wikilink_end(bytes: &[u8], open: usize) -> Option<usize> {
+    let mut i = open + 2;
+    while i < bytes.len() {
+        match bytes[i] {
+            b']' if bytes.get(i + 1) == Some(&b']') => return Some(i),
+            b'[' | b']' | b'\n' => return None,
+            _ => i += 1,
+        }
+    }
+    None
+}
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+/// Parses the inner text of a wikilink, `@key` or `@key|alias`, as a
+/// citation whose alias is a locator (with an optional suffix).
+fn wikilink_token(inner: &str, span: (usize, usize), index: &mut LineIndex) -> Option<Token> {
+    let (target, alias) = match inner.split_once('|') {
+        Some((target, alias)) => (target, Some(alias)),
+        None => (inner, None),
+    };
+    let key = target.trim().strip_prefix('@')?;
+    // `[[@smith2020#Summary]]` links to a heading of a note
+    if key.contains('#') {
+        return None;
+    }
+    let (id, key_len) = parse_citation_key(key)?;
+    if key_len != key.len() {
+        return None;
+    }
+    let (locator, label, suffix) = alias.map_or((None, None, None), parse_locator);
+
+    let item = CitationItem {
+        id: id.to_string(),
+        locator,
+        label,
+        url: None,
+        mode: CitationMode::Normal,
+        suppress_author: false,
+        prefix: None,
+        suffix,
+        position: index.position(span),
+    };
+    Some(Token {
+        items: vec![item],
+        span,
+        grouped: false,
+    })
+}
 
-        // Then: We find one citation with the correct id
-        assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "item-1");
-        assert_eq!(citations[0].locator, None);
-        assert_eq!(citations[0].label, None);
-        assert_eq!(citations[0].url, None);
+/// Parses the inner text of a bracket pair as a citation or a Pandoc group.
+///
+/// `inner_start` is the position of `inner` in the document and `span` the
+/// span of the whole token.
+fn bracket_token(
+    inner: &str,
+    inner_start: usize,
+    url: Option<&str>,
+    span: (usize, usize),
+    index: &mut LineIndex,
+) -> Option<Token> {
+    let parts = split_outside_braces(inner, ';');
+
+    if parts.len() == 1 {
+        let mut item = parse_bracketed_item(inner)?;
+        item.url = url.map(str::to_string);
+        item.position = index.position(span);
+        return Some(Token {
+            items: vec![item],
+            span,
+            grouped: false,
+        });
     }
 
-    #[test]
-    fn test_pmid_format_citation() {
-        // Given: Markdown with a PMID-prefixed citation key
-        let markdown = "L'étude [@pmid:12345678] a démontré ces résultats.";
+    // Parse each citation item; if one part is not a citation item, the
+    // brackets are ordinary text
+    let items = parts
+        .into_iter()
+        .filter(|(_, part)| !part.trim().is_empty())
+        .map(|(offset, part)| {
+            let mut item = parse_bracketed_item(part)?;
+            // The URL of the group applies to each of its items
+            item.url = url.map(str::to_string);
+            // Each item points at its own text within the brackets
+            let start = inner_start + offset + (part.len() - part.trim_start().len());
+            item.position = index.position((start, start + part.trim().len()));
+            Some(item)
+        })
+        .collect::<Option<Vec<_>>>()?;
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+    (!items.is_empty()).then_some(Token {
+        items,
+        span,
+        grouped: true,
+    })
+}
 
-        // Then: We find one citation with the PMID key
-        assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "pmid:12345678");
+/// Builds the token of a narrative citation whose `@` is at `at`, with its
+/// optional bracketed locator (`@smith2020 [p. 4]`), and returns where it ends.
+fn narrative_token(
+    prose: &str,
+    at: usize,
+    id: &str,
+    key_len: usize,
+    index: &mut LineIndex,
+) -> (Token, usize) {
+    let mut end = at + 1 + key_len;
+
+    // Optional locator and suffix in brackets, with at most one space before it
+    let (mut locator, mut label, mut suffix) = (None, None, None);
+    if let Some((inner, bracket_end)) = narrative_locator(prose, end) {
+        (locator, label, suffix) = parse_locator(inner);
+        end = bracket_end;
     }
 
-    #[test]
-    fn test_citation_span() {
-        // Given: Markdown with a citation
-        let markdown = "Text [@item-1] more text.";
+    let item = CitationItem {
+        id: id.to_string(),
+        locator,
+        label,
+        url: None,
+        mode: CitationMode::AuthorInText,
+        suppress_author: false,
+        prefix: None,
+        suffix,
+        position: index.position((at, end)),
+    };
+    let token = Token {
+        items: vec![item],
+        span: (at, end),
+        grouped: false,
+    };
+    (token, end)
+}
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+/// Returns true if `before` ends with a URL scheme (`https`, `svn+ssh`...):
+/// a letter followed by letters, digits, `+`, `.` or `-`.
+fn is_url_scheme(before: &str) -> bool {
+    let is_scheme_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '+' | '.' | '-');
+    let scheme_start = before
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_scheme_char(c))
+        .last()
+        .map(|(i, _)| i);
+    // Leading characters that cannot start a scheme are skipped, as the
+    // scheme may start later in the run (`1http://`)
+    scheme_start.is_some_and(|start| before[start..].chars().any(|c| c.is_ascii_alphabetic()))
+}
 
-        // Then: The span correctly points to the citation in the text
-        assert_eq!(citations.len(), 1);
-        let (start, end) = citations[0].span;
-        assert_eq!(&markdown[start..end], "[@item-1]");
-    }
+/// Finds the next occurrence of a byte, remembering the last result so that
+/// repeated searches over the same text stay linear.
+struct NextByte {
+    byte: u8,
+    /// Position searched from and result of the last search
+    last: Option<(usize, Option<usize>)>,
+}
 
-    #[test]
-    fn test_multiple_citations() {
-        // Given: Markdown with multiple citations
-        let markdown = "First [@item-1] and second [@item-2] citations.";
+impl NextByte {
+    fn new(byte: u8) -> Self {
+        NextByte { byte, last: None }
+    }
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+    /// Returns the position of the first occurrence at or after `from`.
+    fn find(&mut self, bytes: &[u8], from: usize) -> Option<usize> {
+        if let Some((searched_from, found)) = self.last {
+            // The previous answer still holds if it lies ahead of `from`
+            if searched_from <= from && !matches!(found, Some(pos) if pos < from) {
+                return found;
+            }
+        }
+        let found = bytes
+            .get(from..)
+            .and_then(|rest| rest.iter().position(|&b| b == self.byte))
+            .map(|pos| from + pos);
+        self.last = Some((from, found));
+        found
+    }
+}
 
-        // Then: We find both citations in order
-        assert_eq!(citations.len(), 2);
-        assert_eq!(citations[0].id, "item-1");
-        assert_eq!(citations[1].id, "item-2");
+/// Finds a bracketed locator such as ` [p. 4]` right after a narrative key
+/// ending at `key_end`, returning its inner text and end position.
+///
+/// Link texts (`[text](url)`), footnote references (`[^1]`) and brackets
+/// holding another citation are not locators.
+fn narrative_locator(prose: &str, key_end: usize) -> Option<(&str, usize)> {
+    let rest = &prose[key_end..];
+    let after_space = rest.strip_prefix(' ').unwrap_or(rest);
+    let bracket_start = key_end + (rest.len() - after_space.len());
+
+    let inner_and_rest = after_space.strip_prefix('[')?;
+    // Stopping at a nested bracket keeps the scan from running ahead
+    let close = inner_and_rest.find(['[', ']'])?;
+    if inner_and_rest.as_bytes()[close] == b'[' {
+        return None;
+    }
+    let inner = &inner_and_rest[..close];
+    let bracket_end = bracket_start + 1 + close + 1;
+
+    if inner.trim().is_empty()
+        || inner.starts_with('^')
+        || inner.contains('@')
+        || prose[bracket_end..].starts_with('(')
+    {
+        return None;
     }
 
-    #[test]
-    fn test_citation_with_special_chars() {
-        // Given: Markdown with citation keys containing special characters
-        let markdown = "See [@doi:10.1234/test_key] for details.";
+    Some((inner, bracket_end))
+}
 
-        // When: We extract citations
+/// Parses one item of a bracketed citation: `[prefix] [-]@id[, locator] [suffix]`.
+///
+/// The key starts at the first `@` that begins a word (or follows a `-`), so
+/// `see @a` has the prefix "see" while `foo@bar.com` is not a citation item.
+/// Text after the key that is not introduced by a comma is a suffix.
+///
+/// Returns `None` when the text contains no citation key.
+fn parse_bracketed_item(part: &str) -> Option<CitationItem> {
+    let part = part.trim();
+
+    let at = part.match_indices('@').map(|(i, _)| i).find(|&i| {
+        let before = part[..i].trim_end_matches('-');
+        let dashes = i - before.len();
+        dashes <= 1 && (before.is_empty() || before.ends_with(char::is_whitespace))
+    })?;
+
+    let suppress_author = part[..at].ends_with('-');
+    let prefix = part[..at].strip_suffix('-').unwrap_or(&part[..at]).trim();
+
+    let after = &part[at + 1..];
+    let (id, key_len) = parse_citation_key(after)?;
+
+    let rest = after[key_len..].trim();
+    let (locator, label, suffix) = match rest.strip_prefix(',') {
+        Some(locator_str) => match parse_locator(locator_str) {
+            // Without a locator, the comma is part of the suffix
+            (None, _, _) => (None, None, Some(rest.to_string())),
+            parsed => parsed,
+        },
+        None if rest.is_empty() => (None, None, None),
+        None => (None, None, Some(rest.to_string())),
+    };
+
+    Some(CitationItem {
+        id: id.to_string(),
+        locator,
+        label,
+        url: None,
+        mode: CitationMode::Normal,
+        suppress_author,
+        prefix: (!prefix.is_empty()).then(|| prefix.to_string()),
+        suffix,
+        position: SourcePosition::default(),
+    })
+}
+
+/// Parses the citation key at the start of `text` (just after the `@`).
+///
+/// Follows Pandoc's rules: a key starts with an alphanumeric character or
+/// `_`, and may contain the punctuation `:.#$%&-+?<>~/` when it is followed
+/// by another key character (or `//`, for URLs), so trailing punctuation
+/// belongs to the sentence. Any other key is written in braces:
+/// `@{arbitrary key, with commas}`.
+///
+/// Returns the key and the number of bytes it spans in `text` (braces
+/// included), or `None` if `text` does not start with a key.
+pub(crate) fn parse_citation_key(text: &str) -> Option<(&str, usize)> {
+    if let Some(braced) = text.strip_prefix('{') {
+        // Nested braces must be balanced
+        let mut depth = 1;
+        for (i, c) in braced.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let key = braced[..i].trim();
+                        return (!key.is_empty()).then_some((key, i + 2));
+                    }
+                }
+                _ => {}
+            }
+        }
+        return None;
+    }
+
+    let is_key_char = |c: char| c.is_alphanumeric() || c == '_';
+    let mut chars = text.char_indices().peekable();
+    let mut len = 0;
+
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        let accepted = is_key_char(c)
+            || (len > 0 && ":.#$%&-+?<>~/".contains(c) && next.is_some_and(is_key_char))
+            || (len > 0 && ":/".contains(c) && next == Some('/'));
+        if !accepted {
+            break;
+        }
+        len = i + c.len_utf8();
+    }
+
+    (len > 0).then(|| (&text[..len], len))
+}
+
+/// Splits `text` on `separator`, except inside braced citation keys.
+///
+/// Returns each part with its byte offset in `text`.
+fn split_outside_braces(text: &str, separator: char) -> Vec<(usize, &str)> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut part_start = 0;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            c if c == separator && depth == 0 => {
+                parts.push((part_start, &text[part_start..i]));
+                part_start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push((part_start, &text[part_start..]));
+
+    parts
+}
+
+/// Parses a locator string like "p. 42", "pp. 10-20", "chap. 3", "Bd. 2"
+/// or full labels like "page 15", "chapter 7", "Seite 4", followed by an
+/// optional suffix: "pp. 3-5 and passim". Labels are looked up in the CSL
+/// locator table (see [`crate::locator`]).
+///
+/// As in Pandoc, the locator value is made of the words that contain a digit
+/// (or a roman numeral, after a label); everything from the first other word
+/// on is the suffix. A "sub verbo" value is the headword itself, up to the
+/// first comma. Without a recognized label, the value stays unlabeled.
+///
+/// Returns (locator_value, label, suffix) tuple.
+pub(crate) fn parse_locator(locator_str: &str) -> (Option<String>, Option<String>, Option<String>) {
+    let locator_str = locator_str.trim();
+
+    if let Some((label, rest)) = split_label(locator_str) {
+        let rest = rest.trim();
+        let (value, suffix) = if label == "sub-verbo" {
+            let (value, suffix) = rest.split_at(rest.find(',').unwrap_or(rest.len()));
+            (
+                (!value.trim().is_empty()).then(|| value.trim().to_string()),
+                (!suffix.is_empty()).then(|| suffix.to_string()),
+            )
+        } else {
+            split_locator_value(rest, true)
+        };
+        if let Some(value) = value {
+            return (Some(value), Some(label.to_string()), suffix);
+        }
+    }
+
+    // If no recognized label, the leading value (if any) is an unlabeled locator
+    let (value, suffix) = split_locator_value(locator_str, false);
+    (value, None, suffix)
+}
+
+/// Splits `text` into a locator value and the suffix that follows it.
+///
+/// The value is the longest run of leading words that contain a digit (or, if
+/// `allow_roman` is set, are roman numerals such as "xii" or "iv-vi"). Roman
+/// numerals only make up a value that has no digit yet, so "4 mix" gives "4"
+/// and "mix". A comma ending the value belongs to the suffix: "33, 35-37, and
+/// passim" gives "33, 35-37" and ", and passim".
+fn split_locator_value(text: &str, allow_roman: bool) -> (Option<String>, Option<String>) {
+    let mut has_digit = false;
+    let mut value_end = 0;
+    let mut offset = 0;
+    for word in text.split_inclusive(char::is_whitespace) {
+        let core = word.trim_end().trim_end_matches([',', '.', ';', ':']);
+        let core = core.trim_matches(|c: char| c == '-' || c == '\u{2013}');
+        if core.chars().any(|c| c.is_ascii_digit()) {
+            has_digit = true;
+        } else if !(allow_roman && !has_digit && is_roman_range(core)) {
+            break;
+        }
+        value_end = offset + word.trim_end().len();
+        offset += word.len();
+    }
+
+    let value = text[..value_end].trim_end_matches(',');
+    let suffix = text[value.len()..].trim();
+
+    (
+        (!value.is_empty()).then(|| value.to_string()),
+        (!suffix.is_empty()).then(|| suffix.to_string()),
+    )
+}
+
+/// Tells whether `text` is a well-formed roman numeral, or a range of them
+/// joined by `-` or `–`, in either case.
+fn is_roman_range(text: &str) -> bool {
+    static ROMAN_RE: OnceLock<Regex> = OnceLock::new();
+    let roman = ROMAN_RE.get_or_init(|| {
+        Regex::new(r"(?i)^m{0,4}(cm|cd|d?c{0,3})(xc|xl|l?x{0,3})(ix|iv|v?i{0,3})$").unwrap()
+    });
+
+    text.split(['-', '\u{2013}'])
+        .all(|part| !part.is_empty() && roman.is_match(part))
+}
+
+/// Finds the backslashes escaping citations, such as `\[@key]` or `\@key`.
+///
+/// Escaped citations are never extracted; the caller drops these backslashes
+/// from the output so that the citation text appears literally. Backslashes
+/// inside code or comments are not reported.
+///
+/// # Arguments
+///
+/// * `markdown` - The Markdown text to search
+///
+/// # Returns
+///
+/// The byte positions of the escaping backslashes, in document order.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::markdown::find_citation_escapes;
+///
+/// let escapes = find_citation_escapes(r"Write \[@key] or \@key to cite.");
+/// assert_eq!(escapes, vec![6, 17]);
+/// ```
+pub fn find_citation_escapes(markdown: &str) -> Vec<usize> {
+    // Code blocks, code spans and comments never start with a backslash, so
+    // the ranges that do are exactly the escaped citations
+    non_prose_ranges(markdown)
+        .into_iter()
+        .map(|(start, _)| start)
+        .filter(|&start| markdown.as_bytes()[start] == b'\\')
+        .collect()
+}
+
+/// Finds the backslashes escaping citations, as `find_citation_escapes`,
+/// leaving out those that `extensions` gives a meaning: in Quarto mode,
+/// bookdown cross-references `\@ref(label)` keep their backslash.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::markdown::{find_citation_escapes_with, SyntaxExtensions};
+///
+/// let markdown = r"See Figure \@ref(fig:plot), not \@key.";
+/// let quarto = SyntaxExtensions { quarto: true, ..Default::default() };
+/// assert_eq!(find_citation_escapes_with(markdown, quarto), vec![32]);
+/// ```
+pub fn find_citation_escapes_with(markdown: &str, extensions: SyntaxExtensions) -> Vec<usize> {
+    find_citation_escapes(markdown)
+        .into_iter()
+        .filter(|&pos| !(extensions.quarto && markdown[pos..].starts_with("\\@ref(")))
+        .collect()
+}
+
+/// Finds where the bibliography should be inserted: a `<!-- bibliography -->`
+/// comment or a Pandoc `::: {#refs}` div, on lines of their own.
+///
+/// Markers inside code blocks are ignored. The span covers the whole marker
+/// (for a div, from its opening fence to its closing fence, if any), without
+/// the final newline.
+///
+/// # Returns
+///
+/// The byte span of the first marker, or `None` if there is none.
+pub(crate) fn find_bibliography_marker(markdown: &str) -> Option<(usize, usize)> {
+    // Compiled once: this runs for every notebook cell and reference section
+    static COMMENT_RE: OnceLock<Regex> = OnceLock::new();
+    static DIV_OPEN_RE: OnceLock<Regex> = OnceLock::new();
+    static DIV_CLOSE_RE: OnceLock<Regex> = OnceLock::new();
+    let comment_re =
+        COMMENT_RE.get_or_init(|| Regex::new(r"^\s*<!--\s*bibliography\s*-->\s*$").unwrap());
+    let div_open_re =
+        DIV_OPEN_RE.get_or_init(|| Regex::new(r"^\s*:{3,}\s*\{#refs(?:\s[^}]*)?\}\s*$").unwrap());
+    let div_close_re = DIV_CLOSE_RE.get_or_init(|| Regex::new(r"^\s*:{3,}\s*$").unwrap());
+
+    let lines = prose_lines(markdown);
+    for (i, &(start, content)) in lines.iter().enumerate() {
+        if comment_re.is_match(content) {
+            return Some((start, start + content.len()));
+        }
+        if div_open_re.is_match(content) {
+            let end = lines[i + 1..]
+                .iter()
+                .find(|(_, line)| div_close_re.is_match(line))
+                .map_or(start + content.len(), |&(close, line)| close + line.len());
+            return Some((start, end));
+        }
+    }
+
+    None
+}
+
+/// Splits `markdown` into reference sections, each getting its own
+/// bibliography.
+///
+/// A new section starts at every `<!-- refsection -->` line and, if
+/// `heading_level` is given, at every ATX heading of that level (`#` for 1,
+/// `##` for 2...). Text before the first boundary is a section of its own.
+/// Boundaries inside code blocks are ignored.
+///
+/// # Arguments
+///
+/// * `markdown` - The Markdown text to split
+/// * `heading_level` - Optional heading level starting a new section
+///
+/// # Returns
+///
+/// The byte ranges of the sections, covering the whole text in order. A text
+/// without boundaries is a single section.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::markdown::refsection_ranges;
+///
+/// let markdown = "# One\n[@a]\n# Two\n[@b]\n";
+/// assert_eq!(refsection_ranges(markdown, Some(1)), vec![(0, 11), (11, 22)]);
+/// ```
+pub fn refsection_ranges(markdown: &str, heading_level: Option<usize>) -> Vec<(usize, usize)> {
+    static MARKER_RE: OnceLock<Regex> = OnceLock::new();
+    let marker_re =
+        MARKER_RE.get_or_init(|| Regex::new(r"^\s*<!--\s*refsection\s*-->\s*$").unwrap());
+    let is_section_heading = |content: &str| {
+        let trimmed = content.trim_start_matches(' ');
+        let hashes = trimmed.bytes().take_while(|&b| b == b'#').count();
+        content.len() - trimmed.len() <= 3
+            && heading_level == Some(hashes)
+            && matches!(trimmed.as_bytes().get(hashes), None | Some(b' ' | b'\t'))
+    };
+
+    let mut boundaries: Vec<usize> = prose_lines(markdown)
+        .into_iter()
+        .filter(|&(start, content)| {
+            start > 0 && (marker_re.is_match(content) || is_section_heading(content))
+        })
+        .map(|(start, _)| start)
+        .collect();
+    boundaries.push(markdown.len());
+
+    let mut ranges = Vec::with_capacity(boundaries.len());
+    let mut start = 0;
+    for end in boundaries {
+        ranges.push((start, end));
+        start = end;
+    }
+    ranges
+}
+
+/// Parses the keys of a `nocite` entry, such as "@a, @b" or "@*".
+///
+/// Keys are separated by commas, semicolons or whitespace, and follow the
+/// citation key grammar; the `@` is optional. The `@*` wildcard, which stands
+/// for every reference, is returned as "*".
+///
+/// # Arguments
+///
+/// * `text` - The nocite entry (front matter value or `--nocite` argument)
+///
+/// # Returns
+///
+/// The keys, in order.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::markdown::parse_nocite_keys;
+///
+/// assert_eq!(parse_nocite_keys("@a, @{b c}; d"), vec!["a", "b c", "d"]);
+/// assert_eq!(parse_nocite_keys("@*"), vec!["*"]);
+/// ```
+pub fn parse_nocite_keys(text: &str) -> Vec<String> {
+    let is_separator = |c: char| c == ',' || c == ';' || c.is_whitespace();
+    let mut keys = Vec::new();
+    let mut rest = text.trim_start_matches(is_separator);
+
+    while !rest.is_empty() {
+        let key = rest.strip_prefix('@').unwrap_or(rest);
+        let consumed = if key.starts_with('*') {
+            keys.push("*".to_string());
+            1
+        } else if let Some((id, len)) = parse_citation_key(key) {
+            keys.push(id.to_string());
+            len
+        } else {
+            // Not a key: skip to the next separator
+            match key.find(is_separator).unwrap_or(key.len()) {
+                0 => key.chars().next().map_or(0, char::len_utf8),
+                end => end,
+            }
+        };
+        let consumed = consumed.min(key.len());
+        rest = key[consumed..].trim_start_matches(is_separator);
+    }
+
+    keys
+}
+
+/// Returns the lines of `markdown` outside code blocks, with their byte
+/// offset and without their line ending.
+fn prose_lines(markdown: &str) -> Vec<(usize, &str)> {
+    let code_blocks = code_block_ranges(markdown);
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in markdown.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        if !code_blocks.iter().any(|&(s, e)| start >= s && start < e) {
+            lines.push((start, line.trim_end_matches(['\n', '\r'])));
+        }
+    }
+    lines
+}
+
+/// Returns a copy of `markdown` where everything that is not prose is blanked out.
+///
+/// Fenced and indented code blocks, inline code spans, HTML comments and
+/// escaped citations are replaced by spaces (newlines are kept). The result
+/// has exactly the same byte length as the input, so any span found in it is
+/// valid in the original text.
+pub(crate) fn mask_non_prose(markdown: &str) -> String {
+    let mut masked = markdown.as_bytes().to_vec();
+
+    for (start, end) in non_prose_ranges(markdown) {
+        for byte in &mut masked[start..end] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+    }
+
+    // Ranges always start and end on char boundaries, so whole characters are
+    // replaced and the buffer stays valid UTF-8
+    String::from_utf8(masked).expect("masking whole characters keeps valid UTF-8")
+}
+
+/// Finds the byte ranges of code (blocks and inline spans) and HTML comments.
+///
+/// Block-level code is located first; inline code spans and comments are then
+/// searched only in the prose between those blocks. The ranges are returned
+/// in document order and never overlap.
+fn non_prose_ranges(markdown: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut prose_start = 0;
+
+    for (start, end) in code_block_ranges(markdown) {
+        ranges.extend(inline_non_prose_ranges(markdown, prose_start, start));
+        ranges.push((start, end));
+        prose_start = end;
+    }
+    ranges.extend(inline_non_prose_ranges(
+        markdown,
+        prose_start,
+        markdown.len(),
+    ));
+
+    ranges
+}
+
+/// Finds fenced (```` ``` ```` / `~~~`) and indented code blocks, line by line.
+///
+/// Follows the CommonMark rules closely enough for citation scanning:
+/// - a fence closes on a line with at least as many fence characters, and an
+///   unclosed fence runs to the end of the document;
+/// - an indented block (4+ columns) cannot interrupt a paragraph, and indented
+///   lines inside a list (or a footnote definition) are its content, not code.
+///
+/// A YAML front matter block is returned as the first range, so that its
+/// values are never read as citations.
+fn code_block_ranges(markdown: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    // Open fence: (fence character, fence length, block start)
+    let mut fence: Option<(u8, usize, usize)> = None;
+    // Open indented block: (block start, end of its last non-blank line)
+    let mut indented: Option<(usize, usize)> = None;
+    let mut paragraph_open = false;
+    let mut in_list = false;
+    let mut offset = 0;
+
+    if let Some((start, end)) = front_matter_span(markdown) {
+        ranges.push((start, end));
+        offset = end;
+    }
+
+    for line in markdown[offset..].split_inclusive('\n') {
+        let line_start = offset;
+        let line_end = offset + line.len();
+        offset = line_end;
+
+        let content = line.trim_end_matches(['\n', '\r']);
+        let trimmed = content.trim_start();
+        let blank = trimmed.is_empty();
+        let indent = indent_width(content);
+
+        if let Some((ch, len, start)) = fence {
+            if is_closing_fence(trimmed, ch, len) {
+                ranges.push((start, line_end));
+                fence = None;
+            }
+            continue;
+        }
+
+        if let Some((start, last_end)) = indented {
+            if blank || indent >= 4 {
+                if !blank {
+                    indented = Some((start, line_end));
+                }
+                continue;
+            }
+            // Trailing blank lines are not part of the block
+            ranges.push((start, last_end));
+            indented = None;
+        }
+
+        if blank {
+            paragraph_open = false;
+            continue;
+        }
+
+        if indent >= 4 && !paragraph_open && !in_list {
+            indented = Some((line_start, line_end));
+            continue;
+        }
+
+        if indent <= 3 || in_list {
+            if let Some((ch, len)) = opening_fence(trimmed) {
+                fence = Some((ch, len, line_start));
+                paragraph_open = false;
+                continue;
+            }
+        }
+
+        if indent <= 3 && (is_list_item(trimmed) || is_footnote_definition(trimmed)) {
+            in_list = true;
+        } else if indent == 0 && !paragraph_open {
+            // A non-indented line after a blank line ends the list
+            in_list = false;
+        }
+        // ATX headings are single-line blocks: they never leave a paragraph open
+        paragraph_open = !trimmed.starts_with('#');
+    }
+
+    if let Some((_, _, start)) = fence {
+        ranges.push((start, markdown.len()));
+    }
+    if let Some((start, last_end)) = indented {
+        ranges.push((start, last_end));
+    }
+
+    ranges
+}
+
+/// Width of the leading whitespace of a line, with tabs advancing to the next
+/// multiple of 4 as in CommonMark.
+fn indent_width(line: &str) -> usize {
+    let mut width = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => width += 1,
+            '\t' => width += 4 - width % 4,
+            _ => break,
+        }
+    }
+    width
+}
+
+/// Recognises an opening code fence (3+ backticks or tildes) at the start of
+/// `trimmed` and returns its character and length.
+fn opening_fence(trimmed: &str) -> Option<(u8, usize)> {
+    let ch = *trimmed.as_bytes().first()?;
+    if ch != b'`' && ch != b'~' {
+        return None;
+    }
+    let len = trimmed.bytes().take_while(|&b| b == ch).count();
+    if len < 3 {
+        return None;
+    }
+    // The info string of a backtick fence may not contain backticks
+    if ch == b'`' && trimmed[len..].contains('`') {
+        return None;
+    }
+    Some((ch, len))
+}
+
+/// Checks whether `trimmed` closes a fence opened with `len` times `ch`.
+fn is_closing_fence(trimmed: &str, ch: u8, len: usize) -> bool {
+    let run = trimmed.bytes().take_while(|&b| b == ch).count();
+    run >= len && trimmed[run..].trim().is_empty()
+}
+
+/// Recognises a bullet (`-`, `*`, `+`) or ordered (`1.`, `1)`) list item marker.
+fn is_list_item(trimmed: &str) -> bool {
+    let bytes = trimmed.as_bytes();
+    let marker_end = match bytes.first() {
+        Some(b'-' | b'*' | b'+') => 1,
+        Some(b) if b.is_ascii_digit() => {
+            let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+            match bytes.get(digits) {
+                Some(b'.' | b')') if digits <= 9 => digits + 1,
+                _ => return false,
+            }
+        }
+        _ => return false,
+    };
+    matches!(bytes.get(marker_end), None | Some(b' ' | b'\t'))
+}
+
+/// Recognises the start of a footnote definition (`[^label]:`). Like list
+/// items, footnotes hold indented continuation paragraphs.
+fn is_footnote_definition(trimmed: &str) -> bool {
+    trimmed.strip_prefix("[^").is_some_and(|rest| {
+        rest.find("]:")
+            .is_some_and(|end| end > 0 && !rest[..end].contains(char::is_whitespace))
+    })
+}
+
+/// Finds inline code spans, HTML comments and escaped citations in
+/// `markdown[from..to]`.
+///
+/// A code span opened by a run of N backticks ends at the next run of exactly
+/// N backticks in the same paragraph; an unmatched run is literal text.
+/// Backslash-escaped backticks never open a code span. An escaped citation is
+/// `\@key` or `\[...]` containing an `@`, up to the closing bracket; its range
+/// starts with the backslash.
+fn inline_non_prose_ranges(markdown: &str, from: usize, to: usize) -> Vec<(usize, usize)> {
+    let bytes = markdown.as_bytes();
+    let mut ranges = Vec::new();
+    let mut i = from;
+
+    // Only ASCII bytes are compared, so stepping through UTF-8 continuation
+    // bytes one at a time is harmless
+    while i < to {
+        match bytes[i] {
+            b'\\' if i + 1 < to && bytes[i + 1] == b'@' => {
+                ranges.push((i, i + 2));
+                i += 2;
+            }
+            b'\\' if i + 1 < to && bytes[i + 1] == b'[' => {
+                match escaped_bracket_end(bytes, i + 2, to) {
+                    Some(end) => {
+                        ranges.push((i, end));
+                        i = end;
+                    }
+                    None => i += 2,
+                }
+            }
+            b'\\' if i + 1 < to && bytes[i + 1].is_ascii_punctuation() => i += 2,
+            b'`' => {
+                let run = bytes[i..to].iter().take_while(|&&b| b == b'`').count();
+                match closing_backticks(bytes, i + run, to, run) {
+                    Some(end) => {
+                        ranges.push((i, end));
+                        i = end;
+                    }
+                    None => i += run,
+                }
+            }
+            b'<' if markdown[i..to].starts_with("<!--") => match markdown[i + 4..to].find("-->") {
+                Some(pos) => {
+                    let end = i + 4 + pos + 3;
+                    ranges.push((i, end));
+                    i = end;
+                }
+                None => i += 4,
+            },
+            _ => i += 1,
+        }
+    }
+
+    ranges
+}
+
+/// Returns the end of an escaped citation bracket whose content starts at
+/// `from`: the position after its `]`, if the content has an `@`, no nested
+/// `[` and no blank line.
+fn escaped_bracket_end(bytes: &[u8], from: usize, to: usize) -> Option<usize> {
+    let len = bytes[from..to]
+        .iter()
+        .position(|&b| matches!(b, b']' | b'['))
+        .filter(|&len| bytes[from + len] == b']')?;
+    let content = &bytes[from..from + len];
+
+    let blank_line = content
+        .split(|&b| b == b'\n')
+        .skip(1)
+        .any(|line| line.iter().all(|b| b.is_ascii_whitespace()));
+    (content.contains(&b'@') && !blank_line).then_some(from + len + 1)
+}
+
+/// Returns the end of the run of exactly `run` backticks closing a code span
+/// that starts before `from`, or `None` if the paragraph ends first.
+fn closing_backticks(bytes: &[u8], from: usize, to: usize, run: usize) -> Option<usize> {
+    let mut j = from;
+    while j < to {
+        match bytes[j] {
+            b'`' => {
+                let len = bytes[j..to].iter().take_while(|&&b| b == b'`').count();
+                if len == run {
+                    return Some(j + len);
+                }
+                j += len;
+            }
+            b'\n' => {
+                // A blank line ends the paragraph, and with it the code span
+                let next = bytes[j + 1..to]
+                    .iter()
+                    .position(|&b| !matches!(b, b' ' | b'\t' | b'\r'))
+                    .map(|p| j + 1 + p);
+                match next {
+                    Some(k) if bytes[k] != b'\n' => j = k,
+                    _ => return None,
+                }
+            }
+            _ => j += 1,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_text() {
+        let citations = extract_citations("");
+        assert!(citations.is_empty());
+    }
+
+    #[test]
+    fn test_no_citations() {
+        let citations = extract_citations("This is plain text without citations.");
+        assert!(citations.is_empty());
+    }
+
+    // Sub-phase 2.1: Basic citation parsing tests
+
+    #[test]
+    fn test_simple_citation() {
+        // Given: Markdown with a simple citation
+        let markdown = "Les résultats montrent [@item-1] que la méthode fonctionne.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: We find one citation with the correct id
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "item-1");
+        assert_eq!(citations[0].locator, None);
+        assert_eq!(citations[0].label, None);
+        assert_eq!(citations[0].url, None);
+    }
+
+    #[test]
+    fn test_pmid_format_citation() {
+        // Given: Markdown with a PMID-prefixed citation key
+        let markdown = "L'étude [@pmid:12345678] a démontré ces résultats.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: We find one citation with the PMID key
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "pmid:12345678");
+    }
+
+    #[test]
+    fn test_citation_span() {
+        // Given: Markdown with a citation
+        let markdown = "Text [@item-1] more text.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: The span correctly points to the citation in the text
+        assert_eq!(citations.len(), 1);
+        let (start, end) = citations[0].span;
+        assert_eq!(&markdown[start..end], "[@item-1]");
+    }
+
+    #[test]
+    fn test_multiple_citations() {
+        // Given: Markdown with multiple citations
+        let markdown = "First [@item-1] and second [@item-2] citations.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: We find both citations in order
+        assert_eq!(citations.len(), 2);
+        assert_eq!(citations[0].id, "item-1");
+        assert_eq!(citations[1].id, "item-2");
+    }
+
+    #[test]
+    fn test_citation_with_special_chars() {
+        // Given: Markdown with citation keys containing special characters
+        let markdown = "See [@doi:10.1234/test_key] for details.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: The key with special characters is extracted correctly
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "doi:10.1234/test_key");
+    }
+
+    #[test]
+    fn test_citation_at_start_of_text() {
+        // Given: Citation at the very start
+        let markdown = "[@item-1] begins the sentence.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: Citation is found
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "item-1");
+        assert_eq!(citations[0].span.0, 0);
+    }
+
+    #[test]
+    fn test_citation_at_end_of_text() {
+        // Given: Citation at the very end
+        let markdown = "The source is [@item-1]";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: Citation is found
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "item-1");
+    }
+
+    // Sub-phase 2.2: Citation with URL parsing tests
+
+    #[test]
+    fn test_citation_with_doi_url() {
+        // Given: Markdown with a citation followed by a DOI URL
+        let markdown =
+            "Les coronavirus [@pmid:33024307](https://doi.org/10.1038/s41579-020-00459-7) sont étudiés.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: We find one citation with the correct id and URL
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "pmid:33024307");
+        assert_eq!(
+            citations[0].url,
+            Some("https://doi.org/10.1038/s41579-020-00459-7".to_string())
+        );
+    }
+
+    #[test]
+    fn test_citation_with_url_span() {
+        // Given: Markdown with a citation and URL
+        let markdown = "Text [@item-1](https://example.com) more text.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: The span includes the entire citation with URL
+        assert_eq!(citations.len(), 1);
+        let (start, end) = citations[0].span;
+        assert_eq!(&markdown[start..end], "[@item-1](https://example.com)");
+    }
+
+    #[test]
+    fn test_citation_without_url_still_works() {
+        // Given: Markdown with a citation without URL (regression test)
+        let markdown = "See [@item-1] for details.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: Citation is found with no URL
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "item-1");
+        assert_eq!(citations[0].url, None);
+    }
+
+    #[test]
+    fn test_mixed_citations_with_and_without_urls() {
+        // Given: Markdown with some citations having URLs and some not
+        let markdown = "First [@item-1](https://doi.org/1) and second [@item-2] citations.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: Both citations are found with correct URL status
+        assert_eq!(citations.len(), 2);
+        assert_eq!(citations[0].id, "item-1");
+        assert_eq!(citations[0].url, Some("https://doi.org/1".to_string()));
+        assert_eq!(citations[1].id, "item-2");
+        assert_eq!(citations[1].url, None);
+    }
+
+    #[test]
+    fn test_citation_url_with_special_chars() {
+        // Given: URL with special characters (query params, fragments)
+        let markdown = "See [@item-1](https://example.com/path?query=1&foo=bar#section) here.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: URL is captured correctly including special chars
+        assert_eq!(citations.len(), 1);
+        assert_eq!(
+            citations[0].url,
+            Some("https://example.com/path?query=1&foo=bar#section".to_string())
+        );
+    }
+
+    // Sub-phase 2.3: Locator parsing tests
+
+    #[test]
+    fn test_citation_with_page_locator() {
+        // Given: Markdown with a citation containing a page locator
+        let markdown = "Voir [@book-1, p. 42] pour plus de détails.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: We find the citation with locator and label
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "book-1");
+        assert_eq!(citations[0].locator, Some("42".to_string()));
+        assert_eq!(citations[0].label, Some("page".to_string()));
+    }
+
+    #[test]
+    fn test_citation_with_page_range() {
+        // Given: Markdown with a citation containing a page range
+        let markdown = "Cette section [@article-1, pp. 10-20] décrit la méthodologie.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: We find the citation with page range
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "article-1");
+        assert_eq!(citations[0].locator, Some("10-20".to_string()));
+        assert_eq!(citations[0].label, Some("page".to_string()));
+    }
+
+    #[test]
+    fn test_citation_with_chapter_locator() {
+        // Given: Markdown with a citation containing a chapter locator
+        let markdown = "See [@book-2, ch. 3] for the introduction.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: We find the citation with chapter locator
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "book-2");
+        assert_eq!(citations[0].locator, Some("3".to_string()));
+        assert_eq!(citations[0].label, Some("chapter".to_string()));
+    }
+
+    #[test]
+    fn test_citation_with_section_locator() {
+        // Given: Markdown with a citation containing a section locator
+        let markdown = "Refer to [@manual-1, sec. 4.2] for details.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: We find the citation with section locator
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "manual-1");
+        assert_eq!(citations[0].locator, Some("4.2".to_string()));
+        assert_eq!(citations[0].label, Some("section".to_string()));
+    }
+
+    #[test]
+    fn test_citation_with_full_page_label() {
+        // Given: Markdown with "page" instead of "p."
+        let markdown = "Check [@doc-1, page 15] for the formula.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: We find the citation with page locator
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "doc-1");
+        assert_eq!(citations[0].locator, Some("15".to_string()));
+        assert_eq!(citations[0].label, Some("page".to_string()));
+    }
+
+    #[test]
+    fn test_citation_with_full_pages_label() {
+        // Given: Markdown with "pages" instead of "pp."
+        let markdown = "See [@doc-2, pages 5-10] for examples.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: We find the citation with pages locator
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "doc-2");
+        assert_eq!(citations[0].locator, Some("5-10".to_string()));
+        assert_eq!(citations[0].label, Some("page".to_string()));
+    }
+
+    #[test]
+    fn test_citation_with_full_chapter_label() {
+        // Given: Markdown with "chapter" instead of "ch."
+        let markdown = "Read [@book-3, chapter 7] first.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: We find the citation with chapter locator
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "book-3");
+        assert_eq!(citations[0].locator, Some("7".to_string()));
+        assert_eq!(citations[0].label, Some("chapter".to_string()));
+    }
+
+    #[test]
+    fn test_citation_with_full_section_label() {
+        // Given: Markdown with "section" instead of "sec."
+        let markdown = "Consult [@guide-1, section 2.1] for setup.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: We find the citation with section locator
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "guide-1");
+        assert_eq!(citations[0].locator, Some("2.1".to_string()));
+        assert_eq!(citations[0].label, Some("section".to_string()));
+    }
+
+    #[test]
+    fn test_citation_locator_span() {
+        // Given: Markdown with a locator citation
+        let markdown = "Text [@item-1, p. 42] more text.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: The span correctly includes the entire citation with locator
+        assert_eq!(citations.len(), 1);
+        let (start, end) = citations[0].span;
+        assert_eq!(&markdown[start..end], "[@item-1, p. 42]");
+    }
+
+    #[test]
+    fn test_mixed_citations_with_and_without_locators() {
+        // Given: Markdown with both types of citations
+        let markdown = "First [@item-1] and second [@item-2, p. 10] in text.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: We find both citations with correct properties
+        assert_eq!(citations.len(), 2);
+        assert_eq!(citations[0].id, "item-1");
+        assert_eq!(citations[0].locator, None);
+        assert_eq!(citations[0].label, None);
+        assert_eq!(citations[1].id, "item-2");
+        assert_eq!(citations[1].locator, Some("10".to_string()));
+        assert_eq!(citations[1].label, Some("page".to_string()));
+    }
+
+    // Code spans, code blocks and HTML comments are not prose
+
+    #[test]
+    fn test_citation_in_inline_code_ignored() {
+        // Given: A citation inside an inline code span, and one in prose
+        let markdown = "Syntax: `[@shin2025]` renders as [@knip2025].";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: Only the prose citation is found, with a span valid in the original text
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "knip2025");
+        let (start, end) = citations[0].span;
+        assert_eq!(&markdown[start..end], "[@knip2025]");
+    }
+
+    #[test]
+    fn test_citation_in_double_backtick_code_ignored() {
+        // Given: A double-backtick code span that contains a single backtick
+        let markdown = "Use ``a ` b [@x]`` then [@y].";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: The code span ends at the matching double backtick
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "y");
+    }
+
+    #[test]
+    fn test_unmatched_backtick_is_literal() {
+        // Given: A lone backtick that never closes
+        let markdown = "A stray ` backtick before [@item-1].";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: The citation is still found
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "item-1");
+    }
+
+    #[test]
+    fn test_code_span_does_not_cross_blank_line() {
+        // Given: A backtick in one paragraph and another in the next one
+        let markdown = "Open ` here.\n\nCited [@item-1] then ` closed.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: No code span is formed across paragraphs
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "item-1");
+    }
+
+    #[test]
+    fn test_citation_in_fenced_block_ignored() {
+        // Given: Citations inside backtick and tilde fenced blocks
+        let markdown =
+            "Before [@a].\n\n```markdown\nSee [@b].\n```\n\n~~~~\n[@c] ```\n~~~~\n\nAfter [@d].";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: Only citations outside the fences are found
+        let ids: Vec<&str> = citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "d"]);
+        let (start, end) = citations[1].span;
+        assert_eq!(&markdown[start..end], "[@d]");
+    }
+
+    #[test]
+    fn test_unclosed_fence_runs_to_end() {
+        // Given: A fence that is never closed
+        let markdown = "Cited [@a].\n\n```\n[@b]\n\nStill code [@c].";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: Everything after the fence is code
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "a");
+    }
+
+    #[test]
+    fn test_citation_in_indented_code_ignored() {
+        // Given: An indented code block after a blank line
+        let markdown = "Text [@a].\n\n    code [@b]\n\n    more code [@c]\n\nText [@d].";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: The indented block is skipped
+        let ids: Vec<&str> = citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "d"]);
+    }
+
+    #[test]
+    fn test_citation_in_front_matter_ignored() {
+        // Given: A YAML front matter whose values look like citations
+        let markdown = "---\nnocite: \"@b\"\ntitle: On [@c]\n---\nText [@a].";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: Only the body citation is found
+        let ids: Vec<&str> = citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["a"]);
+    }
+
+    #[test]
+    fn test_indented_paragraph_continuation_is_prose() {
+        // Given: An indented line that continues a paragraph (not a code block)
+        let markdown = "A paragraph line\n    continued [@a].";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: The citation is found
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "a");
+    }
+
+    #[test]
+    fn test_indented_list_content_is_prose() {
+        // Given: A nested list item indented by four spaces after a blank line
+        let markdown = "- First item [@a]\n\n    - Nested item [@b]\n";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: List content is prose, not an indented code block
+        let ids: Vec<&str> = citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_indented_footnote_content_is_prose() {
+        // Given: A footnote with an indented continuation paragraph
+        let markdown = "Text[^1].\n\n[^1]: First paragraph.\n\n    Second [@smith2020].\n";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: The continuation is part of the note, not a code block
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "smith2020");
+    }
+
+    #[test]
+    fn test_citation_in_html_comment_ignored() {
+        // Given: Citations in single-line and multi-line HTML comments
+        let markdown = "<!-- TODO [@a] -->\nText [@b].\n<!--\n[@c]\n\n[@d]\n-->\nEnd [@e].";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: Only citations outside comments are found
+        let ids: Vec<&str> = citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "e"]);
+    }
+
+    #[test]
+    fn test_pandoc_group_in_code_ignored() {
+        // Given: A Pandoc grouped citation in inline code and one in prose
+        let markdown = "Write `[@a; @b]` to get [@c; @d].";
+
+        // When: We extract citation clusters
+        let clusters = extract_citation_clusters(markdown);
+
+        // Then: Only the prose cluster is found
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].items.len(), 2);
+        assert_eq!(clusters[0].items[0].id, "c");
+        let (start, end) = clusters[0].span;
+        assert_eq!(&markdown[start..end], "[@c; @d]");
+    }
+
+    #[test]
+    fn test_citations_separated_by_code_not_grouped() {
+        // Given: Two citations separated only by an inline code span
+        let markdown = "See [@a] `x` [@b].";
+
+        // When: We extract citation clusters
+        let clusters = extract_citation_clusters(markdown);
+
+        // Then: The code span counts as text and keeps them apart
+        assert_eq!(clusters.len(), 2);
+    }
+
+    // Narrative citations (bare @id)
+
+    #[test]
+    fn test_narrative_citation() {
+        // Given: A bare @id used as part of the sentence
+        let markdown = "@smith2020 says that the method works.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: One author-in-text citation is found
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "smith2020");
+        assert_eq!(citations[0].mode, CitationMode::AuthorInText);
+        let (start, end) = citations[0].span;
+        assert_eq!(&markdown[start..end], "@smith2020");
+    }
+
+    #[test]
+    fn test_narrative_citation_trailing_punctuation() {
+        // Given: A narrative citation at the end of a sentence
+        let markdown = "As shown by @smith2020. Also @doe:2021, later.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: Trailing punctuation is not part of the key, internal punctuation is
+        assert_eq!(citations.len(), 2);
+        assert_eq!(citations[0].id, "smith2020");
+        assert_eq!(citations[1].id, "doe:2021");
+        let (start, end) = citations[0].span;
+        assert_eq!(&markdown[start..end], "@smith2020");
+    }
+
+    #[test]
+    fn test_narrative_citation_with_locator() {
+        // Given: A narrative citation followed by a bracketed locator
+        let markdown = "@smith2020 [p. 4] says so.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: The locator is parsed and included in the span
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].locator, Some("4".to_string()));
+        assert_eq!(citations[0].label, Some("page".to_string()));
+        let (start, end) = citations[0].span;
+        assert_eq!(&markdown[start..end], "@smith2020 [p. 4]");
+    }
+
+    #[test]
+    fn test_narrative_citation_not_followed_by_link() {
+        // Given: A narrative citation followed by a link and a footnote
+        let markdown = "@smith2020 [the paper](https://example.com) and @doe2021 [^1].";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: Neither bracket is taken as a locator
+        assert_eq!(citations.len(), 2);
+        assert_eq!(citations[0].locator, None);
+        assert_eq!(citations[1].locator, None);
+        let (start, end) = citations[0].span;
+        assert_eq!(&markdown[start..end], "@smith2020");
+    }
+
+    #[test]
+    fn test_email_and_urls_are_not_citations() {
+        // Given: An e-mail address, a URL and a link containing @
+        let markdown =
+            "Mail doe@example.com, see https://x.org/@doe or [profile](https://x.org/@smith).";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: None of them is a citation
+        assert!(citations.is_empty());
+    }
+
+    #[test]
+    fn test_bracketed_citations_are_not_narrative() {
+        // Given: Bracketed citations only
+        let markdown = "Text [@a] and [@b; @c].";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: No @id inside brackets is reported as narrative
+        assert!(citations.iter().all(|c| c.mode == CitationMode::Normal));
+    }
+
+    #[test]
+    fn test_narrative_and_bracketed_in_document_order() {
+        // Given: A mix of narrative and bracketed citations
+        let markdown = "@a showed it [@b], then @c agreed.";
+
+        // When: We extract citations
+        let citations = extract_citations(markdown);
+
+        // Then: They are returned in document order with their mode
+        let found: Vec<(&str, CitationMode)> =
+            citations.iter().map(|c| (c.id.as_str(), c.mode)).collect();
+        assert_eq!(
+            found,
+            vec![
+                ("a", CitationMode::AuthorInText),
+                ("b", CitationMode::Normal),
+                ("c", CitationMode::AuthorInText),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_narrative_citations_never_grouped() {
+        // Given: A narrative citation right next to bracketed ones
+        let markdown = "@a [@b] [@c] and @d @e.";
+
+        // When: We extract citation clusters
+        let clusters = extract_citation_clusters(markdown);
+
+        // Then: Narrative citations stand alone, bracketed ones are still grouped
+        let ids: Vec<Vec<&str>> = clusters
+            .iter()
+            .map(|c| c.items.iter().map(|i| i.id.as_str()).collect())
+            .collect();
+        assert_eq!(ids, vec![vec!["a"], vec!["b", "c"], vec!["d"], vec!["e"]]);
+        assert_eq!(clusters[0].items[0].mode, CitationMode::AuthorInText);
+    }
+
+    #[test]
+    fn test_retain_narrative_keeps_references_and_brackets() {
+        // Given: Narrative citations of a reference and of a person, and a
+        // bracketed citation of an unknown key
+        let mut scan = scan_citations(
+            "@a said so, as @team knows [@unknown].",
+            GroupingPolicy::Whitespace,
+        );
+
+        // When: We keep the narrative citations of references only
+        scan.retain_narrative(|key| key == "a");
+
+        // Then: The mention is gone, the bracketed citation is kept
+        let ids: Vec<&str> = scan
+            .clusters
+            .iter()
+            .map(|c| c.items[0].id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["a", "unknown"]);
+        assert_eq!(scan.citations.len(), 2);
+    }
+
+    // Suppress-author citations ([-@id])
+
+    #[test]
+    fn test_suppress_author_citation() {
+        // Given: A citation with a leading dash
+        let markdown = "Smith showed [-@smith2020, p. 3] that it works.";
+
+        // When: We extract citations
         let citations = extract_citations(markdown);
 
-        // Then: The key with special characters is extracted correctly
-        assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "doi:10.1234/test_key");
+        // Then: The author is suppressed, id and locator are parsed as usual
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].id, "smith2020");
+        assert!(citations[0].suppress_author);
+        assert_eq!(citations[0].locator, Some("3".to_string()));
+        let (start, end) = citations[0].span;
+        assert_eq!(&markdown[start..end], "[-@smith2020, p. 3]");
+    }
+
+    #[test]
+    fn test_regular_citation_keeps_author() {
+        // Given: A citation without a dash
+        let citations = extract_citations("See [@smith2020].");
+
+        // Then: The author is not suppressed
+        assert!(!citations[0].suppress_author);
+    }
+
+    #[test]
+    fn test_suppress_author_in_pandoc_group() {
+        // Given: A Pandoc group with suppressed authors on the first and last items
+        let markdown = "Smith [-@smith2020; @doe2021, ch. 2; -@smith2022].";
+
+        // When: We extract citation clusters
+        let clusters = extract_citation_clusters(markdown);
+
+        // Then: Each item carries its own flag
+        assert_eq!(clusters.len(), 1);
+        let flags: Vec<(&str, bool)> = clusters[0]
+            .items
+            .iter()
+            .map(|i| (i.id.as_str(), i.suppress_author))
+            .collect();
+        assert_eq!(
+            flags,
+            vec![("smith2020", true), ("doe2021", false), ("smith2022", true)]
+        );
+        let (start, end) = clusters[0].span;
+        assert_eq!(
+            &markdown[start..end],
+            "[-@smith2020; @doe2021, ch. 2; -@smith2022]"
+        );
+    }
+
+    #[test]
+    fn test_suppress_author_adjacent_citations_grouped() {
+        // Given: A suppressed-author citation next to a regular one
+        let clusters = extract_citation_clusters("Smith [-@a] [@b] agrees.");
+
+        // Then: They are grouped like any adjacent citations
+        assert_eq!(clusters.len(), 1);
+        assert!(clusters[0].items[0].suppress_author);
+        assert!(!clusters[0].items[1].suppress_author);
     }
 
+    // Prefix and suffix ([see @id, p. 3 and passim])
+
     #[test]
-    fn test_citation_at_start_of_text() {
-        // Given: Citation at the very start
-        let markdown = "[@item-1] begins the sentence.";
+    fn test_citation_with_prefix_locator_and_suffix() {
+        // Given: A citation with text before the key and after the locator
+        let markdown = "As argued [see @smith2020, pp. 3-5 and passim].";
 
         // When: We extract citations
         let citations = extract_citations(markdown);
 
-        // Then: Citation is found
+        // Then: Prefix, locator and suffix are separated
         assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "item-1");
-        assert_eq!(citations[0].span.0, 0);
+        assert_eq!(citations[0].id, "smith2020");
+        assert_eq!(citations[0].prefix, Some("see".to_string()));
+        assert_eq!(citations[0].locator, Some("3-5".to_string()));
+        assert_eq!(citations[0].label, Some("page".to_string()));
+        assert_eq!(citations[0].suffix, Some("and passim".to_string()));
+        let (start, end) = citations[0].span;
+        assert_eq!(
+            &markdown[start..end],
+            "[see @smith2020, pp. 3-5 and passim]"
+        );
     }
 
     #[test]
-    fn test_citation_at_end_of_text() {
-        // Given: Citation at the very end
-        let markdown = "The source is [@item-1]";
+    fn test_pandoc_group_with_prefixes_and_suffixes() {
+        // Given: A Pandoc group where each item has its own affixes
+        let markdown = "Text [see @a, pp. 3-5 and passim; cf. @b].";
+
+        // When: We extract citation clusters
+        let clusters = extract_citation_clusters(markdown);
+
+        // Then: Affixes stay attached to their item
+        assert_eq!(clusters.len(), 1);
+        let items = &clusters[0].items;
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].prefix, Some("see".to_string()));
+        assert_eq!(items[0].locator, Some("3-5".to_string()));
+        assert_eq!(items[0].suffix, Some("and passim".to_string()));
+        assert_eq!(items[1].id, "b");
+        assert_eq!(items[1].prefix, Some("cf.".to_string()));
+        assert_eq!(items[1].suffix, None);
+    }
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+    #[test]
+    fn test_suffix_without_locator() {
+        // Given: Text after the comma that is not a locator
+        let citations = extract_citations("See [@a, emphasis added] and [@b and others].");
 
-        // Then: Citation is found
-        assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "item-1");
+        // Then: It becomes the suffix, the comma included
+        assert_eq!(citations[0].locator, None);
+        assert_eq!(citations[0].suffix, Some(", emphasis added".to_string()));
+        assert_eq!(citations[1].suffix, Some("and others".to_string()));
     }
 
-    // Sub-phase 2.2: Citation with URL parsing tests
-
     #[test]
-    fn test_citation_with_doi_url() {
-        // Given: Markdown with a citation followed by a DOI URL
-        let markdown =
-            "Les coronavirus [@pmid:33024307](https://doi.org/10.1038/s41579-020-00459-7) sont étudiés.";
+    fn test_locator_list_keeps_comma_in_suffix() {
+        // Given: A locator made of several ranges followed by a suffix
+        let citations = extract_citations("See [@a, pp. 33, 35-37, and passim].");
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+        // Then: The whole list is the locator
+        assert_eq!(citations[0].locator, Some("33, 35-37".to_string()));
+        assert_eq!(citations[0].suffix, Some(", and passim".to_string()));
+    }
 
-        // Then: We find one citation with the correct id and URL
-        assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "pmid:33024307");
-        assert_eq!(
-            citations[0].url,
-            Some("https://doi.org/10.1038/s41579-020-00459-7".to_string())
+    #[test]
+    fn test_roman_locator_stops_at_words() {
+        // Given: Roman locators, and suffix words made of roman letters
+        let citations = extract_citations(
+            "See [@a, pp. xii-xiv], [@b, p. 4 mix], [@c, vol. 2 CDC data], [@d, ch. 3 civil].",
         );
+
+        // Then: Only well-formed numerals before any digit are in the locator
+        assert_eq!(citations[0].locator, Some("xii-xiv".to_string()));
+        assert_eq!(citations[0].suffix, None);
+        assert_eq!(citations[1].locator, Some("4".to_string()));
+        assert_eq!(citations[1].suffix, Some("mix".to_string()));
+        assert_eq!(citations[2].locator, Some("2".to_string()));
+        assert_eq!(citations[2].suffix, Some("CDC data".to_string()));
+        assert_eq!(citations[3].locator, Some("3".to_string()));
+        assert_eq!(citations[3].suffix, Some("civil".to_string()));
     }
 
     #[test]
-    fn test_citation_with_url_span() {
-        // Given: Markdown with a citation and URL
-        let markdown = "Text [@item-1](https://example.com) more text.";
+    fn test_narrative_locator_with_suffix() {
+        // Given: A narrative citation whose locator is followed by a suffix
+        let citations = extract_citations("@smith2020 [p. 4, emphasis added] says so.");
 
-        // When: We extract citations
+        // Then: The suffix is kept apart from the locator
+        assert_eq!(citations[0].locator, Some("4".to_string()));
+        assert_eq!(citations[0].suffix, Some(", emphasis added".to_string()));
+    }
+
+    #[test]
+    fn test_email_in_brackets_is_not_a_prefixed_citation() {
+        // Given: Brackets containing an email address
+        let citations = extract_citations("Contact [mail john@example.com] today.");
+
+        // Then: No citation is extracted
+        assert!(citations.is_empty());
+    }
+
+    #[test]
+    fn test_localized_and_extended_locator_labels() {
+        // Given: Locators written with CSL terms beyond page/chapter/section
+        let citations =
+            extract_citations("See [@a, fig. 2], [@b, Bd. 3, S. 12], [@c, s.v. Rhetoric, passim].");
+
+        // Then: Each term maps to its CSL label
+        assert_eq!(citations[0].label, Some("figure".to_string()));
+        assert_eq!(citations[0].locator, Some("2".to_string()));
+        assert_eq!(citations[1].label, Some("volume".to_string()));
+        assert_eq!(citations[1].locator, Some("3".to_string()));
+        assert_eq!(citations[1].suffix, Some(", S. 12".to_string()));
+        assert_eq!(citations[2].label, Some("sub-verbo".to_string()));
+        assert_eq!(citations[2].locator, Some("Rhetoric".to_string()));
+        assert_eq!(citations[2].suffix, Some(", passim".to_string()));
+    }
+
+    // Escaped citations (\[@id], \@id)
+
+    #[test]
+    fn test_escaped_bracketed_citation_not_extracted() {
+        // Given: Escaped bracketed citations, single and grouped
+        let markdown = r"Type \[@smith2020] or \[see @a; @b] to cite.";
+
+        // When: We extract citations and clusters
         let citations = extract_citations(markdown);
+        let clusters = extract_citation_clusters(markdown);
 
-        // Then: The span includes the entire citation with URL
-        assert_eq!(citations.len(), 1);
-        let (start, end) = citations[0].span;
-        assert_eq!(&markdown[start..end], "[@item-1](https://example.com)");
+        // Then: Nothing is extracted, not even as narrative citations
+        assert!(citations.is_empty());
+        assert!(clusters.is_empty());
+        assert_eq!(find_citation_escapes(markdown), vec![5, 22]);
     }
 
     #[test]
-    fn test_citation_without_url_still_works() {
-        // Given: Markdown with a citation without URL (regression test)
-        let markdown = "See [@item-1] for details.";
+    fn test_escaped_at_sign_not_narrative() {
+        // Given: An escaped at sign next to a real citation
+        let markdown = r"Mention \@smith2020 but cite @doe2021.";
 
         // When: We extract citations
         let citations = extract_citations(markdown);
 
-        // Then: Citation is found with no URL
+        // Then: Only the unescaped one is a citation
         assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "item-1");
-        assert_eq!(citations[0].url, None);
+        assert_eq!(citations[0].id, "doe2021");
+        assert_eq!(find_citation_escapes(markdown), vec![8]);
     }
 
     #[test]
-    fn test_mixed_citations_with_and_without_urls() {
-        // Given: Markdown with some citations having URLs and some not
-        let markdown = "First [@item-1](https://doi.org/1) and second [@item-2] citations.";
+    fn test_escapes_in_code_are_not_reported() {
+        // Given: Escapes inside a code span and other escaped punctuation
+        let markdown = r"Code `\@key` and \[not a citation] and \*stars\*.";
+
+        // Then: No citation escape is found
+        assert!(find_citation_escapes(markdown).is_empty());
+    }
+
+    // Citation key grammar
+
+    #[test]
+    fn test_key_with_internal_punctuation() {
+        // Given: Keys made of DOIs and URLs, and a key ending a sentence
+        let markdown =
+            "See [@10.1000/xyz-123] and [@https://example.org/paper#sec] as @doe:2021/a.";
 
         // When: We extract citations
+        let ids: Vec<String> = extract_citations(markdown)
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+
+        // Then: Internal punctuation is kept, trailing punctuation is not
+        assert_eq!(
+            ids,
+            vec![
+                "10.1000/xyz-123",
+                "https://example.org/paper#sec",
+                "doe:2021/a"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_with_spaces_is_not_a_key() {
+        // Given: A bracket where a space follows the key
+        let citations = extract_citations("See [@smith 2020].");
+
+        // Then: The key stops at the space and the rest is a suffix
+        assert_eq!(citations[0].id, "smith");
+        assert_eq!(citations[0].suffix, Some("2020".to_string()));
+    }
+
+    #[test]
+    fn test_braced_keys() {
+        // Given: Braced keys with commas, semicolons and brackets
+        let markdown = "See [@{Smith, 2020}, p. 4], [@{a;b}; @c] and @{x [y] z} too.";
+
+        // When: We extract citations and clusters
         let citations = extract_citations(markdown);
+        let clusters = extract_citation_clusters(markdown);
 
-        // Then: Both citations are found with correct URL status
-        assert_eq!(citations.len(), 2);
-        assert_eq!(citations[0].id, "item-1");
-        assert_eq!(citations[0].url, Some("https://doi.org/1".to_string()));
-        assert_eq!(citations[1].id, "item-2");
-        assert_eq!(citations[1].url, None);
+        // Then: The braces delimit the key
+        assert_eq!(citations[0].id, "Smith, 2020");
+        assert_eq!(citations[0].locator, Some("4".to_string()));
+        assert_eq!(citations[1].id, "x [y] z");
+        let group = clusters
+            .iter()
+            .find(|c| c.items.len() == 2)
+            .expect("Pandoc group should be found");
+        assert_eq!(group.items[0].id, "a;b");
+        assert_eq!(group.items[1].id, "c");
     }
 
     #[test]
-    fn test_citation_url_with_special_chars() {
-        // Given: URL with special characters (query params, fragments)
-        let markdown = "See [@item-1](https://example.com/path?query=1&foo=bar#section) here.";
+    fn test_unclosed_brace_in_brackets() {
+        // Given: Brackets holding an unclosed brace, then a braced group
+        // spread over two lines, before ordinary citations
+        let markdown = "Set [x = \\frac{a] and [@a].\n[{y\n}] [@b]";
 
         // When: We extract citations
         let citations = extract_citations(markdown);
 
-        // Then: URL is captured correctly including special chars
-        assert_eq!(citations.len(), 1);
+        // Then: The brackets are ordinary text and the citations are found
+        let ids: Vec<&str> = citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_parse_citation_key() {
+        // Given/When/Then: Keys end where Pandoc's grammar ends them
+        assert_eq!(parse_citation_key("smith2020."), Some(("smith2020", 9)));
+        assert_eq!(parse_citation_key("a--b"), Some(("a", 1)));
         assert_eq!(
-            citations[0].url,
-            Some("https://example.com/path?query=1&foo=bar#section".to_string())
+            parse_citation_key("{ spaced key }!"),
+            Some(("spaced key", 14))
         );
+        assert_eq!(parse_citation_key("{unclosed"), None);
+        assert_eq!(parse_citation_key("-nope"), None);
     }
 
-    // Sub-phase 2.3: Locator parsing tests
+    // Source positions
 
     #[test]
-    fn test_citation_with_page_locator() {
-        // Given: Markdown with a citation containing a page locator
-        let markdown = "Voir [@book-1, p. 42] pour plus de détails.";
+    fn test_citation_positions() {
+        // Given: Citations on several lines, after a multi-byte character
+        let markdown = "Intro.\n\nLes résultats [@a] et\n  @b [p. 2].";
 
         // When: We extract citations
         let citations = extract_citations(markdown);
 
-        // Then: We find the citation with locator and label
-        assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "book-1");
-        assert_eq!(citations[0].locator, Some("42".to_string()));
-        assert_eq!(citations[0].label, Some("page".to_string()));
+        // Then: Lines and columns are 1-based, columns counted in characters
+        let positions: Vec<(usize, usize)> = citations
+            .iter()
+            .map(|c| (c.position.line, c.position.column))
+            .collect();
+        assert_eq!(positions, vec![(3, 15), (4, 3)]);
+        assert_eq!(citations[0].position.span, citations[0].span);
     }
 
     #[test]
-    fn test_citation_with_page_range() {
-        // Given: Markdown with a citation containing a page range
-        let markdown = "Cette section [@article-1, pp. 10-20] décrit la méthodologie.";
+    fn test_pandoc_group_item_positions() {
+        // Given: A Pandoc group on the second line
+        let markdown = "Title\nSee [@a;  see @b, p. 4].";
+
+        // When: We extract citation clusters
+        let clusters = extract_citation_clusters(markdown);
+
+        // Then: Each item points at its own text
+        let items = &clusters[0].items;
+        assert_eq!((items[0].position.line, items[0].position.column), (2, 6));
+        assert_eq!((items[1].position.line, items[1].position.column), (2, 11));
+        let (start, end) = items[1].position.span;
+        assert_eq!(&markdown[start..end], "see @b, p. 4");
+    }
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+    // Bibliography marker
 
-        // Then: We find the citation with page range
-        assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "article-1");
-        assert_eq!(citations[0].locator, Some("10-20".to_string()));
-        assert_eq!(citations[0].label, Some("page".to_string()));
+    #[test]
+    fn test_bibliography_marker_comment() {
+        // Given: A document with a bibliography comment before an appendix
+        let markdown = "Text.\n\n## References\n\n<!-- bibliography -->\n\n## Appendix\n";
+
+        // When: We look for the marker
+        let (start, end) = find_bibliography_marker(markdown).unwrap();
+
+        // Then: The comment line is found
+        assert_eq!(&markdown[start..end], "<!-- bibliography -->");
     }
 
     #[test]
-    fn test_citation_with_chapter_locator() {
-        // Given: Markdown with a citation containing a chapter locator
-        let markdown = "See [@book-2, ch. 3] for the introduction.";
+    fn test_bibliography_marker_refs_div() {
+        // Given: A Pandoc refs div, empty or not
+        let markdown = "Text.\n\n::: {#refs}\nPlaceholder\n:::\n\nAppendix.";
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+        // When: We look for the marker
+        let (start, end) = find_bibliography_marker(markdown).unwrap();
 
-        // Then: We find the citation with chapter locator
-        assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "book-2");
-        assert_eq!(citations[0].locator, Some("3".to_string()));
-        assert_eq!(citations[0].label, Some("chapter".to_string()));
+        // Then: The whole div is the marker
+        assert_eq!(&markdown[start..end], "::: {#refs}\nPlaceholder\n:::");
     }
 
     #[test]
-    fn test_citation_with_section_locator() {
-        // Given: Markdown with a citation containing a section locator
-        let markdown = "Refer to [@manual-1, sec. 4.2] for details.";
+    fn test_bibliography_marker_ignored_in_code() {
+        // Given: Markers only inside code blocks, or not alone on their line
+        let markdown = "```\n<!-- bibliography -->\n```\n\nSee <!-- bibliography --> here.";
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+        // Then: No marker is found
+        assert_eq!(find_bibliography_marker(markdown), None);
+    }
 
-        // Then: We find the citation with section locator
-        assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "manual-1");
-        assert_eq!(citations[0].locator, Some("4.2".to_string()));
-        assert_eq!(citations[0].label, Some("section".to_string()));
+    // Reference sections
+
+    #[test]
+    fn test_refsections_by_marker() {
+        // Given: A document with refsection markers, one inside a code block
+        let markdown =
+            "Intro [@a].\n<!-- refsection -->\nOne [@b].\n```\n<!-- refsection -->\n```\n";
+
+        // When: We split it into reference sections without a heading level
+        let sections = refsection_ranges(markdown, None);
+
+        // Then: Only the marker in prose starts a section
+        assert_eq!(sections.len(), 2);
+        assert_eq!(&markdown[sections[0].0..sections[0].1], "Intro [@a].\n");
+        assert!(markdown[sections[1].0..].starts_with("<!-- refsection -->"));
+        assert_eq!(sections[1].1, markdown.len());
     }
 
     #[test]
-    fn test_citation_with_full_page_label() {
-        // Given: Markdown with "page" instead of "p."
-        let markdown = "Check [@doc-1, page 15] for the formula.";
+    fn test_refsections_by_heading_level() {
+        // Given: A thesis with chapters and sub-sections
+        let markdown = "# Chapter 1\n## Part\n[@a]\n# Chapter 2\n#hashtag [@b]\n";
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+        // When: We split it at level-1 headings
+        let sections = refsection_ranges(markdown, Some(1));
 
-        // Then: We find the citation with page locator
-        assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "doc-1");
-        assert_eq!(citations[0].locator, Some("15".to_string()));
-        assert_eq!(citations[0].label, Some("page".to_string()));
+        // Then: Only the chapters start sections
+        let starts: Vec<&str> = sections
+            .iter()
+            .map(|&(start, end)| markdown[start..end].lines().next().unwrap())
+            .collect();
+        assert_eq!(starts, vec!["# Chapter 1", "# Chapter 2"]);
     }
 
     #[test]
-    fn test_citation_with_full_pages_label() {
-        // Given: Markdown with "pages" instead of "pp."
-        let markdown = "See [@doc-2, pages 5-10] for examples.";
+    fn test_refsections_single_section() {
+        // Given: A document without boundaries
+        let markdown = "# Title\nText [@a].";
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+        // Then: It is a single section
+        assert_eq!(refsection_ranges(markdown, None), vec![(0, markdown.len())]);
+        assert_eq!(refsection_ranges("", Some(1)), vec![(0, 0)]);
+    }
 
-        // Then: We find the citation with pages locator
-        assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "doc-2");
-        assert_eq!(citations[0].locator, Some("5-10".to_string()));
-        assert_eq!(citations[0].label, Some("page".to_string()));
+    #[test]
+    fn test_parse_nocite_keys() {
+        // Given: Nocite entries in the forms used by front matter and the CLI
+        let cases = [
+            ("@a, @b", vec!["a", "b"]),
+            ("@a;@b @c", vec!["a", "b", "c"]),
+            ("plain-key", vec!["plain-key"]),
+            ("@*", vec!["*"]),
+            (
+                "@{Smith, 2020}, @doi:10.1/x",
+                vec!["Smith, 2020", "doi:10.1/x"],
+            ),
+            ("", vec![]),
+        ];
+
+        for (text, expected) in cases {
+            // When: We parse the keys
+            let keys = parse_nocite_keys(text);
+
+            // Then: Each key is found once, in order
+            assert_eq!(keys, expected, "for {:?}", text);
+        }
     }
 
     #[test]
-    fn test_citation_with_full_chapter_label() {
-        // Given: Markdown with "chapter" instead of "ch."
-        let markdown = "Read [@book-3, chapter 7] first.";
+    fn test_grouping_policies() {
+        // Given: Citations separated by a space, a comma, a line break and a blank line
+        let markdown = "[@a] [@b], [@c]\n[@d]\n\n[@e]";
+        let cases = [
+            (GroupingPolicy::Whitespace, vec![2, 1, 1, 1]),
+            (GroupingPolicy::WhitespaceAndNewlines, vec![2, 2, 1]),
+            (GroupingPolicy::Comma, vec![3, 1, 1]),
+            (GroupingPolicy::Never, vec![1, 1, 1, 1, 1]),
+        ];
+
+        for (policy, expected) in cases {
+            // When: We cluster them with the policy
+            let clusters = extract_citation_clusters_with(markdown, policy);
+
+            // Then: The clusters have the expected sizes
+            let sizes: Vec<usize> = clusters.iter().map(|c| c.items.len()).collect();
+            assert_eq!(sizes, expected, "for {:?}", policy);
+        }
+    }
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+    #[test]
+    fn test_never_policy_keeps_pandoc_groups() {
+        // Given: A Pandoc group next to a citation
+        let markdown = "[@a; @b] [@c]";
 
-        // Then: We find the citation with chapter locator
-        assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "book-3");
-        assert_eq!(citations[0].locator, Some("7".to_string()));
-        assert_eq!(citations[0].label, Some("chapter".to_string()));
+        // When: We never group
+        let clusters = extract_citation_clusters_with(markdown, GroupingPolicy::Never);
+
+        // Then: The explicit group is kept whole
+        let sizes: Vec<usize> = clusters.iter().map(|c| c.items.len()).collect();
+        assert_eq!(sizes, vec![2, 1]);
     }
 
     #[test]
-    fn test_citation_with_full_section_label() {
-        // Given: Markdown with "section" instead of "sec."
-        let markdown = "Consult [@guide-1, section 2.1] for setup.";
+    fn test_scan_citations() {
+        // Given: Narrative, bracketed and grouped citations
+        let markdown = "As @a showed [@b](https://x.org/@c) [see @d; @e, p. 4].";
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+        // When: We scan the document
+        let scan = scan_citations(markdown, GroupingPolicy::Whitespace);
 
-        // Then: We find the citation with section locator
-        assert_eq!(citations.len(), 1);
-        assert_eq!(citations[0].id, "guide-1");
-        assert_eq!(citations[0].locator, Some("2.1".to_string()));
-        assert_eq!(citations[0].label, Some("section".to_string()));
+        // Then: Clusters match extract_citation_clusters
+        assert_eq!(scan.clusters, extract_citation_clusters(markdown));
+        assert_eq!(scan.clusters.len(), 2);
+
+        // And: The citations include the items of the Pandoc group, each with its own span
+        let ids: Vec<&str> = scan.citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "d", "e"]);
+        assert_eq!(
+            &markdown[scan.citations[2].span.0..scan.citations[2].span.1],
+            "see @d"
+        );
+        assert_eq!(scan.citations[3].locator.as_deref(), Some("4"));
+        assert_eq!(extract_citations(markdown).len(), 2);
     }
 
     #[test]
-    fn test_citation_locator_span() {
-        // Given: Markdown with a locator citation
-        let markdown = "Text [@item-1, p. 42] more text.";
+    fn test_scan_unbalanced_brackets() {
+        // Given: A large document full of unclosed brackets and parentheses
+        let markdown = "[a [b [@c ](x @d ".repeat(20_000);
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+        // When: We scan it
+        let scan = scan_citations(&markdown, GroupingPolicy::Whitespace);
 
-        // Then: The span correctly includes the entire citation with locator
-        assert_eq!(citations.len(), 1);
-        let (start, end) = citations[0].span;
-        assert_eq!(&markdown[start..end], "[@item-1, p. 42]");
+        // Then: Every citation is found, none taking the unclosed parenthesis as a URL
+        assert_eq!(scan.citations.len(), 40_000);
+        assert!(scan.citations.iter().all(|c| c.url.is_none()));
+        assert_eq!(scan.citations[1].id, "d");
     }
 
     #[test]
-    fn test_mixed_citations_with_and_without_locators() {
-        // Given: Markdown with both types of citations
-        let markdown = "First [@item-1] and second [@item-2, p. 10] in text.";
+    fn test_scan_wikilinks() {
+        // Given: Wikilink citations, one with a locator alias, next to a
+        // bracketed citation, and wikilinks that are not citations
+        let markdown =
+            "Shown [[@a|pp. 3-5]] [[@b]] [@c], see [[Notes]], [[@d#Summary]] and ![[@e]].";
+        let extensions = SyntaxExtensions {
+            wikilinks: true,
+            ..Default::default()
+        };
 
-        // When: We extract citations
-        let citations = extract_citations(markdown);
+        // When: We scan with wikilinks on
+        let scan = scan_citations_with(markdown, GroupingPolicy::Whitespace, extensions);
 
-        // Then: We find both citations with correct properties
-        assert_eq!(citations.len(), 2);
-        assert_eq!(citations[0].id, "item-1");
-        assert_eq!(citations[0].locator, None);
-        assert_eq!(citations[0].label, None);
-        assert_eq!(citations[1].id, "item-2");
-        assert_eq!(citations[1].locator, Some("10".to_string()));
-        assert_eq!(citations[1].label, Some("page".to_string()));
+        // Then: Adjacent wikilinks cluster with the bracketed citation
+        assert_eq!(scan.clusters.len(), 1);
+        let cluster = &scan.clusters[0];
+        assert_eq!(
+            &markdown[cluster.span.0..cluster.span.1],
+            "[[@a|pp. 3-5]] [[@b]] [@c]"
+        );
+        assert_eq!(cluster.items[0].locator.as_deref(), Some("3-5"));
+        assert_eq!(cluster.items[0].label.as_deref(), Some("page"));
+        assert_eq!(cluster.items[1].id, "b");
+        assert_eq!(cluster.items[1].mode, CitationMode::Normal);
+
+        // And: Other wikilinks and embeds are left alone
+        let ids: Vec<&str> = scan.citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_wikilinks_off_by_default() {
+        // Given: A wikilink citation
+        let markdown = "Shown [[@a]].";
+
+        // When: We scan without extensions
+        let scan = scan_citations(markdown, GroupingPolicy::Whitespace);
+
+        // Then: Only the inner brackets are a citation, as in Pandoc
+        assert_eq!(scan.clusters.len(), 1);
+        assert_eq!(scan.clusters[0].span, (7, 11));
+    }
+
+    #[test]
+    fn test_scan_quarto_crossrefs() {
+        // Given: A Quarto document with cross-references, citations and an R chunk
+        let markdown = "\
+As @fig-plot and [@tbl-data; @sec-intro] show, @smith2020 [@eq-1] [@jones2019].
+
+```{r}
+#| label: fig-plot
+plot(df$x, main = \"[@not_a_citation]\")
+```
+";
+        let quarto = SyntaxExtensions {
+            quarto: true,
+            ..Default::default()
+        };
+
+        // When: We scan it in Quarto mode
+        let scan = scan_citations_with(markdown, GroupingPolicy::Whitespace, quarto);
+
+        // Then: Only the bibliography keys are citations
+        let ids: Vec<&str> = scan.citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["smith2020", "jones2019"]);
+
+        // And: Without Quarto mode, cross-references read as citations
+        let scan = scan_citations(markdown, GroupingPolicy::Whitespace);
+        assert_eq!(scan.citations.len(), 6);
+    }
+
+    #[test]
+    fn test_quarto_crossrefs_mixed_with_citations() {
+        // Given: Groups mixing cross-references and bibliography keys
+        let markdown = "See [@fig-a; @smith, p. 4] and [@jones; see @tbl-b; @doe].";
+        let quarto = SyntaxExtensions {
+            quarto: true,
+            ..Default::default()
+        };
+
+        // When: We scan it in Quarto mode
+        let scan = scan_citations_with(markdown, GroupingPolicy::Whitespace, quarto);
+
+        // Then: The keys are still cited, the cross-references kept as affixes
+        let items: Vec<&CitationItem> = scan.clusters.iter().flat_map(|c| &c.items).collect();
+        let ids: Vec<&str> = items.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(ids, vec!["smith", "jones", "doe"]);
+        assert_eq!(items[0].prefix.as_deref(), Some("@fig-a;"));
+        assert_eq!(items[0].locator.as_deref(), Some("4"));
+        assert_eq!(items[1].suffix.as_deref(), Some("; see @tbl-b"));
     }
 }
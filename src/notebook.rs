@@ -0,0 +1,553 @@
+//! Jupyter notebooks.
+//!
+//! The Markdown cells of a notebook are processed as one document, so that
+//! citation numbering runs across cells. The notebook JSON is never
+//! re-serialized: only the `source` of Markdown cells whose text changed is
+//! rewritten in place, so code cells, outputs and metadata are kept byte for
+//! byte.
+
+use crate::markdown::find_bibliography_marker;
+use crate::output::{generate_output, replace_citations};
+use crate::processor::ProcessedCitation;
+use serde_json::Value;
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Separates the cells in the Markdown document: a blank line, so that
+/// citations never group across cells.
+const CELL_SEPARATOR: &str = "\n\n";
+
+/// Errors that can occur while reading a notebook.
+#[derive(Error, Debug)]
+pub enum NotebookError {
+    #[error("Invalid notebook: {0}")]
+    Invalid(String),
+}
+
+/// A Markdown cell of a notebook.
+#[derive(Debug, Clone, PartialEq)]
+struct MarkdownCell {
+    /// Index of the cell in the notebook, code cells included
+    index: usize,
+    /// Span of the `source` value in the notebook JSON
+    source_span: Span,
+    /// Range of the cell text in the Markdown document
+    range: (usize, usize),
+}
+
+/// A parsed Jupyter notebook (nbformat 4).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notebook<'a> {
+    /// The notebook JSON, as read
+    json: &'a str,
+    /// The Markdown cells, in order
+    cells: Vec<MarkdownCell>,
+    /// The text of the Markdown cells, joined by blank lines
+    markdown: String,
+    /// Position of the `]` closing the `cells` array
+    cells_end: usize,
+    /// Id for a new cell, when the format requires cell ids (nbformat 4.5)
+    new_cell_id: Option<String>,
+}
+
+impl<'a> Notebook<'a> {
+    /// Parses a notebook.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The notebook JSON
+    ///
+    /// # Returns
+    ///
+    /// The notebook, or an error if the JSON is not a notebook with a `cells`
+    /// array.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use csl_tools::notebook::Notebook;
+    ///
+    /// let json = r#"{"cells": [
+    ///   {"cell_type": "markdown", "id": "a", "metadata": {}, "source": ["See [@a].\n", "More."]},
+    ///   {"cell_type": "code", "id": "b", "metadata": {}, "source": "x = 1", "outputs": []}
+    /// ], "metadata": {}, "nbformat": 4, "nbformat_minor": 5}"#;
+    /// let notebook = Notebook::parse(json).unwrap();
+    /// assert_eq!(notebook.markdown(), "See [@a].\nMore.");
+    /// ```
+    pub fn parse(json: &'a str) -> Result<Self, NotebookError> {
+        // Validate the whole document first, so that the scanner below only
+        // sees well-formed JSON
+        let value: Value =
+            serde_json::from_str(json).map_err(|e| NotebookError::Invalid(e.to_string()))?;
+        if !value.get("cells").is_some_and(Value::is_array) {
+            return Err(NotebookError::Invalid(
+                "no `cells` array (only nbformat 4 notebooks are supported)".to_string(),
+            ));
+        }
+
+        let version = |key: &str| value.get(key).and_then(Value::as_u64).unwrap_or(0);
+        let new_cell_id = (version("nbformat") > 4
+            || (version("nbformat") == 4 && version("nbformat_minor") >= 5))
+            .then(|| unused_cell_id(&value["cells"]));
+
+        let mut scanner = Scanner { json, pos: 0 };
+        let cells_start = scanner
+            .object_entries()?
+            .into_iter()
+            .find(|(key, _)| key == "cells")
+            .map(|(_, span)| span.0)
+            .expect("validated above");
+
+        let mut notebook = Notebook {
+            json,
+            cells: Vec::new(),
+            markdown: String::new(),
+            cells_end: 0,
+            new_cell_id,
+        };
+        scanner.pos = cells_start;
+        let (cell_spans, cells_end) = scanner.array_items()?;
+        notebook.cells_end = cells_end;
+
+        for (index, cell_span) in cell_spans.into_iter().enumerate() {
+            scanner.pos = cell_span.0;
+            let entries = scanner.object_entries()?;
+            let value_of = |name: &str| {
+                entries
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map(|&(_, (start, end))| &json[start..end])
+            };
+            if value_of("cell_type") != Some("\"markdown\"") {
+                continue;
+            }
+            let Some(source) = value_of("source") else {
+                continue;
+            };
+            let source_start = source.as_ptr() as usize - json.as_ptr() as usize;
+
+            if !notebook.cells.is_empty() {
+                notebook.markdown.push_str(CELL_SEPARATOR);
+            }
+            let start = notebook.markdown.len();
+            notebook.markdown.push_str(&source_text(source)?);
+            notebook.cells.push(MarkdownCell {
+                index,
+                source_span: (source_start, source_start + source.len()),
+                range: (start, notebook.markdown.len()),
+            });
+        }
+
+        Ok(notebook)
+    }
+
+    /// Returns the text of the Markdown cells, joined by blank lines.
+    pub fn markdown(&self) -> &str {
+        &self.markdown
+    }
+
+    /// Returns the number of the cell holding a position of `markdown()`,
+    /// counting all the cells of the notebook from 1.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use csl_tools::notebook::Notebook;
+    ///
+    /// let json = r#"{"cells": [
+    ///   {"cell_type": "markdown", "id": "a", "metadata": {}, "source": "Intro."},
+    ///   {"cell_type": "code", "id": "b", "metadata": {}, "source": "x = 1", "outputs": []},
+    ///   {"cell_type": "markdown", "id": "c", "metadata": {}, "source": "See [@a]."}
+    /// ], "metadata": {}, "nbformat": 4, "nbformat_minor": 5}"#;
+    /// let notebook = Notebook::parse(json).unwrap();
+    /// let offset = notebook.markdown().find("[@a]").unwrap();
+    /// assert_eq!(notebook.cell_number(offset), Some(3));
+    /// ```
+    pub fn cell_number(&self, offset: usize) -> Option<usize> {
+        self.cells
+            .iter()
+            .find(|cell| offset >= cell.range.0 && offset <= cell.range.1)
+            .map(|cell| cell.index + 1)
+    }
+
+    /// Writes the notebook with the citations of its Markdown cells replaced.
+    ///
+    /// The bibliography replaces the first `<!-- bibliography -->` or
+    /// `::: {#refs}` marker of a cell; without a marker, it is appended in a
+    /// new Markdown cell after `bib_header`. Footnote definitions go at the
+    /// end of the last Markdown cell.
+    ///
+    /// # Arguments
+    ///
+    /// * `replacements` - The replacements, with spans in `markdown()`
+    /// * `bibliography` - The formatted bibliography (if any)
+    /// * `bib_header` - The header of an appended bibliography
+    /// * `footnotes` - Footnote definitions to add (may be empty)
+    ///
+    /// # Returns
+    ///
+    /// The notebook JSON.
+    pub fn render(
+        &self,
+        replacements: &[ProcessedCitation],
+        bibliography: Option<&str>,
+        bib_header: &str,
+        footnotes: &str,
+    ) -> String {
+        let bibliography = bibliography.filter(|bib| !bib.is_empty());
+        let mut bibliography_placed = false;
+        let mut output = String::with_capacity(self.json.len());
+        let mut copied = 0;
+
+        for (n, cell) in self.cells.iter().enumerate() {
+            let (start, end) = cell.range;
+            let local: Vec<ProcessedCitation> = replacements
+                .iter()
+                .filter(|r| r.original_span.0 >= start && r.original_span.1 <= end)
+                .map(|r| ProcessedCitation {
+                    original_span: (r.original_span.0 - start, r.original_span.1 - start),
+                    formatted: r.formatted.clone(),
+                })
+                .collect();
+            let original = &self.markdown[start..end];
+            let mut text = replace_citations(original, &local);
+
+            if let Some(bib) = bibliography.filter(|_| !bibliography_placed) {
+                if let Some((marker_start, marker_end)) = find_bibliography_marker(&text) {
+                    text.replace_range(marker_start..marker_end, bib);
+                    bibliography_placed = true;
+                }
+            }
+            if n + 1 == self.cells.len() && !footnotes.is_empty() {
+                text = format!("{}\n\n{}", text.trim_end(), footnotes);
+            }
+
+            if text != original {
+                let (source_start, source_end) = cell.source_span;
+                output.push_str(&self.json[copied..source_start]);
+                output.push_str(&source_json(&text, &self.json[source_start..source_end]));
+                copied = source_end;
+            }
+        }
+
+        // Without a marker, the bibliography gets a cell of its own
+        if let Some(bib) = bibliography.filter(|_| !bibliography_placed) {
+            let text = generate_output("", Some(bib), bib_header);
+            let source = source_json(text.trim_start(), "[]");
+            let before_end = self.json[..self.cells_end].trim_end();
+            let separator = if before_end.ends_with('[') { "" } else { "," };
+            output.push_str(&self.json[copied..before_end.len()]);
+            let id = match &self.new_cell_id {
+                Some(id) => format!("\n   \"id\": \"{}\",", id),
+                None => String::new(),
+            };
+            output.push_str(&format!(
+                "{}\n  {{\n   \"cell_type\": \"markdown\",{}\n   \"metadata\": {{}},\n   \"source\": {}\n  }}",
+                separator,
+                id,
+                source
+            ));
+            copied = before_end.len();
+        }
+
+        output.push_str(&self.json[copied..]);
+        output
+    }
+}
+
+/// Returns an id that no cell of the notebook has, for the bibliography cell.
+fn unused_cell_id(cells: &Value) -> String {
+    let ids: HashSet<&str> = cells
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|cell| cell.get("id")?.as_str())
+        .collect();
+    let mut id = "bibliography".to_string();
+    let mut n = 1;
+    while ids.contains(id.as_str()) {
+        n += 1;
+        id = format!("bibliography-{}", n);
+    }
+    id
+}
+
+/// Returns the text of a `source` value: a string, or an array of strings
+/// to concatenate.
+fn source_text(source: &str) -> Result<String, NotebookError> {
+    let value: Value =
+        serde_json::from_str(source).map_err(|e| NotebookError::Invalid(e.to_string()))?;
+    match value {
+        Value::String(text) => Ok(text),
+        Value::Array(lines) => lines
+            .iter()
+            .map(|line| line.as_str())
+            .collect::<Option<String>>()
+            .ok_or_else(|| NotebookError::Invalid("cell source must hold strings".to_string())),
+        _ => Err(NotebookError::Invalid(
+            "cell source must be a string or an array of strings".to_string(),
+        )),
+    }
+}
+
+/// Serializes `text` as a `source` value in the form of `original`: an array
+/// of lines laid out like the original items, or a single string.
+fn source_json(text: &str, original: &str) -> String {
+    let quote = |line: &str| serde_json::to_string(line).expect("strings always serialize");
+    if !original.starts_with('[') {
+        return quote(text);
+    }
+
+    // Keep the whitespace around the items: "\n    " in Jupyter's layout
+    let inner = &original[1..original.len() - 1];
+    let (item_indent, closing_indent) = if inner.trim().is_empty() {
+        ("\n    ", "\n   ")
+    } else {
+        let closing = &inner[inner.trim_end().len()..];
+        (&inner[..inner.len() - inner.trim_start().len()], closing)
+    };
+
+    let items: Vec<String> = text.split_inclusive('\n').map(quote).collect();
+    if items.is_empty() {
+        return "[]".to_string();
+    }
+    format!(
+        "[{}{}{}]",
+        item_indent,
+        items.join(&format!(",{}", item_indent)),
+        closing_indent
+    )
+}
+
+/// Start and end of a JSON value in the notebook.
+type Span = (usize, usize);
+
+/// Finds the spans of values in well-formed JSON.
+struct Scanner<'a> {
+    json: &'a str,
+    pos: usize,
+}
+
+impl Scanner<'_> {
+    /// Reads the object at `pos`: each key with the span of its value.
+    fn object_entries(&mut self) -> Result<Vec<(String, Span)>, NotebookError> {
+        self.expect(b'{')?;
+        let mut entries = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(b'}') {
+                self.pos += 1;
+                return Ok(entries);
+            }
+            let key_start = self.pos;
+            self.skip_value()?;
+            let key: String = serde_json::from_str(&self.json[key_start..self.pos])
+                .map_err(|e| NotebookError::Invalid(e.to_string()))?;
+            self.expect(b':')?;
+            self.skip_whitespace();
+            let value_start = self.pos;
+            self.skip_value()?;
+            entries.push((key, (value_start, self.pos)));
+            self.skip_whitespace();
+            if self.peek() == Some(b',') {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Reads the array at `pos`: the span of each item, and the position of
+    /// the closing `]`.
+    fn array_items(&mut self) -> Result<(Vec<Span>, usize), NotebookError> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(b']') {
+                self.pos += 1;
+                return Ok((items, self.pos - 1));
+            }
+            let start = self.pos;
+            self.skip_value()?;
+            items.push((start, self.pos));
+            self.skip_whitespace();
+            if self.peek() == Some(b',') {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Moves past the value at `pos`.
+    fn skip_value(&mut self) -> Result<(), NotebookError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.object_entries().map(|_| ()),
+            Some(b'[') => self.array_items().map(|_| ()),
+            Some(b'"') => {
+                let bytes = self.json.as_bytes();
+                let mut i = self.pos + 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                self.pos = i + 1;
+                Ok(())
+            }
+            Some(_) => {
+                let rest = &self.json[self.pos..];
+                self.pos += rest
+                    .find(|c: char| c == ',' || c == '}' || c == ']' || c.is_whitespace())
+                    .unwrap_or(rest.len());
+                Ok(())
+            }
+            None => Err(NotebookError::Invalid("unexpected end of JSON".to_string())),
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), NotebookError> {
+        self.skip_whitespace();
+        if self.peek() != Some(byte) {
+            return Err(NotebookError::Invalid(format!(
+                "expected '{}' at byte {}",
+                byte as char, self.pos
+            )));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.json[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.json.as_bytes().get(self.pos).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A notebook in Jupyter's own layout
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "id": "intro",
+   "metadata": {},
+   "source": [
+    "# Analysis\n",
+    "As shown [@a]."
+   ]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "id": "plot",
+   "metadata": {},
+   "outputs": [{"output_type": "stream", "name": "stdout", "text": ["[@a]\n"]}],
+   "source": ["print('[@a]')"]
+  },
+  {
+   "cell_type": "markdown",
+   "id": "bibliography",
+   "metadata": {},
+   "source": "Also [@b]."
+  }
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}
+"##;
+
+    #[test]
+    fn test_parse_markdown_cells() {
+        // Given: A notebook with two Markdown cells around a code cell
+
+        // When: We parse it
+        let notebook = Notebook::parse(NOTEBOOK).unwrap();
+
+        // Then: The Markdown cells form one document, without the code cell
+        assert_eq!(
+            notebook.markdown(),
+            "# Analysis\nAs shown [@a].\n\nAlso [@b]."
+        );
+    }
+
+    #[test]
+    fn test_render_keeps_code_cells() {
+        // Given: Replacements in both Markdown cells
+        let notebook = Notebook::parse(NOTEBOOK).unwrap();
+        let markdown = notebook.markdown();
+        let replace = |text: &str, formatted: &str| {
+            let start = markdown.find(text).unwrap();
+            ProcessedCitation {
+                original_span: (start, start + text.len()),
+                formatted: formatted.to_string(),
+            }
+        };
+        let replacements = vec![replace("[@a]", "(1)"), replace("[@b]", "(2)")];
+
+        // When: We render the notebook with a bibliography
+        let output = notebook.render(&replacements, Some("BIB"), "## References", "");
+
+        // Then: Markdown sources are rewritten in their own layout, the code
+        // cell is untouched and the bibliography gets a new cell, with an id
+        // of its own
+        let code_cell = &NOTEBOOK[NOTEBOOK.find("  {\n   \"cell_type\": \"code\"").unwrap()..];
+        let code_cell = &code_cell[..code_cell.find("\n  },").unwrap()];
+        assert!(output.contains(code_cell));
+        assert!(output
+            .contains("   \"source\": [\n    \"# Analysis\\n\",\n    \"As shown (1).\"\n   ]"));
+        assert!(output.contains("\"source\": \"Also (2).\""));
+        assert!(output.contains(
+            "\"source\": [\n    \"## References\\n\",\n    \"\\n\",\n    \"BIB\"\n   ]\n  }\n ],"
+        ));
+        let value: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(value["cells"].as_array().unwrap().len(), 4);
+        assert_eq!(value["cells"][3]["id"].as_str(), Some("bibliography-2"));
+    }
+
+    #[test]
+    fn test_render_bibliography_at_marker_cell() {
+        // Given: A notebook whose last cell is a bibliography marker
+        let json = r###"{"cells": [{"cell_type": "markdown", "id": "a", "metadata": {}, "source": ["[@a]"]},
+{"cell_type": "markdown", "id": "b", "metadata": {}, "source": ["## Sources\n", "<!-- bibliography -->"]}],
+"metadata": {}, "nbformat": 4, "nbformat_minor": 5}"###;
+        let notebook = Notebook::parse(json).unwrap();
+
+        // When: We render it with a bibliography
+        let output = notebook.render(&[], Some("BIB"), "## References", "");
+
+        // Then: The marker is replaced, no cell is added
+        let value: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(value["cells"].as_array().unwrap().len(), 2);
+        assert_eq!(value["cells"][1]["source"][1].as_str(), Some("BIB"));
+    }
+
+    #[test]
+    fn test_render_bibliography_cell_without_id_before_nbformat_4_5() {
+        // Given: An nbformat 4.4 notebook, whose cells have no ids
+        let json = r#"{"cells": [{"cell_type": "markdown", "metadata": {}, "source": "[@a]"}],
+"metadata": {}, "nbformat": 4, "nbformat_minor": 4}"#;
+        let notebook = Notebook::parse(json).unwrap();
+
+        // When: We render it with a bibliography
+        let output = notebook.render(&[], Some("BIB"), "## References", "");
+
+        // Then: The bibliography cell has no id either
+        let value: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(value["cells"].as_array().unwrap().len(), 2);
+        assert!(value["cells"][1].get("id").is_none());
+    }
+
+    #[test]
+    fn test_invalid_notebook() {
+        // Given: JSON that is not a notebook
+        let result = Notebook::parse(r#"{"worksheets": []}"#);
+
+        // Then: It is rejected
+        assert!(matches!(result, Err(NotebookError::Invalid(_))));
+    }
+}
@@ -0,0 +1,421 @@
+//! Footnote generation for note styles.
+//!
+//! With a note style (`<style class="note">`, e.g. Chicago notes or OSCOLA),
+//! every citation is rendered as a footnote. Clusters in the body become
+//! generated `[^cN]` footnote references with matching definitions, while
+//! clusters already inside a footnote (a `[^label]: ...` definition or an
+//! inline `^[...]` note) are rendered in place. As in Pandoc, a narrative
+//! citation in the body (`As @smith2020 says`) keeps the author's name in the
+//! sentence, followed by the note: `As Smith[^c1] says`.
+
+use crate::markdown::{mask_non_prose, CitationCluster, CitationMode};
+use crate::processor::ProcessedCitation;
+use regex::Regex;
+use std::collections::HashMap;
+
+/// The footnotes of a Markdown document.
+struct Footnotes {
+    /// `[^label]: ...` definitions: (label, span of the whole definition)
+    definitions: Vec<(String, (usize, usize))>,
+    /// `[^label]` references in document order: (label, position)
+    references: Vec<(String, usize)>,
+    /// `^[...]` inline notes
+    inline_notes: Vec<(usize, usize)>,
+}
+
+impl Footnotes {
+    /// Finds the footnote definitions, references and inline notes, ignoring
+    /// code and comments.
+    fn parse(markdown: &str) -> Self {
+        let prose = mask_non_prose(markdown);
+        let definition_re = Regex::new(r"^ {0,3}\[\^([^\]\s]+)\]:").unwrap();
+        let reference_re = Regex::new(r"\[\^([^\]\s]+)\]").unwrap();
+
+        // A definition runs until a blank line followed by a non-indented line
+        let mut definitions = Vec::new();
+        // Position of the `[^label]` opening each definition
+        let mut definition_labels = Vec::new();
+        let mut current: Option<(String, usize, usize)> = None;
+        let mut previous_blank = false;
+        let mut offset = 0;
+
+        for line in prose.split_inclusive('\n') {
+            let line_start = offset;
+            offset += line.len();
+            let content = line.trim_end_matches(['\n', '\r']);
+            let blank = content.trim().is_empty();
+            let indented = content.starts_with("    ") || content.starts_with('\t');
+
+            if let Some(cap) = definition_re.captures(content) {
+                definitions.extend(current.take().map(|(l, s, e)| (l, (s, e))));
+                definition_labels.push(line_start + content.len() - content.trim_start().len());
+                current = Some((cap[1].to_string(), line_start, line_start + content.len()));
+            } else if let Some((_, _, end)) = current.as_mut() {
+                if !blank && previous_blank && !indented {
+                    definitions.extend(current.take().map(|(l, s, e)| (l, (s, e))));
+                } else if !blank {
+                    *end = line_start + content.len();
+                }
+            }
+            previous_blank = blank;
+        }
+        definitions.extend(current.map(|(l, s, e)| (l, (s, e))));
+
+        // The label opening a definition is not a reference to it
+        let references = reference_re
+            .captures_iter(&prose)
+            .filter(|cap| !definition_labels.contains(&cap.get(0).unwrap().start()))
+            .map(|cap| (cap[1].to_string(), cap.get(0).unwrap().start()))
+            .collect();
+
+        Footnotes {
+            definitions,
+            references,
+            inline_notes: inline_note_ranges(&prose),
+        }
+    }
+
+    /// Returns where the note holding `pos` is referenced in the text (the
+    /// `^[` of an inline note, or the first `[^label]` reference to a
+    /// definition), or `None` if `pos` is not inside a footnote.
+    fn note_anchor(&self, pos: usize) -> Option<usize> {
+        if let Some(&(start, _)) = self
+            .inline_notes
+            .iter()
+            .find(|&&(start, end)| pos >= start && pos < end)
+        {
+            return Some(start);
+        }
+
+        let (label, (start, _)) = self
+            .definitions
+            .iter()
+            .find(|(_, (start, end))| pos >= *start && pos < *end)?;
+        let reference = self
+            .references
+            .iter()
+            .find(|(l, _)| l == label)
+            .map(|&(_, reference)| reference);
+        Some(reference.unwrap_or(*start))
+    }
+
+    /// Returns true if a footnote with this label already exists.
+    fn has_label(&self, label: &str) -> bool {
+        self.definitions.iter().any(|(l, _)| l == label)
+            || self.references.iter().any(|(l, _)| l == label)
+    }
+}
+
+/// Finds the `^[...]` inline notes, with balanced brackets inside.
+fn inline_note_ranges(prose: &str) -> Vec<(usize, usize)> {
+    let bytes = prose.as_bytes();
+    let mut ranges = Vec::new();
+    let mut search_from = 0;
+
+    while let Some(found) = prose[search_from..].find("^[") {
+        let start = search_from + found;
+        let mut depth = 0;
+        let mut end = None;
+        for (i, &byte) in bytes.iter().enumerate().skip(start + 1) {
+            match byte {
+                b'[' => depth += 1,
+                b']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        end = Some(i + 1);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        match end {
+            Some(end) => {
+                ranges.push((start, end));
+                search_from = end;
+            }
+            None => search_from = start + 2,
+        }
+    }
+
+    ranges
+}
+
+/// Sorts citation clusters in note order.
+///
+/// A cluster inside a footnote takes the place of the footnote reference, so
+/// that positions computed by the CSL processor (first, subsequent, ibid)
+/// follow the order in which the reader meets the notes.
+///
+/// # Arguments
+///
+/// * `markdown` - The Markdown text the clusters were extracted from
+/// * `clusters` - The citation clusters, in document order
+///
+/// # Returns
+///
+/// The clusters in note order.
+pub fn order_by_notes(markdown: &str, clusters: &[CitationCluster]) -> Vec<CitationCluster> {
+    let footnotes = Footnotes::parse(markdown);
+
+    let mut ordered = clusters.to_vec();
+    ordered.sort_by_key(|cluster| {
+        let start = cluster.span.0;
+        (footnotes.note_anchor(start).unwrap_or(start), start)
+    });
+    ordered
+}
+
+/// Prepares citation clusters for formatting with a note style.
+///
+/// Narrative citations in the body become regular ones, since their note
+/// holds a full citation while the author's name stays in the text (see
+/// `footnote_citations`). Those inside a footnote are rendered in place, and
+/// stay narrative.
+///
+/// # Arguments
+///
+/// * `markdown` - The Markdown text the clusters were extracted from
+/// * `clusters` - The citation clusters
+///
+/// # Returns
+///
+/// The clusters to format.
+pub fn note_style_clusters(markdown: &str, clusters: &[CitationCluster]) -> Vec<CitationCluster> {
+    let footnotes = Footnotes::parse(markdown);
+
+    let mut prepared = clusters.to_vec();
+    for cluster in &mut prepared {
+        if footnotes.note_anchor(cluster.span.0).is_none() {
+            for item in &mut cluster.items {
+                item.mode = CitationMode::Normal;
+            }
+        }
+    }
+    prepared
+}
+
+/// Turns formatted citations into footnotes.
+///
+/// Each citation in the body is replaced by a `[^cN]` reference (labels
+/// already used in the document are skipped). As in Pandoc, the reference
+/// is attached to the preceding word and moved after any punctuation that
+/// follows: "as shown [@a]." becomes "as shown.[^c1]". A narrative citation
+/// is replaced by the author's name followed by the reference instead:
+/// "as @a says" becomes "as Smith[^c1] says". Citations already inside a
+/// footnote keep their formatted text.
+///
+/// # Arguments
+///
+/// * `markdown` - The original Markdown text
+/// * `processed` - The formatted citations, with spans in `markdown`
+/// * `clusters` - The citation clusters, for their citation mode
+/// * `authors` - The short author names by reference id (see
+///   `processor::short_author_names`)
+///
+/// # Returns
+///
+/// The replacements to apply with `replace_citations`, and the footnote
+/// definitions to append to the document (one per line, empty if none).
+pub fn footnote_citations(
+    markdown: &str,
+    processed: &[ProcessedCitation],
+    clusters: &[CitationCluster],
+    authors: &HashMap<String, String>,
+) -> (Vec<ProcessedCitation>, String) {
+    let footnotes = Footnotes::parse(markdown);
+
+    let mut sorted: Vec<&ProcessedCitation> = processed.iter().collect();
+    sorted.sort_by_key(|citation| citation.original_span.0);
+
+    let mut replacements = Vec::with_capacity(sorted.len());
+    let mut definitions = Vec::new();
+    let mut number = 0;
+
+    for citation in sorted {
+        let (mut start, mut end) = citation.original_span;
+        if footnotes.note_anchor(start).is_some() {
+            replacements.push(citation.clone());
+            continue;
+        }
+
+        let label = loop {
+            number += 1;
+            let label = format!("c{}", number);
+            if !footnotes.has_label(&label) {
+                break label;
+            }
+        };
+
+        // A narrative citation keeps its author in the sentence
+        let author = clusters
+            .iter()
+            .find(|cluster| cluster.span == citation.original_span)
+            .and_then(|cluster| cluster.items.first())
+            .filter(|item| item.mode == CitationMode::AuthorInText)
+            .and_then(|item| authors.get(&item.id));
+        if let Some(author) = author {
+            replacements.push(ProcessedCitation {
+                original_span: (start, end),
+                formatted: format!("{}[^{}]", author, label),
+            });
+            definitions.push(format!("[^{}]: {}", label, citation.formatted));
+            continue;
+        }
+
+        // Drop the space before the citation, unless it starts the line
+        let before = markdown[..start].trim_end_matches([' ', '\t']);
+        if !before.is_empty() && !before.ends_with('\n') {
+            start = before.len();
+        }
+        let punctuation: String = markdown[end..]
+            .chars()
+            .take_while(|c| ".,;:!?".contains(*c))
+            .collect();
+        end += punctuation.len();
+
+        replacements.push(ProcessedCitation {
+            original_span: (start, end),
+            formatted: format!("{}[^{}]", punctuation, label),
+        });
+        definitions.push(format!("[^{}]: {}", label, citation.formatted));
+    }
+
+    (replacements, definitions.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown::extract_citation_clusters;
+    use crate::output::replace_citations;
+
+    /// Formats each cluster as its ids, to check replacements without csl_proc
+    fn fake_processed(clusters: &[CitationCluster]) -> Vec<ProcessedCitation> {
+        clusters
+            .iter()
+            .map(|cluster| ProcessedCitation {
+                original_span: cluster.span,
+                formatted: cluster
+                    .items
+                    .iter()
+                    .map(|item| item.id.as_str())
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_body_citations_become_footnotes() {
+        // Given: Two citations in the body
+        let markdown = "As shown [@a]. Later work [@b; @c] agrees";
+        let clusters = extract_citation_clusters(markdown);
+
+        // When: We turn them into footnotes
+        let (replacements, definitions) = footnote_citations(
+            markdown,
+            &fake_processed(&clusters),
+            &clusters,
+            &HashMap::new(),
+        );
+        let content = replace_citations(markdown, &replacements);
+
+        // Then: References follow the punctuation, definitions are generated
+        assert_eq!(content, "As shown.[^c1] Later work[^c2] agrees");
+        assert_eq!(definitions, "[^c1]: a\n[^c2]: b; c");
+    }
+
+    #[test]
+    fn test_narrative_citation_keeps_author_in_text() {
+        // Given: A narrative citation in the body and one in a footnote
+        let markdown = "As @a says, it works.[^1]\n\n[^1]: Unlike @b.\n";
+        let clusters = extract_citation_clusters(markdown);
+        let authors: HashMap<String, String> = [("a", "Smith"), ("b", "Doe")]
+            .map(|(id, name)| (id.to_string(), name.to_string()))
+            .into();
+
+        // When: We prepare the clusters and turn them into footnotes
+        let prepared = note_style_clusters(markdown, &clusters);
+        let (replacements, definitions) =
+            footnote_citations(markdown, &fake_processed(&clusters), &clusters, &authors);
+        let content = replace_citations(markdown, &replacements);
+
+        // Then: The body one is formatted as a regular note, after the author's name
+        assert_eq!(prepared[0].items[0].mode, CitationMode::Normal);
+        assert_eq!(prepared[1].items[0].mode, CitationMode::AuthorInText);
+        assert_eq!(
+            content,
+            "As Smith[^c1] says, it works.[^1]\n\n[^1]: Unlike b.\n"
+        );
+        assert_eq!(definitions, "[^c1]: a");
+    }
+
+    #[test]
+    fn test_citations_in_footnotes_stay_in_place() {
+        // Given: Citations inside a footnote definition and an inline note
+        let markdown = "Text[^1] and^[See [@b].] more.\n\n[^1]: As in [@a].\n";
+        let clusters = extract_citation_clusters(markdown);
+
+        // When: We turn them into footnotes
+        let (replacements, definitions) = footnote_citations(
+            markdown,
+            &fake_processed(&clusters),
+            &clusters,
+            &HashMap::new(),
+        );
+        let content = replace_citations(markdown, &replacements);
+
+        // Then: They are rendered where they are, no footnote is generated
+        assert_eq!(content, "Text[^1] and^[See b.] more.\n\n[^1]: As in a.\n");
+        assert!(definitions.is_empty());
+    }
+
+    #[test]
+    fn test_generated_labels_skip_existing_ones() {
+        // Given: A document that already uses the [^c1] label
+        let markdown = "Text[^c1] cites [@a].\n\n[^c1]: A note.";
+        let clusters = extract_citation_clusters(markdown);
+
+        // When: We turn the citation into a footnote
+        let (_, definitions) = footnote_citations(
+            markdown,
+            &fake_processed(&clusters),
+            &clusters,
+            &HashMap::new(),
+        );
+
+        // Then: The next free label is used
+        assert_eq!(definitions, "[^c2]: a");
+    }
+
+    #[test]
+    fn test_clusters_ordered_by_notes() {
+        // Given: A footnote referenced before a body citation, but defined after it
+        let markdown = "First[^n] then [@b].\n\n[^n]: See [@a].\n";
+        let clusters = extract_citation_clusters(markdown);
+
+        // When: We order the clusters by notes
+        let ordered = order_by_notes(markdown, &clusters);
+
+        // Then: The footnote's citation comes first
+        let ids: Vec<&str> = ordered.iter().map(|c| c.items[0].id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_definition_continues_over_indented_paragraphs() {
+        // Given: A multi-paragraph footnote followed by body text
+        let markdown = "A[^1].\n\n[^1]: One.\n\n    Two [@a].\n\nBody [@b].";
+
+        // When: We parse the footnotes
+        let footnotes = Footnotes::parse(markdown);
+
+        // Then: The indented paragraph belongs to the note, the body does not
+        let in_note = markdown.find("[@a]").unwrap();
+        let in_body = markdown.find("[@b]").unwrap();
+        assert_eq!(footnotes.note_anchor(in_note), Some(1));
+        assert_eq!(footnotes.note_anchor(in_body), None);
+    }
+}
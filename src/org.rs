@@ -0,0 +1,422 @@
+//! Org-mode input.
+//!
+//! Recognises org-cite citations:
+//!
+//! ```text
+//! [cite:@a;@b]  [cite/t:@a]  [cite:see @a p. 4]  [cite/na:@a]
+//! [cite:common prefix; @a; @b; common suffix]
+//! ```
+//!
+//! Each citation is a cluster. The style after `cite/` sets the mode: `t`
+//! (`text`) is narrative, `na` (`noauthor`) suppresses the author, others are
+//! parenthetical. Text after a key is its suffix, or its locator when it
+//! reads as one (`p. 4`).
+//!
+//! The bibliography replaces the `#+print_bibliography:` keyword. Source,
+//! example and comment blocks, and comment lines, are left untouched.
+
+use crate::markdown::{
+    parse_citation_key, parse_locator, Citation, CitationCluster, CitationItem, CitationMode,
+    CitationScan, LineIndex,
+};
+use crate::output::{bibliography_entries, convert_html};
+
+/// Blocks whose content is not Org prose.
+const VERBATIM_BLOCKS: &[&str] = &["src", "example", "export", "comment", "verse"];
+
+/// Finds the org-cite citations of an Org document.
+///
+/// # Arguments
+///
+/// * `org` - The Org document
+///
+/// # Returns
+///
+/// One cluster per citation, and one citation per item, in document order.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::markdown::CitationMode;
+/// use csl_tools::org::scan_org_citations;
+///
+/// let scan = scan_org_citations("As [cite/t:@a] showed [cite:see @b p. 4;@c].");
+/// assert_eq!(scan.clusters.len(), 2);
+/// assert_eq!(scan.clusters[0].items[0].mode, CitationMode::AuthorInText);
+/// assert_eq!(scan.clusters[1].items[0].locator.as_deref(), Some("4"));
+/// ```
+pub fn scan_org_citations(org: &str) -> CitationScan {
+    let prose = mask_non_prose(org);
+    let mut index = LineIndex::new(org);
+    let mut scan = CitationScan::default();
+    let mut i = 0;
+
+    while let Some(offset) = prose[i..].find("[cite") {
+        let start = i + offset;
+        match parse_org_citation(&prose, start, &mut index) {
+            Some(cluster) => {
+                scan.citations
+                    .extend(cluster.items.iter().map(|item| Citation {
+                        id: item.id.clone(),
+                        locator: item.locator.clone(),
+                        label: item.label.clone(),
+                        mode: item.mode,
+                        suppress_author: item.suppress_author,
+                        prefix: item.prefix.clone(),
+                        suffix: item.suffix.clone(),
+                        span: item.position.span,
+                        position: item.position,
+                        ..Default::default()
+                    }));
+                i = cluster.span.1;
+                scan.clusters.push(cluster);
+            }
+            None => i = start + 1,
+        }
+    }
+
+    scan
+}
+
+/// Parses the citation starting at `start` (on `[cite`).
+fn parse_org_citation(prose: &str, start: usize, index: &mut LineIndex) -> Option<CitationCluster> {
+    let rest = &prose[start + "[cite".len()..];
+    let colon = rest.find(|c: char| c == ':' || c == ']' || c == '[' || c.is_whitespace())?;
+    if !rest[colon..].starts_with(':') {
+        return None;
+    }
+    let style = match &rest[..colon] {
+        "" => "",
+        style => style.strip_prefix('/')?,
+    };
+    // Variants (`t/c`) only change capitalisation
+    let (mode, suppress_author) = match style.split('/').next().unwrap_or("") {
+        "t" | "text" => (CitationMode::AuthorInText, false),
+        "na" | "noauthor" => (CitationMode::Normal, true),
+        _ => (CitationMode::Normal, false),
+    };
+
+    let inner_start = start + "[cite".len() + colon + 1;
+    let close = prose[inner_start..].find(['[', ']'])?;
+    if prose.as_bytes()[inner_start + close] != b']' {
+        return None;
+    }
+    let inner = &prose[inner_start..inner_start + close];
+    let end = inner_start + close + 1;
+
+    // Parts without a key are the common prefix (first) and suffix (last)
+    let parts: Vec<(usize, &str)> = split_parts(inner);
+    let mut common_prefix = None;
+    let mut common_suffix = None;
+    let mut items = Vec::new();
+    for (n, &(offset, part)) in parts.iter().enumerate() {
+        match parse_org_item(part) {
+            Some(mut item) => {
+                item.mode = mode;
+                item.suppress_author = suppress_author;
+                let item_start = inner_start + offset + (part.len() - part.trim_start().len());
+                item.position = index.position((item_start, item_start + part.trim().len()));
+                items.push(item);
+            }
+            None if n == 0 => common_prefix = Some(part.trim()),
+            None if n + 1 == parts.len() && !items.is_empty() => common_suffix = Some(part.trim()),
+            None => return None,
+        }
+    }
+
+    if let Some(prefix) = common_prefix.filter(|p| !p.is_empty()) {
+        let first = items.first_mut()?;
+        first.prefix = Some(join_text(Some(prefix), first.prefix.as_deref()));
+    }
+    if let Some(suffix) = common_suffix.filter(|s| !s.is_empty()) {
+        let last = items.last_mut()?;
+        match (&last.locator, last.suffix.take()) {
+            // The common suffix may be the locator of the last item
+            (None, None) => set_suffix(last, suffix.to_string()),
+            (_, item_suffix) => {
+                last.suffix = Some(join_text(item_suffix.as_deref(), Some(suffix)));
+            }
+        }
+    }
+
+    (!items.is_empty()).then_some(CitationCluster {
+        items,
+        span: (start, end),
+    })
+}
+
+/// Splits the content of a citation on `;`, returning each part with its
+/// byte offset.
+fn split_parts(inner: &str) -> Vec<(usize, &str)> {
+    let mut parts = Vec::new();
+    let mut part_start = 0;
+    for (i, _) in inner.match_indices(';') {
+        parts.push((part_start, &inner[part_start..i]));
+        part_start = i + 1;
+    }
+    parts.push((part_start, &inner[part_start..]));
+    parts
+}
+
+/// Parses one citation item: `[prefix] @key [suffix]`.
+///
+/// Returns `None` if the part has no key.
+fn parse_org_item(part: &str) -> Option<CitationItem> {
+    let part = part.trim();
+    let at = part
+        .match_indices('@')
+        .map(|(i, _)| i)
+        .find(|&i| i == 0 || part[..i].ends_with(char::is_whitespace))?;
+    let (id, key_len) = parse_citation_key(&part[at + 1..])?;
+
+    let prefix = part[..at].trim();
+    let mut item = CitationItem {
+        id: id.to_string(),
+        prefix: (!prefix.is_empty()).then(|| prefix.to_string()),
+        ..Default::default()
+    };
+    let suffix = part[at + 1 + key_len..].trim();
+    if !suffix.is_empty() {
+        set_suffix(&mut item, suffix.to_string());
+    }
+    Some(item)
+}
+
+/// Sets the text after a key: its locator if it reads as one, or its suffix.
+fn set_suffix(item: &mut CitationItem, suffix: String) {
+    (item.locator, item.label, item.suffix) =
+        match parse_locator(suffix.strip_prefix(',').unwrap_or(&suffix)) {
+            (None, _, _) => (None, None, Some(suffix)),
+            parsed => parsed,
+        };
+}
+
+/// Joins two optional texts with a space.
+fn join_text(first: Option<&str>, second: Option<&str>) -> String {
+    [first, second]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Finds the `#+print_bibliography:` keyword line, where the bibliography goes.
+///
+/// # Arguments
+///
+/// * `org` - The Org document
+///
+/// # Returns
+///
+/// The span of the line (without its newline), if any.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::org::find_print_bibliography;
+///
+/// let org = "* References\n#+print_bibliography:\n";
+/// assert_eq!(find_print_bibliography(org), Some((13, 34)));
+/// ```
+pub fn find_print_bibliography(org: &str) -> Option<(usize, usize)> {
+    const KEYWORD: &str = "#+print_bibliography:";
+    let prose = mask_non_prose(org);
+    let mut offset = 0;
+    for line in prose.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let keyword = content.trim_start().get(..KEYWORD.len());
+        if keyword.is_some_and(|k| k.eq_ignore_ascii_case(KEYWORD)) {
+            return Some((offset, offset + content.len()));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Converts a bibliography rendered by csl_proc (HTML) to Org: one paragraph
+/// per entry.
+///
+/// # Arguments
+///
+/// * `html` - The formatted bibliography
+///
+/// # Returns
+///
+/// The entries as Org paragraphs.
+pub fn org_bibliography(html: &str) -> String {
+    bibliography_entries(html)
+        .into_iter()
+        .map(|entry| {
+            html_to_org(entry)
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Converts formatted text (csl_proc HTML) to Org markup.
+///
+/// Italic, bold, superscripts and subscripts become `/.../`, `*...*`,
+/// `^{...}` and `_{...}`; other tags are dropped and entities decoded.
+///
+/// # Arguments
+///
+/// * `html` - The formatted text
+///
+/// # Returns
+///
+/// The same text in Org markup.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::org::html_to_org;
+///
+/// assert_eq!(html_to_org("<i>Nature</i> &amp; <b>Science</b>"), "/Nature/ & *Science*");
+/// ```
+pub fn html_to_org(html: &str) -> String {
+    let markup = |tag: &str| match tag.split_whitespace().next().unwrap_or("") {
+        "i" | "em" => ("/", "/"),
+        "b" | "strong" => ("*", "*"),
+        "sup" => ("^{", "}"),
+        "sub" => ("_{", "}"),
+        "div" => ("", " "),
+        _ => ("", ""),
+    };
+    convert_html(html, markup, |c, org| org.push(c))
+}
+
+/// Generates the output Org document.
+///
+/// The bibliography replaces the `#+print_bibliography:` line, under the
+/// heading the document provides there. Without that keyword, it is appended
+/// at the end after `heading`.
+///
+/// # Arguments
+///
+/// * `content` - The Org document with citations already replaced
+/// * `bibliography` - The bibliography, in Org markup (if any)
+/// * `heading` - The heading of an appended bibliography, e.g. `* References`
+///
+/// # Returns
+///
+/// The complete output document.
+pub fn generate_org_output(content: &str, bibliography: Option<&str>, heading: &str) -> String {
+    let Some(bib) = bibliography.filter(|bib| !bib.is_empty()) else {
+        return content.to_string();
+    };
+
+    if let Some((start, end)) = find_print_bibliography(content) {
+        let mut output = content.to_string();
+        output.replace_range(start..end, bib);
+        return output;
+    }
+    format!("{}\n\n{}\n\n{}\n", content.trim_end(), heading, bib)
+}
+
+/// Blanks out verbatim blocks and comment lines, keeping byte offsets.
+fn mask_non_prose(org: &str) -> String {
+    let mut masked = org.as_bytes().to_vec();
+    let mut open_block: Option<String> = None;
+    let mut offset = 0;
+
+    for line in org.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim_start().to_ascii_lowercase();
+
+        let is_code = match &open_block {
+            Some(end_tag) => {
+                if trimmed.starts_with(end_tag.as_str()) {
+                    open_block = None;
+                }
+                true
+            }
+            None => {
+                if let Some(name) = trimmed.strip_prefix("#+begin_") {
+                    let name: String = name.chars().take_while(|c| c.is_alphanumeric()).collect();
+                    if VERBATIM_BLOCKS.contains(&name.as_str()) {
+                        open_block = Some(format!("#+end_{}", name));
+                    }
+                }
+                open_block.is_some() || trimmed == "#" || trimmed.starts_with("# ")
+            }
+        };
+
+        if is_code {
+            for byte in &mut masked[start..offset] {
+                if *byte != b'\n' {
+                    *byte = b' ';
+                }
+            }
+        }
+    }
+
+    // Whole lines are replaced, so the buffer stays valid UTF-8
+    String::from_utf8(masked).expect("masking whole lines keeps valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cite_styles() {
+        // Given: Citations in the default, text and noauthor styles
+        let org = "[cite:@a;@b] [cite/t:@c] [cite/na/b:@d]";
+
+        // When: We scan the document
+        let scan = scan_org_citations(org);
+
+        // Then: Each citation is a cluster with the mode of its style
+        assert_eq!(scan.clusters.len(), 3);
+        assert_eq!(scan.clusters[0].items.len(), 2);
+        assert_eq!(scan.clusters[0].span, (0, 12));
+        assert_eq!(scan.clusters[1].items[0].mode, CitationMode::AuthorInText);
+        assert!(scan.clusters[2].items[0].suppress_author);
+        assert_eq!(scan.citations.len(), 4);
+    }
+
+    #[test]
+    fn test_prefixes_and_suffixes() {
+        // Given: Item and common prefixes and suffixes
+        let org = "[cite:compare; see @a p. 4; @b and passim; for details]";
+
+        // When: We scan the document
+        let scan = scan_org_citations(org);
+
+        // Then: Common affixes go to the first and last items
+        let items = &scan.clusters[0].items;
+        assert_eq!(items[0].prefix.as_deref(), Some("compare see"));
+        assert_eq!(items[0].locator.as_deref(), Some("4"));
+        assert_eq!(items[0].label.as_deref(), Some("page"));
+        assert_eq!(items[1].suffix.as_deref(), Some("and passim for details"));
+    }
+
+    #[test]
+    fn test_blocks_and_comments_ignored() {
+        // Given: Citations in a source block and a comment line
+        let org = "#+BEGIN_SRC org\n[cite:@a]\n#+END_SRC\n# [cite:@b]\nText [cite:@c].\n";
+
+        // When: We scan the document
+        let scan = scan_org_citations(org);
+
+        // Then: Only the citation in prose is found
+        let ids: Vec<&str> = scan.citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["c"]);
+    }
+
+    #[test]
+    fn test_generate_org_output() {
+        // Given: A document with a #+print_bibliography: keyword
+        let org = "Text.\n\n* References\n#+PRINT_BIBLIOGRAPHY: :style numeric\n\n* Appendix\n";
+
+        // When: We add a bibliography
+        let output = generate_org_output(org, Some("Entry."), "* References");
+
+        // Then: It replaces the keyword, under the document's heading
+        assert_eq!(output, "Text.\n\n* References\nEntry.\n\n* Appendix\n");
+    }
+}
@@ -3,6 +3,7 @@
 //! This module handles replacing citations in the original Markdown text
 //! and generating the final output with the bibliography.
 
+use crate::markdown::find_bibliography_marker;
 use crate::processor::ProcessedCitation;
 
 /// Replaces citation markers in the Markdown with formatted citations.
@@ -45,6 +46,11 @@ pub fn replace_citations(markdown: &str, processed: &[ProcessedCitation]) -> Str
 
 /// Generates the final output with formatted citations and bibliography.
 ///
+/// The bibliography replaces the first `<!-- bibliography -->` comment or
+/// `::: {#refs}` div in the content, so that it can precede appendices; the
+/// document then provides its own heading. Without a marker, the bibliography
+/// is appended at the end after `bib_header`.
+///
 /// # Arguments
 ///
 /// * `content` - The Markdown content with citations already replaced
@@ -55,6 +61,14 @@ pub fn replace_citations(markdown: &str, processed: &[ProcessedCitation]) -> Str
 ///
 /// The complete output document.
 pub fn generate_output(content: &str, bibliography: Option<&str>, bib_header: &str) -> String {
+    if let Some(bib) = bibliography.filter(|bib| !bib.is_empty()) {
+        if let Some((start, end)) = find_bibliography_marker(content) {
+            let mut output = content.to_string();
+            output.replace_range(start..end, bib);
+            return output;
+        }
+    }
+
     let mut output = content.trim_end().to_string();
 
     if let Some(bib) = bibliography {
@@ -69,6 +83,101 @@ pub fn generate_output(content: &str, bibliography: Option<&str>, bib_header: &s
     output
 }
 
+/// Splits a bibliography rendered by csl_proc (HTML) into its entries, each
+/// from its `<div class="csl-entry">` to the matching `</div>`.
+pub(crate) fn bibliography_entries(html: &str) -> Vec<&str> {
+    const ENTRY: &str = "<div class=\"csl-entry\"";
+    let mut entries = Vec::new();
+    let mut rest = html;
+
+    while let Some(pos) = rest.find(ENTRY) {
+        let entry = &rest[pos..];
+        let mut depth = 0;
+        let mut end = entry.len();
+        for (i, _) in entry.match_indices('<') {
+            if entry[i..].starts_with("<div") {
+                depth += 1;
+            } else if entry[i..].starts_with("</div>") {
+                depth -= 1;
+                if depth == 0 {
+                    end = i + "</div>".len();
+                    break;
+                }
+            }
+        }
+        entries.push(&entry[..end]);
+        rest = &entry[end..];
+    }
+
+    entries
+}
+
+/// Rewrites formatted text (csl_proc HTML) in another markup.
+///
+/// `markup` gives the text opening and closing an HTML tag (passed without
+/// its angle brackets); `push_char` writes each character of the text, with
+/// entities already decoded, escaping it as the markup requires.
+pub(crate) fn convert_html(
+    html: &str,
+    markup: impl Fn(&str) -> (&'static str, &'static str),
+    mut push_char: impl FnMut(char, &mut String),
+) -> String {
+    let mut converted = String::with_capacity(html.len());
+    // What closes each open tag
+    let mut closing: Vec<&str> = Vec::new();
+    let mut rest = html;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            if let Some(tag_end) = rest.find('>') {
+                let tag = &rest[1..tag_end];
+                if tag.starts_with('/') {
+                    converted.push_str(closing.pop().unwrap_or(""));
+                } else if !tag.ends_with('/') {
+                    let (open, close) = markup(tag);
+                    converted.push_str(open);
+                    closing.push(close);
+                }
+                rest = &rest[tag_end + 1..];
+                continue;
+            }
+        }
+
+        let (decoded, len) = if c == '&' {
+            decode_entity(rest).unwrap_or((c, 1))
+        } else {
+            (c, c.len_utf8())
+        };
+        push_char(decoded, &mut converted);
+        rest = &rest[len..];
+    }
+
+    converted
+}
+
+/// Decodes the HTML entity at the start of `text`, returning the character
+/// and the length of the entity.
+pub(crate) fn decode_entity(text: &str) -> Option<(char, usize)> {
+    let end = text.find(';').filter(|&end| end <= 10)?;
+    let name = &text[1..end];
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        _ => {
+            let code = match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => name.strip_prefix('#')?.parse().ok()?,
+            };
+            char::from_u32(code)?
+        }
+    };
+    Some((c, end + 1))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -246,4 +355,43 @@ mod tests {
         assert!(result.contains("## References"));
         assert!(result.contains("<div>Bib</div>"));
     }
+
+    #[test]
+    fn test_generate_output_at_marker() {
+        // Given: Content with a bibliography marker before an appendix
+        let content = "Text.\n\n## References\n\n<!-- bibliography -->\n\n## Appendix\n";
+
+        // When: We generate the output
+        let result = generate_output(content, Some("<div>Bib</div>"), "## References");
+
+        // Then: The bibliography replaces the marker, without a second header
+        assert_eq!(
+            result,
+            "Text.\n\n## References\n\n<div>Bib</div>\n\n## Appendix\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_output_at_refs_div() {
+        // Given: Content with a Pandoc refs div
+        let content = "Text.\n\n::: {#refs}\n:::\n\nSupplementary material.";
+
+        // When: We generate the output
+        let result = generate_output(content, Some("<div>Bib</div>"), "## References");
+
+        // Then: The div is replaced by the bibliography
+        assert_eq!(result, "Text.\n\n<div>Bib</div>\n\nSupplementary material.");
+    }
+
+    #[test]
+    fn test_generate_output_marker_kept_without_bib() {
+        // Given: Content with a marker but no bibliography
+        let content = "Text.\n\n<!-- bibliography -->";
+
+        // When: We generate the output
+        let result = generate_output(content, None, "## References");
+
+        // Then: The content is unchanged
+        assert_eq!(result, content);
+    }
 }
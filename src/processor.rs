@@ -3,7 +3,8 @@
 //! This module orchestrates the formatting of citations and bibliographies
 //! by calling into the csl_proc library.
 
-use crate::markdown::Citation;
+use crate::aliases::KeyAliases;
+use crate::markdown::{Citation, CitationItem, CitationMode, CitationScan};
 use serde_json::Value;
 use std::collections::{HashMap, HashSet};
 use thiserror::Error;
@@ -20,6 +21,8 @@ pub enum ProcessorError {
     #[error("Invalid JSON: {0}")]
     InvalidJson(String),
 
+    #[error("Ambiguous reference: {key} matches several references {ids:?}")]
+    AmbiguousReference { key: String, ids: Vec<String> },
 }
 
 /// A citation that has been formatted by csl_proc.
@@ -61,37 +64,24 @@ pub fn format_citations(
         ProcessorError::InvalidJson("References must be a JSON array".to_string())
     })?;
 
-    // Build a set of available reference IDs for validation
-    let available_ids: HashSet<String> = refs_array
+    // Build a set of available reference IDs for validation, and an index
+    // of their DOI and PMID for `doi:`/`pmid:` keys
+    let available_ids: HashSet<&str> = refs_array
         .iter()
         .filter_map(|r| r.get("id").and_then(|id| id.as_str()))
-        .map(|s| s.to_string())
         .collect();
+    let field_index = field_index(refs_array);
 
-    // Verify all cited references exist
+    // Build citation_items JSON for csl_proc, verifying that all cited references exist
+    // Each citation gets its own cluster for individual formatting
+    let mut citation_items: Vec<Vec<serde_json::Value>> = Vec::with_capacity(citations.len());
     for citation in citations {
-        if !available_ids.contains(&citation.id) {
-            return Err(ProcessorError::ReferenceNotFound(citation.id.clone()));
-        }
+        let id = resolve_key(&citation.id, &available_ids, &field_index)?;
+        let mut json_item = citation_item_json(&CitationItem::from(citation.clone()));
+        json_item["id"] = serde_json::json!(id);
+        citation_items.push(vec![json_item]);
     }
 
-    // Build citation_items JSON for csl_proc
-    // Each citation gets its own cluster for individual formatting
-    let citation_items: Vec<Vec<serde_json::Value>> = citations
-        .iter()
-        .map(|c| {
-            let mut item = serde_json::json!({"id": c.id});
-            // Add locator if present
-            if let Some(ref locator) = c.locator {
-                item["locator"] = serde_json::json!(locator);
-            }
-            if let Some(ref label) = c.label {
-                item["label"] = serde_json::json!(label);
-            }
-            vec![item]
-        })
-        .collect();
-
     let citation_items_json = serde_json::to_string(&citation_items)
         .map_err(|e| ProcessorError::CslError(e.to_string()))?;
 
@@ -141,6 +131,32 @@ pub fn format_citations_clusters(
     clusters: &[crate::markdown::CitationCluster],
     refs_json: &str,
     style_csl: &str,
+) -> Result<Vec<ProcessedCitation>, ProcessorError> {
+    format_citations_clusters_with(clusters, refs_json, style_csl, &KeyAliases::default())
+}
+
+/// Formats citation clusters like `format_citations_clusters`, citing the
+/// reference an old key is an alias of.
+///
+/// A key is looked up as a reference id, then as an alias, then as a
+/// `doi:`/`pmid:` key; only a key that none of them resolves raises
+/// `ReferenceNotFound`.
+///
+/// # Arguments
+///
+/// * `clusters` - The citation clusters extracted from the Markdown
+/// * `refs_json` - The CSL-JSON references as a string
+/// * `style_csl` - The CSL style XML as a string
+/// * `aliases` - The key aliases (see `aliases::KeyAliases`)
+///
+/// # Returns
+///
+/// A vector of formatted citations, one per cluster.
+pub fn format_citations_clusters_with(
+    clusters: &[crate::markdown::CitationCluster],
+    refs_json: &str,
+    style_csl: &str,
+    aliases: &KeyAliases,
 ) -> Result<Vec<ProcessedCitation>, ProcessorError> {
     // Handle empty clusters case early
     if clusters.is_empty() {
@@ -156,45 +172,29 @@ pub fn format_citations_clusters(
         ProcessorError::InvalidJson("References must be a JSON array".to_string())
     })?;
 
-    // Build a set of available reference IDs for validation
-    let available_ids: HashSet<String> = refs_array
+    // Build a set of available reference IDs for validation, and an index
+    // of their DOI and PMID for `doi:`/`pmid:` keys
+    let available_ids: HashSet<&str> = refs_array
         .iter()
         .filter_map(|r| r.get("id").and_then(|id| id.as_str()))
-        .map(|s| s.to_string())
         .collect();
+    let field_index = field_index(refs_array);
 
-    // Verify all cited references exist
+    // Build citation_items JSON for csl_proc, verifying that all cited references exist
+    // Each cluster becomes an array of items (for grouping)
+    let mut citation_items: Vec<Vec<serde_json::Value>> = Vec::with_capacity(clusters.len());
     for cluster in clusters {
+        let mut items = Vec::with_capacity(cluster.items.len());
         for item in &cluster.items {
-            if !available_ids.contains(&item.id) {
-                return Err(ProcessorError::ReferenceNotFound(item.id.clone()));
-            }
+            let key = aliases.resolve(&item.id, &available_ids);
+            let id = resolve_key(key, &available_ids, &field_index)?;
+            let mut json_item = citation_item_json(item);
+            json_item["id"] = serde_json::json!(id);
+            items.push(json_item);
         }
+        citation_items.push(items);
     }
 
-    // Build citation_items JSON for csl_proc
-    // Each cluster becomes an array of items (for grouping)
-    let citation_items: Vec<Vec<serde_json::Value>> = clusters
-        .iter()
-        .map(|cluster| {
-            cluster
-                .items
-                .iter()
-                .map(|item| {
-                    let mut json_item = serde_json::json!({"id": item.id});
-                    // Add locator if present
-                    if let Some(ref locator) = item.locator {
-                        json_item["locator"] = serde_json::json!(locator);
-                    }
-                    if let Some(ref label) = item.label {
-                        json_item["label"] = serde_json::json!(label);
-                    }
-                    json_item
-                })
-                .collect()
-        })
-        .collect();
-
     let citation_items_json = serde_json::to_string(&citation_items)
         .map_err(|e| ProcessorError::CslError(e.to_string()))?;
 
@@ -226,6 +226,129 @@ pub fn format_citations_clusters(
     Ok(result)
 }
 
+/// Indexes references by their `DOI` and `PMID` fields, under the citation
+/// keys that cite them: `doi:10.1038/x` and `pmid:33024307`.
+///
+/// DOIs are case-insensitive, so their keys are lowercased; a DOI field may
+/// also be given as a `https://doi.org/` URL.
+fn field_index(refs: &[Value]) -> HashMap<String, Vec<&str>> {
+    let mut index: HashMap<String, Vec<&str>> = HashMap::new();
+    for reference in refs {
+        let Some(id) = reference.get("id").and_then(|id| id.as_str()) else {
+            continue;
+        };
+        if let Some(doi) = reference.get("DOI").and_then(|doi| doi.as_str()) {
+            let doi = strip_doi_prefix(doi.trim());
+            if !doi.is_empty() {
+                let key = format!("doi:{}", doi.to_lowercase());
+                index.entry(key).or_default().push(id);
+            }
+        }
+        let pmid = match reference.get("PMID") {
+            Some(Value::String(pmid)) => pmid.trim().to_string(),
+            Some(Value::Number(pmid)) => pmid.to_string(),
+            _ => String::new(),
+        };
+        if !pmid.is_empty() {
+            index.entry(format!("pmid:{}", pmid)).or_default().push(id);
+        }
+    }
+    index
+}
+
+/// Prefixes of DOIs written as URLs or with their scheme.
+const DOI_PREFIXES: [&str; 5] = [
+    "https://doi.org/",
+    "http://doi.org/",
+    "https://dx.doi.org/",
+    "http://dx.doi.org/",
+    "doi:",
+];
+
+/// Removes the URL or `doi:` prefix of a DOI, if any.
+fn strip_doi_prefix(doi: &str) -> &str {
+    DOI_PREFIXES
+        .iter()
+        .find_map(|prefix| doi.strip_prefix(prefix))
+        .unwrap_or(doi)
+}
+
+/// Returns the `field_index` key of a `doi:`/`pmid:` citation key.
+fn field_key(key: &str) -> Option<String> {
+    let (scheme, value) = key.split_once(':')?;
+    match scheme.to_ascii_lowercase().as_str() {
+        "doi" => Some(format!("doi:{}", strip_doi_prefix(value).to_lowercase())),
+        "pmid" => Some(format!("pmid:{}", value)),
+        _ => None,
+    }
+}
+
+/// Resolves a citation key to the id of the reference it cites.
+///
+/// A key is the id of a reference, or else a `doi:`/`pmid:` key matching the
+/// DOI or PMID of exactly one reference.
+///
+/// # Errors
+///
+/// Returns `ReferenceNotFound` if no reference matches the key, and
+/// `AmbiguousReference` if several references have its DOI or PMID.
+fn resolve_key<'a>(
+    key: &'a str,
+    available_ids: &HashSet<&str>,
+    field_index: &HashMap<String, Vec<&'a str>>,
+) -> Result<&'a str, ProcessorError> {
+    if available_ids.contains(key) {
+        return Ok(key);
+    }
+    let matches = field_key(key).and_then(|k| field_index.get(&k));
+    match matches.map(Vec::as_slice) {
+        Some([id]) => Ok(id),
+        Some(ids) if ids.len() > 1 => Err(ProcessorError::AmbiguousReference {
+            key: key.to_string(),
+            ids: ids.iter().map(|id| id.to_string()).collect(),
+        }),
+        _ => Err(ProcessorError::ReferenceNotFound(key.to_string())),
+    }
+}
+
+/// Builds the csl_proc JSON object for one citation item.
+///
+/// The locator and label are only included when present. Narrative citations
+/// are flagged with `"author-in-text": true` so that the engine renders the
+/// author as part of the sentence ("Smith (2020)"), and `[-@id]` citations
+/// with `"suppress-author": true` so that only the date remains ("(2020)").
+/// Prefix and suffix text are passed as CSL affixes, with the separating
+/// space added since the engine inserts them verbatim.
+fn citation_item_json(item: &CitationItem) -> Value {
+    let mut json_item = serde_json::json!({"id": item.id});
+    // Add locator if present
+    if let Some(ref locator) = item.locator {
+        json_item["locator"] = serde_json::json!(locator);
+    }
+    if let Some(ref label) = item.label {
+        json_item["label"] = serde_json::json!(label);
+    }
+    if item.mode == CitationMode::AuthorInText {
+        json_item["author-in-text"] = serde_json::json!(true);
+    }
+    if item.suppress_author {
+        json_item["suppress-author"] = serde_json::json!(true);
+    }
+    if let Some(ref prefix) = item.prefix {
+        json_item["prefix"] = serde_json::json!(format!("{} ", prefix));
+    }
+    if let Some(ref suffix) = item.suffix {
+        // "and passim" needs a space, ", and passim" does not
+        let suffix = if suffix.starts_with(|c: char| c.is_ascii_punctuation()) {
+            suffix.clone()
+        } else {
+            format!(" {}", suffix)
+        };
+        json_item["suffix"] = serde_json::json!(suffix);
+    }
+    json_item
+}
+
 /// Formats the bibliography for the cited references.
 ///
 /// # Arguments
@@ -256,11 +379,13 @@ pub fn format_bibliography(
         ProcessorError::InvalidJson("References must be a JSON array".to_string())
     })?;
 
-    // Build refs index for O(1) lookup
+    // Build refs index for O(1) lookup, by id and by DOI/PMID
     let refs_by_id: HashMap<&str, &Value> = all_refs
         .iter()
         .filter_map(|r| r.get("id").and_then(|id| id.as_str()).map(|id| (id, r)))
         .collect();
+    let available_ids: HashSet<&str> = refs_by_id.keys().copied().collect();
+    let field_index = field_index(all_refs);
 
     // Order refs by first appearance in text (citations is already in document order).
     // For styles WITHOUT <sort> in <bibliography>, csl_proc assigns citation-number = i+1
@@ -268,16 +393,18 @@ pub fn format_bibliography(
     // For styles WITH <sort> in <bibliography>, csl_proc re-sorts entries anyway — our
     // ordering is a sensible default that gets overridden by the style.
     //
-    // Note: missing refs are silently skipped here. In practice format_citations_clusters()
-    // runs first in main.rs and returns ReferenceNotFound, so this path is only reachable
-    // if format_bibliography() is called directly via the public API.
+    // Note: missing (or ambiguous) refs are silently skipped here. In practice
+    // format_citations_clusters() runs first in main.rs and returns ReferenceNotFound, so
+    // this path is only reachable if format_bibliography() is called directly via the
+    // public API.
     let mut seen = HashSet::new();
     let mut cited_refs: Vec<&Value> = Vec::new();
     for citation in citations {
-        if seen.insert(citation.id.as_str()) {
-            if let Some(&ref_item) = refs_by_id.get(citation.id.as_str()) {
-                cited_refs.push(ref_item);
-            }
+        let Ok(id) = resolve_key(&citation.id, &available_ids, &field_index) else {
+            continue;
+        };
+        if seen.insert(id) {
+            cited_refs.push(refs_by_id[id]);
         }
     }
 
@@ -297,6 +424,314 @@ pub fn format_bibliography(
     Ok(bibliography_output)
 }
 
+/// Returns the short author names of each reference, keyed by id: "Smith",
+/// "Smith and Jones" or "Smith et al.", from the authors or else the editors.
+///
+/// With a note style, this is the text a narrative citation (`@key`) keeps in
+/// the sentence, its citation going to a footnote (see
+/// `notes::footnote_citations`).
+///
+/// # Arguments
+///
+/// * `refs_json` - The CSL-JSON references as a string
+///
+/// # Returns
+///
+/// The names of the references that have authors or editors.
+pub fn short_author_names(refs_json: &str) -> Result<HashMap<String, String>, ProcessorError> {
+    let refs_array: Value =
+        serde_json::from_str(refs_json).map_err(|e| ProcessorError::InvalidJson(e.to_string()))?;
+    let refs_array = refs_array.as_array().ok_or_else(|| {
+        ProcessorError::InvalidJson("References must be a JSON array".to_string())
+    })?;
+
+    let mut names = HashMap::new();
+    for reference in refs_array {
+        let Some(id) = reference.get("id").and_then(|id| id.as_str()) else {
+            continue;
+        };
+        let people = ["author", "editor"]
+            .iter()
+            .filter_map(|role| reference.get(*role).and_then(|p| p.as_array()))
+            .find(|people| !people.is_empty());
+        let Some(people) = people else {
+            continue;
+        };
+        let families: Vec<String> = people
+            .iter()
+            .filter_map(|person| {
+                let family = person.get("family").and_then(|f| f.as_str());
+                let literal = person.get("literal").and_then(|l| l.as_str());
+                let particle = person.get("non-dropping-particle").and_then(|p| p.as_str());
+                match (particle, family.or(literal)) {
+                    (Some(particle), Some(family)) => Some(format!("{} {}", particle, family)),
+                    (None, Some(family)) => Some(family.to_string()),
+                    _ => None,
+                }
+            })
+            .collect();
+        let short = match families.as_slice() {
+            [] => continue,
+            [one] => one.clone(),
+            [first, second] => format!("{} and {}", first, second),
+            [first, ..] => format!("{} et al.", first),
+        };
+        names.insert(id.to_string(), short);
+    }
+    Ok(names)
+}
+
+/// Returns the ids of the references, in the order of the references file.
+///
+/// # Arguments
+///
+/// * `refs_json` - The CSL-JSON references as a string
+///
+/// # Returns
+///
+/// The id of each reference that has one.
+pub fn reference_ids(refs_json: &str) -> Result<Vec<String>, ProcessorError> {
+    let refs_array: Value =
+        serde_json::from_str(refs_json).map_err(|e| ProcessorError::InvalidJson(e.to_string()))?;
+    let refs_array = refs_array.as_array().ok_or_else(|| {
+        ProcessorError::InvalidJson("References must be a JSON array".to_string())
+    })?;
+    Ok(refs_array
+        .iter()
+        .filter_map(|r| r.get("id").and_then(|id| id.as_str()))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Replaces the citation keys that are aliases (see `aliases::KeyAliases`),
+/// or `doi:`/`pmid:` keys, by the ids of their references.
+///
+/// `format_citations_clusters_with` resolves keys the same way; running this
+/// first also gives the citations passed to `format_bibliography` their
+/// reference ids, so that a reference cited under several keys is one
+/// bibliography entry. Keys that resolve to no reference, or to several, are
+/// kept, to be reported when the clusters are formatted.
+///
+/// # Arguments
+///
+/// * `scan` - The clusters and citations of the document
+/// * `refs_json` - The CSL-JSON references as a string
+/// * `aliases` - The key aliases
+pub fn resolve_citation_keys(
+    scan: &mut CitationScan,
+    refs_json: &str,
+    aliases: &KeyAliases,
+) -> Result<(), ProcessorError> {
+    let refs_array: Value =
+        serde_json::from_str(refs_json).map_err(|e| ProcessorError::InvalidJson(e.to_string()))?;
+    let refs_array = refs_array.as_array().ok_or_else(|| {
+        ProcessorError::InvalidJson("References must be a JSON array".to_string())
+    })?;
+    let available_ids: HashSet<&str> = refs_array
+        .iter()
+        .filter_map(|r| r.get("id").and_then(|id| id.as_str()))
+        .collect();
+    let field_index = field_index(refs_array);
+
+    let canonical = |key: &str| {
+        let key = aliases.resolve(key, &available_ids);
+        resolve_key(key, &available_ids, &field_index)
+            .unwrap_or(key)
+            .to_string()
+    };
+    let items = scan.clusters.iter_mut().flat_map(|c| c.items.iter_mut());
+    for item in items {
+        item.id = canonical(&item.id);
+    }
+    for citation in &mut scan.citations {
+        citation.id = canonical(&citation.id);
+    }
+    Ok(())
+}
+
+/// Resolves `nocite` keys into citations, for references to list in the
+/// bibliography without citing them in the text.
+///
+/// The `*` wildcard (from `@*`) stands for every reference, in the order of
+/// the references file. Appending the result to the cited citations keeps
+/// the cited references first, in citation order.
+///
+/// # Arguments
+///
+/// * `keys` - The nocite keys (see `markdown::parse_nocite_keys`)
+/// * `refs_json` - The CSL-JSON references as a string
+///
+/// # Returns
+///
+/// One citation per distinct reference, in order.
+///
+/// # Errors
+///
+/// Returns `ReferenceNotFound` if a key is not in the references, and
+/// `AmbiguousReference` if a `doi:`/`pmid:` key matches several.
+pub fn resolve_nocite(keys: &[String], refs_json: &str) -> Result<Vec<Citation>, ProcessorError> {
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let refs_array: Value =
+        serde_json::from_str(refs_json).map_err(|e| ProcessorError::InvalidJson(e.to_string()))?;
+    let refs_array = refs_array.as_array().ok_or_else(|| {
+        ProcessorError::InvalidJson("References must be a JSON array".to_string())
+    })?;
+    let all_ids: Vec<&str> = refs_array
+        .iter()
+        .filter_map(|r| r.get("id").and_then(|id| id.as_str()))
+        .collect();
+    let available_ids: HashSet<&str> = all_ids.iter().copied().collect();
+    let field_index = field_index(refs_array);
+
+    let mut seen = HashSet::new();
+    let mut citations = Vec::new();
+    for key in keys {
+        let ids = if key == "*" {
+            all_ids.clone()
+        } else {
+            vec![resolve_key(key, &available_ids, &field_index)?]
+        };
+        for id in ids {
+            if seen.insert(id) {
+                citations.push(Citation {
+                    id: id.to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    Ok(citations)
+}
+
+/// Formats the bibliography of one reference section when numbering continues
+/// across sections.
+///
+/// The references are numbered as in a single bibliography of `earlier`
+/// followed by `citations`, and only those first cited in `citations` are
+/// kept, so that the entries match the continuous citation numbers. The
+/// entries are told apart by position, so the bibliography must be in citation
+/// order, as with unsorted numeric styles.
+///
+/// # Arguments
+///
+/// * `earlier` - The citations of the previous sections
+/// * `citations` - The citations of this section
+/// * `refs_json` - The CSL-JSON references as a string
+/// * `style_csl` - The CSL style XML as a string
+///
+/// # Returns
+///
+/// The formatted bibliography HTML, empty if the section cites nothing new.
+///
+/// # Errors
+///
+/// Returns `CslError` if the style sorts its bibliography (see
+/// `style::sorts_bibliography`) and earlier sections cite references, as
+/// their entries could not be told apart.
+pub fn format_bibliography_continued(
+    earlier: &[Citation],
+    citations: &[Citation],
+    refs_json: &str,
+    style_csl: &str,
+) -> Result<String, ProcessorError> {
+    if !earlier.is_empty() && crate::style::sorts_bibliography(style_csl) {
+        return Err(ProcessorError::CslError(
+            "continuous numbering needs a bibliography in citation order, but the style sorts it"
+                .to_string(),
+        ));
+    }
+
+    let all: Vec<Citation> = earlier.iter().chain(citations).cloned().collect();
+    let bibliography = format_bibliography(&all, refs_json, style_csl)?;
+
+    // Entries of the previous sections come first, one per distinct reference
+    let skipped = earlier
+        .iter()
+        .map(|c| c.id.as_str())
+        .collect::<HashSet<_>>()
+        .len();
+    if skipped == 0 {
+        return Ok(bibliography);
+    }
+
+    let entry_starts: Vec<usize> = bibliography
+        .match_indices(CSL_ENTRY)
+        .map(|(i, _)| i)
+        .collect();
+    match entry_starts.get(skipped) {
+        Some(&first_kept) => {
+            let mut section_bib = bibliography.clone();
+            section_bib.replace_range(entry_starts[0]..first_kept, "");
+            Ok(section_bib)
+        }
+        None => Ok(String::new()),
+    }
+}
+
+/// Opening of a bibliography entry in csl_proc output.
+const CSL_ENTRY: &str = r#"<div class="csl-entry""#;
+
+/// Returns the anchor of a bibliography entry, `ref-<id>` as in Pandoc.
+///
+/// Ids may hold any character, so those other than ASCII letters, digits and
+/// `-._~:` are percent-encoded: the anchor is then safe both in an HTML `id`
+/// attribute and in a Markdown link target.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::processor::reference_anchor;
+///
+/// assert_eq!(reference_anchor("smith2020"), "ref-smith2020");
+/// assert_eq!(reference_anchor("a b)\"<"), "ref-a%20b%29%22%3C");
+/// ```
+pub fn reference_anchor(id: &str) -> String {
+    let mut anchor = String::with_capacity(id.len() + 4);
+    anchor.push_str("ref-");
+    for byte in id.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~:".contains(&byte) {
+            anchor.push(byte as char);
+        } else {
+            anchor.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    anchor
+}
+
+/// Gives each bibliography entry an `id` anchor (see `reference_anchor`), as
+/// Pandoc does, so that citations can link to it.
+///
+/// The entries must be in the order of `ids`, which holds when the style does
+/// not sort its bibliography (see `style::sorts_bibliography`).
+///
+/// # Arguments
+///
+/// * `bibliography` - The bibliography HTML from `format_bibliography`
+/// * `ids` - The reference ids of the entries, in order
+///
+/// # Returns
+///
+/// The bibliography HTML with anchored entries.
+pub fn link_bibliography_entries(bibliography: &str, ids: &[&str]) -> String {
+    let mut linked = String::with_capacity(bibliography.len());
+    let mut rest = bibliography;
+    for id in ids {
+        let Some(pos) = rest.find(CSL_ENTRY) else {
+            break;
+        };
+        let entry_end = pos + CSL_ENTRY.len();
+        linked.push_str(&rest[..entry_end]);
+        linked.push_str(&format!(r#" id="{}""#, reference_anchor(id)));
+        rest = &rest[entry_end..];
+    }
+    linked.push_str(rest);
+    linked
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -338,6 +773,7 @@ mod tests {
             label: None,
             url: None,
             span: (10, 20),
+            ..Default::default()
         }];
         let refs = r#"[{"id": "item-1", "type": "book", "author": [{"family": "Doe", "given": "John"}], "title": "Test Book", "issued": {"date-parts": [[2021]]}}]"#;
 
@@ -370,6 +806,7 @@ mod tests {
                 label: None,
                 url: None,
                 span: (5, 15),
+                ..Default::default()
             },
             Citation {
                 id: "item-2".to_string(),
@@ -377,6 +814,7 @@ mod tests {
                 label: None,
                 url: None,
                 span: (30, 40),
+                ..Default::default()
             },
         ];
         let refs = r#"[
@@ -404,6 +842,7 @@ mod tests {
             label: None,
             url: None,
             span: (0, 15),
+            ..Default::default()
         }];
         let refs =
             r#"[{"id": "item-1", "type": "book", "author": [{"family": "Doe"}], "title": "Book"}]"#;
@@ -430,6 +869,7 @@ mod tests {
             label: None,
             url: None,
             span: (0, 10),
+            ..Default::default()
         }];
         let refs = r#"[{"id": "item-1", "invalid json"#;
 
@@ -469,6 +909,7 @@ mod tests {
                 label: None,
                 url: None,
                 span: (5, 15),
+                ..Default::default()
             },
             Citation {
                 id: "item-1".to_string(),
@@ -476,6 +917,7 @@ mod tests {
                 label: None,
                 url: None,
                 span: (30, 40),
+                ..Default::default()
             },
         ];
         let refs = r#"[{"id": "item-1", "type": "book", "author": [{"family": "Doe", "given": "John"}], "title": "Test Book", "issued": {"date-parts": [[2021]]}}]"#;
@@ -492,6 +934,77 @@ mod tests {
         assert!(result[1].formatted.contains("Doe"));
     }
 
+    #[test]
+    fn test_citation_item_json_forwards_mode() {
+        // Given: A narrative and a parenthetical citation item
+        let narrative = CitationItem {
+            id: "item-1".to_string(),
+            mode: CitationMode::AuthorInText,
+            ..Default::default()
+        };
+        let normal = CitationItem {
+            id: "item-2".to_string(),
+            locator: Some("4".to_string()),
+            label: Some("page".to_string()),
+            ..Default::default()
+        };
+
+        // When: We build the csl_proc JSON for each
+        let narrative_json = citation_item_json(&narrative);
+        let normal_json = citation_item_json(&normal);
+
+        // Then: Only the narrative item carries the author-in-text flag
+        assert_eq!(narrative_json["author-in-text"], serde_json::json!(true));
+        assert!(normal_json.get("author-in-text").is_none());
+        assert_eq!(normal_json["locator"], serde_json::json!("4"));
+        assert_eq!(normal_json["label"], serde_json::json!("page"));
+    }
+
+    #[test]
+    fn test_citation_item_json_forwards_suppress_author() {
+        // Given: A citation item with its author suppressed
+        let item = CitationItem {
+            id: "item-1".to_string(),
+            suppress_author: true,
+            ..Default::default()
+        };
+
+        // When: We build the csl_proc JSON
+        let json_item = citation_item_json(&item);
+
+        // Then: The suppress-author flag is set
+        assert_eq!(json_item["suppress-author"], serde_json::json!(true));
+        assert!(citation_item_json(&CitationItem::default())
+            .get("suppress-author")
+            .is_none());
+    }
+
+    #[test]
+    fn test_citation_item_json_forwards_affixes() {
+        // Given: A citation item with a prefix and a suffix
+        let item = CitationItem {
+            id: "item-1".to_string(),
+            prefix: Some("see".to_string()),
+            suffix: Some("and passim".to_string()),
+            ..Default::default()
+        };
+        let comma_suffix = CitationItem {
+            id: "item-1".to_string(),
+            suffix: Some(", emphasis added".to_string()),
+            ..Default::default()
+        };
+
+        // When: We build the csl_proc JSON
+        let json_item = citation_item_json(&item);
+        let comma_json = citation_item_json(&comma_suffix);
+
+        // Then: The affixes are spaced from the rendered citation
+        assert_eq!(json_item["prefix"], serde_json::json!("see "));
+        assert_eq!(json_item["suffix"], serde_json::json!(" and passim"));
+        assert_eq!(comma_json["suffix"], serde_json::json!(", emphasis added"));
+        assert!(comma_json.get("prefix").is_none());
+    }
+
     // ===========================================
     // Tests for format_bibliography (Phase 4.2)
     // ===========================================
@@ -505,6 +1018,7 @@ mod tests {
             label: None,
             url: None,
             span: (0, 10),
+            ..Default::default()
         }];
         let refs = r#"[{"id": "item-1", "type": "book", "author": [{"family": "Doe", "given": "John"}], "title": "Test Book", "issued": {"date-parts": [[2021]]}}]"#;
 
@@ -539,6 +1053,7 @@ mod tests {
                 label: None,
                 url: None,
                 span: (0, 10),
+                ..Default::default()
             },
             Citation {
                 id: "item-2".to_string(),
@@ -546,6 +1061,7 @@ mod tests {
                 label: None,
                 url: None,
                 span: (20, 30),
+                ..Default::default()
             },
         ];
         let refs = r#"[
@@ -573,6 +1089,7 @@ mod tests {
                 label: None,
                 url: None,
                 span: (0, 10),
+                ..Default::default()
             },
             Citation {
                 id: "item-1".to_string(),
@@ -580,6 +1097,7 @@ mod tests {
                 label: None,
                 url: None,
                 span: (20, 30),
+                ..Default::default()
             },
         ];
         let refs = r#"[{"id": "item-1", "type": "book", "author": [{"family": "Doe", "given": "John"}], "title": "Test Book", "issued": {"date-parts": [[2021]]}}]"#;
@@ -652,6 +1170,7 @@ mod tests {
                 label: None,
                 url: None,
                 span: (0, 10),
+                ..Default::default()
             },
             Citation {
                 id: "bravo".to_string(),
@@ -659,6 +1178,7 @@ mod tests {
                 label: None,
                 url: None,
                 span: (20, 30),
+                ..Default::default()
             },
         ];
 
@@ -689,6 +1209,7 @@ mod tests {
                 label: None,
                 url: None,
                 span: (0, 10),
+                ..Default::default()
             },
             Citation {
                 id: "bravo".to_string(),
@@ -696,6 +1217,7 @@ mod tests {
                 label: None,
                 url: None,
                 span: (20, 30),
+                ..Default::default()
             },
             Citation {
                 id: "alpha".to_string(),
@@ -703,6 +1225,7 @@ mod tests {
                 label: None,
                 url: None,
                 span: (40, 50),
+                ..Default::default()
             },
         ];
 
@@ -734,6 +1257,7 @@ mod tests {
             label: None,
             url: None,
             span: (0, 10),
+            ..Default::default()
         }];
         let refs = r#"[
             {"id": "item-1", "type": "book", "author": [{"family": "Doe"}], "title": "Cited Book"},
@@ -755,4 +1279,239 @@ mod tests {
             result
         );
     }
+
+    #[test]
+    fn test_format_bibliography_continued_keeps_new_references() {
+        // Given: A first section citing item-1 and a second citing item-1 and item-2
+        let refs = r#"[
+            {"id": "item-1", "type": "book", "author": [{"family": "Alpha"}], "title": "First"},
+            {"id": "item-2", "type": "book", "author": [{"family": "Bravo"}], "title": "Second"}
+        ]"#;
+        let earlier = vec![Citation {
+            id: "item-1".to_string(),
+            span: (0, 10),
+            ..Default::default()
+        }];
+        let section = vec![
+            Citation {
+                id: "item-1".to_string(),
+                span: (20, 30),
+                ..Default::default()
+            },
+            Citation {
+                id: "item-2".to_string(),
+                span: (40, 50),
+                ..Default::default()
+            },
+        ];
+
+        // When: We format the second section's bibliography with continuous numbering
+        let result =
+            format_bibliography_continued(&earlier, &section, refs, MINIMAL_STYLE).unwrap();
+
+        // Then: Only the reference first cited in this section is listed
+        assert_eq!(result.matches("csl-entry").count(), 1);
+        assert!(result.contains("Second"));
+        assert!(!result.contains("First"));
+    }
+
+    #[test]
+    fn test_format_bibliography_continued_rejects_sorted_style() {
+        // Given: A style sorting its bibliography, and an earlier section
+        let refs = r#"[{"id": "item-1", "author": [{"family": "Alpha"}]}]"#;
+        let sorted_style = MINIMAL_STYLE.replace(
+            "<bibliography>",
+            r#"<bibliography><sort><key variable="author"/></sort>"#,
+        );
+        let earlier = vec![Citation {
+            id: "item-1".to_string(),
+            ..Default::default()
+        }];
+
+        // When: We format a later section's bibliography
+        let result = format_bibliography_continued(&earlier, &earlier, refs, &sorted_style);
+
+        // Then: The entries of earlier sections cannot be dropped by position
+        assert!(matches!(result, Err(ProcessorError::CslError(_))));
+    }
+
+    #[test]
+    fn test_link_bibliography_entries() {
+        // Given: A bibliography with two entries
+        let bibliography = r#"<div class="csl-bib-body">
+  <div class="csl-entry">A</div>
+  <div class="csl-entry">B</div>
+</div>"#;
+
+        // When: We anchor them
+        let linked = link_bibliography_entries(bibliography, &["a", "b"]);
+
+        // Then: Each entry gets the id of its reference
+        assert_eq!(
+            linked,
+            r#"<div class="csl-bib-body">
+  <div class="csl-entry" id="ref-a">A</div>
+  <div class="csl-entry" id="ref-b">B</div>
+</div>"#
+        );
+    }
+
+    #[test]
+    fn test_link_bibliography_entries_encodes_ids() {
+        // Given: An entry whose id holds a space, a quote and a parenthesis
+        let bibliography = r#"<div class="csl-entry">A</div>"#;
+
+        // When: We anchor it
+        let linked = link_bibliography_entries(bibliography, &[r#"doe "2020")"#]);
+
+        // Then: The id is percent-encoded and cannot break the attribute
+        assert_eq!(
+            linked,
+            r#"<div class="csl-entry" id="ref-doe%20%222020%22%29">A</div>"#
+        );
+    }
+
+    #[test]
+    fn test_resolve_nocite_keys_and_wildcard() {
+        // Given: Three references
+        let refs = r#"[{"id": "a"}, {"id": "b"}, {"id": "c"}]"#;
+
+        // When: We resolve a key followed by the wildcard
+        let keys = vec!["b".to_string(), "*".to_string()];
+        let citations = resolve_nocite(&keys, refs).unwrap();
+
+        // Then: Every reference is listed once, the explicit key first
+        let ids: Vec<&str> = citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_resolve_nocite_unknown_key() {
+        // Given: A key missing from the references
+        let refs = r#"[{"id": "a"}]"#;
+
+        // When: We resolve it
+        let result = resolve_nocite(&["missing".to_string()], refs);
+
+        // Then: The key is reported
+        assert!(matches!(
+            result,
+            Err(ProcessorError::ReferenceNotFound(ref id)) if id == "missing"
+        ));
+    }
+
+    #[test]
+    fn test_resolve_citation_keys_dedupes_bibliography() {
+        // Given: A reference cited under its id and under an old key
+        let refs = r#"[{"id": "smith2020", "author": [{"family": "Smith"}], "title": "Trial", "issued": {"date-parts": [[2020]]}}]"#;
+        let aliases = KeyAliases::parse_json(r#"{"pmid:123": "smith2020"}"#).unwrap();
+        let mut scan = crate::markdown::scan_citations(
+            "See [@pmid:123] and [@smith2020; @pmid:999].",
+            crate::markdown::GroupingPolicy::default(),
+        );
+
+        // When: We resolve the aliases
+        resolve_citation_keys(&mut scan, refs, &aliases).unwrap();
+
+        // Then: The old key cites the reference, unknown keys are kept
+        let ids: Vec<&str> = scan.citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["smith2020", "smith2020", "pmid:999"]);
+        assert_eq!(scan.clusters[0].items[0].id, "smith2020");
+
+        // And: The reference is listed once in the bibliography
+        let bib = format_bibliography(&scan.citations[..2], refs, MINIMAL_STYLE).unwrap();
+        assert_eq!(bib.matches(CSL_ENTRY).count(), 1);
+    }
+
+    #[test]
+    fn test_resolve_citation_keys_by_doi_and_pmid() {
+        // Given: References with arbitrary ids, and DOI and PMID fields
+        let refs = r#"[
+            {"id": "item-1", "DOI": "10.1038/S41579-020-00459-7", "PMID": "33024307"},
+            {"id": "item-2", "DOI": "https://doi.org/10.1000/xyz", "PMID": 123}
+        ]"#;
+        let mut scan = crate::markdown::scan_citations(
+            "[@doi:10.1038/s41579-020-00459-7], [@pmid:123], [@doi:10.1000/XYZ] and [@pmid:33024307].",
+            crate::markdown::GroupingPolicy::default(),
+        );
+
+        // When: We resolve the keys
+        resolve_citation_keys(&mut scan, refs, &KeyAliases::default()).unwrap();
+
+        // Then: Each key cites the reference with that DOI or PMID
+        let ids: Vec<&str> = scan.citations.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["item-1", "item-2", "item-2", "item-1"]);
+    }
+
+    #[test]
+    fn test_literal_id_wins_over_doi_field() {
+        // Given: A reference whose id is a DOI key, and another with that DOI
+        let refs = r#"[{"id": "doi:10.1/x"}, {"id": "other", "DOI": "10.1/x"}]"#;
+
+        // When: We resolve the key
+        let citations = resolve_nocite(&["doi:10.1/x".to_string()], refs).unwrap();
+
+        // Then: The reference with that literal id is cited
+        assert_eq!(citations[0].id, "doi:10.1/x");
+    }
+
+    #[test]
+    fn test_format_citations_clusters_ambiguous_doi() {
+        // Given: Two references with the same DOI, cited by DOI
+        let refs = r#"[{"id": "a", "DOI": "10.1/x"}, {"id": "b", "DOI": "10.1/X"}]"#;
+        let clusters = crate::markdown::extract_citation_clusters("See [@doi:10.1/x].");
+
+        // When: We format the citation
+        let result = format_citations_clusters(&clusters, refs, MINIMAL_STYLE);
+
+        // Then: Both matching references are reported
+        match result {
+            Err(ProcessorError::AmbiguousReference { key, ids }) => {
+                assert_eq!(key, "doi:10.1/x");
+                assert_eq!(ids, vec!["a", "b"]);
+            }
+            other => panic!("Expected AmbiguousReference, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_format_citations_clusters_with_aliases() {
+        // Given: A cluster citing a reference under an old key
+        let refs = r#"[{"id": "smith2020", "title": "Trial"}]"#;
+        let aliases = KeyAliases::parse_json(r#"{"pmid:123": "smith2020"}"#).unwrap();
+        let clusters = crate::markdown::extract_citation_clusters("See [@pmid:123].");
+
+        // When: We format it with and without the aliases
+        let with_aliases = format_citations_clusters_with(&clusters, refs, MINIMAL_STYLE, &aliases);
+        let without = format_citations_clusters(&clusters, refs, MINIMAL_STYLE);
+
+        // Then: Only the aliases resolve the old key
+        assert_eq!(with_aliases.unwrap().len(), 1);
+        assert!(matches!(
+            without,
+            Err(ProcessorError::ReferenceNotFound(key)) if key == "pmid:123"
+        ));
+    }
+
+    #[test]
+    fn test_short_author_names() {
+        // Given: References with one, two and three authors, and an edited book
+        let refs = r#"[
+            {"id": "one", "author": [{"family": "Smith", "given": "Jane"}]},
+            {"id": "two", "author": [{"family": "Smith"}, {"family": "Berg", "non-dropping-particle": "van den"}]},
+            {"id": "three", "author": [{"family": "Doe"}, {"family": "Roe"}, {"family": "Poe"}]},
+            {"id": "edited", "editor": [{"literal": "WHO"}]},
+            {"id": "anonymous", "title": "Untitled"}
+        ]"#;
+
+        // When: We get their short author names
+        let names = short_author_names(refs).unwrap();
+
+        // Then: Each reference with people gets its in-text name
+        assert_eq!(names["one"], "Smith");
+        assert_eq!(names["two"], "Smith and van den Berg");
+        assert_eq!(names["three"], "Doe et al.");
+        assert_eq!(names["edited"], "WHO");
+        assert!(!names.contains_key("anonymous"));
+    }
 }
@@ -3,6 +3,7 @@
 //! Handles loading references from JSON files, supporting both
 //! standard JSON arrays and JSONL format (one JSON object per line).
 
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use thiserror::Error;
@@ -41,6 +42,44 @@ pub fn load_refs(path: &Path) -> Result<String, RefsError> {
     normalize_refs(&content)
 }
 
+/// Merges several reference arrays, as returned by `load_refs`, into one.
+///
+/// When several references share an id, the last one wins: a file given
+/// later overrides the entries of the files before it.
+///
+/// # Arguments
+///
+/// * `refs_jsons` - JSON strings, each containing an array of references
+///
+/// # Returns
+///
+/// A JSON string containing the references of every array, in order, each id
+/// kept once.
+pub fn merge_refs(refs_jsons: &[String]) -> Result<String, RefsError> {
+    let mut refs: Vec<serde_json::Value> = Vec::new();
+    for json in refs_jsons {
+        match serde_json::from_str(json)? {
+            serde_json::Value::Array(items) => refs.extend(items),
+            _ => return Err(RefsError::NotAnArray),
+        }
+    }
+    let last_by_id: HashMap<String, usize> = refs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| Some((item.get("id")?.to_string(), i)))
+        .collect();
+    let refs: Vec<serde_json::Value> = refs
+        .into_iter()
+        .enumerate()
+        .filter(|(i, item)| match item.get("id") {
+            Some(id) => last_by_id[&id.to_string()] == *i,
+            None => true,
+        })
+        .map(|(_, item)| item)
+        .collect();
+    Ok(serde_json::to_string(&refs)?)
+}
+
 /// Validates that the given JSON string contains valid CSL-JSON references.
 pub fn validate_refs(json: &str) -> Result<(), RefsError> {
     let value: serde_json::Value = serde_json::from_str(json)?;
@@ -310,4 +349,47 @@ invalid json here
         // Should fail because refs must be an array
         assert!(validate_refs(json).is_err());
     }
+
+    // --- Tests for merge_refs ---
+
+    #[test]
+    fn test_merge_refs_concatenates_arrays() {
+        // Given: Two reference arrays
+        let refs = vec![
+            r#"[{"id": "a"}]"#.to_string(),
+            r#"[{"id": "b"}, {"id": "c"}]"#.to_string(),
+        ];
+
+        // When: We merge them
+        let merged = merge_refs(&refs).unwrap();
+
+        // Then: Every reference is kept, in order
+        let parsed: serde_json::Value = serde_json::from_str(&merged).unwrap();
+        let ids: Vec<&str> = parsed
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_merge_refs_last_duplicate_wins() {
+        // Given: Two files defining the reference "b"
+        let refs = vec![
+            r#"[{"id": "a"}, {"id": "b", "title": "Old"}]"#.to_string(),
+            r#"[{"id": "b", "title": "New"}, {"id": "c"}]"#.to_string(),
+        ];
+
+        // When: We merge them
+        let merged = merge_refs(&refs).unwrap();
+
+        // Then: "b" is kept once, from the last file
+        let parsed: serde_json::Value = serde_json::from_str(&merged).unwrap();
+        let refs = parsed.as_array().unwrap();
+        let ids: Vec<&str> = refs.iter().map(|r| r["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+        assert_eq!(refs[1]["title"].as_str(), Some("New"));
+    }
 }
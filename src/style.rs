@@ -12,6 +12,8 @@ pub enum StyleError {
     #[error("Failed to read file: {0}")]
     IoError(#[from] std::io::Error),
 
+    #[error("Invalid locale '{0}': expected a language tag such as fr-FR")]
+    InvalidLocale(String),
 }
 
 /// Loads a CSL style from a file.
@@ -64,6 +66,110 @@ pub fn builtin_style_names() -> Vec<&'static str> {
     BUILTIN_STYLES.iter().map(|(n, _)| *n).collect()
 }
 
+/// Returns true if the CSL style is a note style (`<style class="note">`).
+///
+/// Citations of note styles (e.g., Chicago notes, OSCOLA) are rendered as
+/// footnotes rather than inline.
+///
+/// # Arguments
+///
+/// * `style_csl` - The CSL style XML as a string
+pub fn is_note_style(style_csl: &str) -> bool {
+    let Some(start) = style_csl.find("<style") else {
+        return false;
+    };
+    let tag = &style_csl[start..];
+    let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+    tag.contains(r#"class="note""#) || tag.contains("class='note'")
+}
+
+/// Returns true if the CSL style sorts its bibliography (`<sort>` inside
+/// `<bibliography>`).
+///
+/// Without a sort, entries come in the order references are first cited.
+///
+/// # Arguments
+///
+/// * `style_csl` - The CSL style XML as a string
+pub fn sorts_bibliography(style_csl: &str) -> bool {
+    let Some(start) = style_csl.find("<bibliography") else {
+        return false;
+    };
+    let bibliography = &style_csl[start..];
+    let end = bibliography
+        .find("</bibliography>")
+        .unwrap_or(bibliography.len());
+    bibliography[..end].contains("<sort")
+}
+
+/// Sets the locale of a CSL style (the `default-locale` attribute of
+/// `<style>`), replacing the one it declares, if any.
+///
+/// # Arguments
+///
+/// * `style_csl` - The CSL style XML as a string
+/// * `lang` - The locale, e.g. "fr-FR"
+///
+/// # Returns
+///
+/// The style XML with the new locale.
+///
+/// # Errors
+///
+/// Returns `InvalidLocale` if `lang` is not a language tag (see
+/// `is_language_tag`), which also keeps it from breaking the XML.
+pub fn with_default_locale(style_csl: &str, lang: &str) -> Result<String, StyleError> {
+    if !is_language_tag(lang) {
+        return Err(StyleError::InvalidLocale(lang.to_string()));
+    }
+    let Some(start) = style_csl.find("<style") else {
+        return Ok(style_csl.to_string());
+    };
+    let tag_end = style_csl[start..]
+        .find('>')
+        .map_or(style_csl.len(), |end| start + end);
+    let attribute = format!(r#"default-locale="{}""#, lang);
+
+    let tag = &style_csl[start..tag_end];
+    let mut style = style_csl.to_string();
+    match tag.find("default-locale=") {
+        Some(pos) => {
+            let value_start = start + pos + "default-locale=".len();
+            let quote = &style_csl[value_start..value_start + 1];
+            let value_end = style_csl[value_start + 1..tag_end]
+                .find(quote)
+                .map_or(tag_end, |end| value_start + 1 + end + 1);
+            style.replace_range(start + pos..value_end, &attribute);
+        }
+        None => style.insert_str(start + "<style".len(), &format!(" {}", attribute)),
+    }
+    Ok(style)
+}
+
+/// Returns true if `lang` has the shape of a BCP 47 language tag, as CSL
+/// locales do: a language of 2 to 8 letters, then subtags of 1 to 8 letters
+/// or digits, separated by hyphens ("fr", "en-US", "zh-Hant-TW").
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::style::is_language_tag;
+///
+/// assert!(is_language_tag("fr-FR"));
+/// assert!(!is_language_tag("fr\"><evil/>"));
+/// ```
+pub fn is_language_tag(lang: &str) -> bool {
+    let is_subtag = |subtag: &str| {
+        (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+    };
+    let mut subtags = lang.split('-');
+    let language = subtags.next().unwrap_or_default();
+    language.len() >= 2
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && is_subtag(language)
+        && subtags.all(is_subtag)
+}
+
 /// Minimal CSL style for testing purposes.
 const MINIMAL_STYLE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
 <style xmlns="http://purl.org/net/xbiblio/csl" class="in-text" version="1.0">
@@ -201,6 +307,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_is_note_style() {
+        // Given: A note style and the builtin in-text styles
+        let note = r#"<?xml version="1.0"?>
+<style xmlns="http://purl.org/net/xbiblio/csl" class="note" version="1.0">"#;
+
+        // Then: Only the note style is detected
+        assert!(is_note_style(note));
+        assert!(!is_note_style(builtin_style("minimal").unwrap()));
+        assert!(!is_note_style(builtin_style("vancouver").unwrap()));
+    }
+
+    #[test]
+    fn test_sorts_bibliography() {
+        // Given: A style sorting its bibliography and one that does not
+        let sorted = r#"<style><citation><layout/></citation>
+<bibliography><sort><key variable="author"/></sort><layout/></bibliography></style>"#;
+        let unsorted = r#"<style><citation><sort><key variable="issued"/></sort></citation>
+<bibliography><layout/></bibliography></style>"#;
+
+        // Then: Only a sort inside <bibliography> counts
+        assert!(sorts_bibliography(sorted));
+        assert!(!sorts_bibliography(unsorted));
+    }
+
+    #[test]
+    fn test_with_default_locale() {
+        // Given: Styles with and without a default locale
+        let without =
+            r#"<style xmlns="http://purl.org/net/xbiblio/csl" version="1.0"><info/></style>"#;
+        let with = r#"<style default-locale="en-US" version="1.0"><info/></style>"#;
+
+        // When: We set the locale to French
+        let added = with_default_locale(without, "fr-FR").unwrap();
+        let replaced = with_default_locale(with, "fr-FR").unwrap();
+
+        // Then: The attribute is added or replaced
+        assert_eq!(
+            added,
+            r#"<style default-locale="fr-FR" xmlns="http://purl.org/net/xbiblio/csl" version="1.0"><info/></style>"#
+        );
+        assert_eq!(
+            replaced,
+            r#"<style default-locale="fr-FR" version="1.0"><info/></style>"#
+        );
+    }
+
+    #[test]
+    fn test_with_default_locale_rejects_invalid_tags() {
+        // Given: Locales that are not language tags, one of them breaking the XML
+        let style = r#"<style version="1.0"><info/></style>"#;
+
+        // When/Then: They are rejected, valid tags are accepted
+        for lang in [r#"fr"><x a=""#, "fr FR", "", "f", "en-", "toolongtag"] {
+            assert!(
+                matches!(
+                    with_default_locale(style, lang),
+                    Err(StyleError::InvalidLocale(_))
+                ),
+                "{:?} should be rejected",
+                lang
+            );
+        }
+        for lang in ["fr", "en-US", "zh-Hant-TW", "de-1996"] {
+            assert!(is_language_tag(lang), "{:?} should be accepted", lang);
+        }
+    }
+
     // ============================================
     // Tests for builtin_style_names() sync
     // ============================================
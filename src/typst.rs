@@ -0,0 +1,480 @@
+//! Typst input.
+//!
+//! Recognises Typst citations, so that a Typst document can be formatted
+//! with a CSL style and a CSL-JSON library:
+//!
+//! ```text
+//! @key  @key[p. 7]  #cite(<key>)  #cite(<key>, supplement: [p. 7], form: "prose")
+//! ```
+//!
+//! In Typst, `@name` is also a reference to a label (`@fig:x`, `@intro`):
+//! only names that are references of the bibliography are citations, the
+//! other ones are left alone. As Typst does, citations separated by spaces
+//! only are grouped into one cluster.
+//!
+//! The bibliography replaces the `#bibliography(...)` call. Raw text and
+//! comments are left untouched.
+
+use crate::markdown::{
+    parse_locator, Citation, CitationCluster, CitationItem, CitationMode, CitationScan, LineIndex,
+};
+use crate::output::{bibliography_entries, convert_html};
+
+/// Finds the citations of a Typst document.
+///
+/// # Arguments
+///
+/// * `typst` - The Typst source
+/// * `is_reference` - Whether a name is a reference of the bibliography;
+///   `@name` references to anything else are labels
+///
+/// # Returns
+///
+/// The citation clusters, and one citation per item, in document order.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::typst::scan_typst_citations;
+///
+/// let typst = "See @smith2020[p. 7] and @fig:plot, or #cite(<doe>, form: \"prose\").";
+/// let scan = scan_typst_citations(typst, |name| name != "fig:plot");
+/// assert_eq!(scan.clusters.len(), 2);
+/// assert_eq!(scan.clusters[0].items[0].locator.as_deref(), Some("7"));
+/// ```
+pub fn scan_typst_citations(typst: &str, is_reference: impl Fn(&str) -> bool) -> CitationScan {
+    let prose = mask_non_prose(typst);
+    let bytes = prose.as_bytes();
+    let mut index = LineIndex::new(typst);
+    let mut scan = CitationScan::default();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let found = match bytes[i] {
+            // An escaped character, such as `\@`
+            b'\\' => {
+                i += 1 + prose[i + 1..].chars().next().map_or(0, char::len_utf8);
+                continue;
+            }
+            b'@' if starts_word(&prose[..i]) => {
+                parse_reference(&prose, i).filter(|(item, _)| is_reference(&item.id))
+            }
+            b'#' if prose[i..].starts_with("#cite(") => parse_cite_call(&prose, i),
+            _ => None,
+        };
+        let Some((mut item, end)) = found else {
+            i += 1;
+            continue;
+        };
+
+        item.position = index.position((i, end));
+        scan.citations.push(Citation {
+            id: item.id.clone(),
+            locator: item.locator.clone(),
+            label: item.label.clone(),
+            mode: item.mode,
+            suppress_author: item.suppress_author,
+            suffix: item.suffix.clone(),
+            span: (i, end),
+            position: item.position,
+            ..Default::default()
+        });
+
+        // Citations separated by spaces only form one cluster
+        match scan.clusters.last_mut() {
+            Some(cluster)
+                if prose[cluster.span.1..i]
+                    .chars()
+                    .all(|c| c == ' ' || c == '\t')
+                    && cluster.items.iter().all(|c| c.mode == CitationMode::Normal)
+                    && item.mode == CitationMode::Normal =>
+            {
+                cluster.items.push(item);
+                cluster.span.1 = end;
+            }
+            _ => scan.clusters.push(CitationCluster {
+                items: vec![item],
+                span: (i, end),
+            }),
+        }
+        i = end;
+    }
+
+    scan
+}
+
+/// Returns true if an `@` after `before` starts a word (not an e-mail address).
+fn starts_word(before: &str) -> bool {
+    !before
+        .chars()
+        .next_back()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Parses `@name` and its optional `[supplement]` at `start`.
+fn parse_reference(prose: &str, start: usize) -> Option<(CitationItem, usize)> {
+    // Label names: letters, digits, `_`, `-`, `.` and `:`, not ending in `.` or `:`
+    let rest = &prose[start + 1..];
+    let name_len = rest
+        .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':')))
+        .unwrap_or(rest.len());
+    let name = rest[..name_len].trim_end_matches(['.', ':']);
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut end = start + 1 + name.len();
+    let mut item = CitationItem {
+        id: name.to_string(),
+        ..Default::default()
+    };
+    if prose[end..].starts_with('[') {
+        let (supplement, supplement_end) = content_block(prose, end)?;
+        set_supplement(&mut item, supplement);
+        end = supplement_end;
+    }
+    Some((item, end))
+}
+
+/// Parses a `#cite(<key>, ...)` call at `start`, with its `supplement` and
+/// `form` arguments.
+fn parse_cite_call(prose: &str, start: usize) -> Option<(CitationItem, usize)> {
+    let args_start = start + "#cite(".len();
+    let rest = prose[args_start..].trim_start();
+    let key = rest.strip_prefix('<')?;
+    let key = &key[..key.find('>')?];
+    let mut pos = prose.len() - rest.len() + 1 + key.len() + 1;
+
+    let mut item = CitationItem {
+        id: key.to_string(),
+        ..Default::default()
+    };
+
+    // Named arguments, up to the closing parenthesis
+    loop {
+        let rest = prose[pos..].trim_start();
+        pos = prose.len() - rest.len();
+        if rest.starts_with(')') {
+            return Some((item, pos + 1));
+        }
+        let rest = rest.strip_prefix(',')?.trim_start();
+        pos = prose.len() - rest.len();
+        if rest.starts_with(')') {
+            return Some((item, pos + 1));
+        }
+
+        let colon = rest.find(':')?;
+        let name = rest[..colon].trim();
+        let value = rest[colon + 1..].trim_start();
+        let value_start = prose.len() - value.len();
+        let value_end = match value.as_bytes().first()? {
+            b'[' => content_block(prose, value_start)?.1,
+            b'"' => value_start + 1 + value[1..].find('"')? + 1,
+            _ => value_start + value.find([',', ')'])?,
+        };
+        let value = prose[value_start..value_end].trim();
+
+        let content = value.strip_prefix('[').and_then(|v| v.strip_suffix(']'));
+        match (name, value, content) {
+            ("supplement", _, Some(supplement)) => set_supplement(&mut item, supplement),
+            ("form", "\"prose\"", _) => item.mode = CitationMode::AuthorInText,
+            ("form", "\"year\"", _) => item.suppress_author = true,
+            _ => {}
+        }
+        pos = value_end;
+    }
+}
+
+/// Reads the content block (`[...]`, with nested brackets) at `start`,
+/// returning its inner text and end position.
+fn content_block(prose: &str, start: usize) -> Option<(&str, usize)> {
+    let mut depth = 0usize;
+    for (i, b) in prose.bytes().enumerate().skip(start) {
+        match b {
+            b'[' => depth += 1,
+            b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((&prose[start + 1..i], i + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Sets a supplement: the locator if it reads as one (`p. 7`), otherwise the
+/// suffix.
+fn set_supplement(item: &mut CitationItem, supplement: &str) {
+    let supplement = supplement.trim();
+    if supplement.is_empty() {
+        return;
+    }
+    (item.locator, item.label, item.suffix) = match parse_locator(supplement) {
+        (None, _, _) => (None, None, Some(supplement.to_string())),
+        parsed => parsed,
+    };
+}
+
+/// Finds the `#bibliography(...)` call, where the bibliography goes.
+///
+/// # Arguments
+///
+/// * `typst` - The Typst source
+///
+/// # Returns
+///
+/// The span of the first call outside raw text and comments, if any.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::typst::find_bibliography_call;
+///
+/// let typst = "Text.\n#bibliography(\"refs.bib\", title: none)\n";
+/// assert_eq!(find_bibliography_call(typst), Some((6, 44)));
+/// ```
+pub fn find_bibliography_call(typst: &str) -> Option<(usize, usize)> {
+    let prose = mask_non_prose(typst);
+    let start = prose.find("#bibliography(")?;
+
+    // The call ends at the matching parenthesis, outside strings
+    let mut depth = 0usize;
+    let mut in_string = false;
+    for (i, b) in prose.bytes().enumerate().skip(start) {
+        match b {
+            b'"' => in_string = !in_string,
+            b'(' if !in_string => depth += 1,
+            b')' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((start, i + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Converts a bibliography rendered by csl_proc (HTML) to Typst: one
+/// paragraph per entry.
+///
+/// # Arguments
+///
+/// * `html` - The formatted bibliography
+///
+/// # Returns
+///
+/// The entries as Typst paragraphs.
+pub fn typst_bibliography(html: &str) -> String {
+    bibliography_entries(html)
+        .into_iter()
+        .map(|entry| {
+            let text = html_to_typst(entry)
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            // "1. Smith" would start a numbered list
+            let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if digits > 0 && text[digits..].starts_with('.') {
+                format!("{}\\{}", &text[..digits], &text[digits..])
+            } else {
+                text
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Converts formatted text (csl_proc HTML) to Typst markup.
+///
+/// Italic, bold, small caps, superscripts and subscripts become the matching
+/// Typst markup; other tags are dropped. Entities are decoded and Typst
+/// special characters escaped.
+///
+/// # Arguments
+///
+/// * `html` - The formatted text
+///
+/// # Returns
+///
+/// The same text in Typst markup.
+///
+/// # Example
+///
+/// ```
+/// use csl_tools::typst::html_to_typst;
+///
+/// assert_eq!(html_to_typst("<i>Nature</i> #5 &amp; more"), r"_Nature_ \#5 & more");
+/// ```
+pub fn html_to_typst(html: &str) -> String {
+    let markup = |tag: &str| match tag.split_whitespace().next().unwrap_or("") {
+        "i" | "em" => ("_", "_"),
+        "b" | "strong" => ("*", "*"),
+        "sup" => ("#super[", "]"),
+        "sub" => ("#sub[", "]"),
+        "span" if tag.contains("small-caps") => ("#smallcaps[", "]"),
+        "div" => ("", " "),
+        _ => ("", ""),
+    };
+    convert_html(html, markup, |c, typst| match c {
+        '\\' | '*' | '_' | '#' | '@' | '$' | '<' | '>' | '[' | ']' | '`' | '~' => {
+            typst.push('\\');
+            typst.push(c);
+        }
+        '\u{a0}' => typst.push('~'),
+        _ => typst.push(c),
+    })
+}
+
+/// Generates the output Typst document.
+///
+/// The bibliography, with `heading` above it, replaces the
+/// `#bibliography(...)` call; without one it is appended at the end.
+///
+/// # Arguments
+///
+/// * `content` - The Typst source with citations already replaced
+/// * `bibliography` - The bibliography, in Typst markup (if any)
+/// * `heading` - The heading of the bibliography, e.g. `= References`
+///
+/// # Returns
+///
+/// The complete output document.
+pub fn generate_typst_output(content: &str, bibliography: Option<&str>, heading: &str) -> String {
+    let Some(bib) = bibliography.filter(|bib| !bib.is_empty()) else {
+        return content.to_string();
+    };
+    let block = format!("{}\n\n{}", heading, bib);
+
+    if let Some((start, end)) = find_bibliography_call(content) {
+        let mut output = content.to_string();
+        output.replace_range(start..end, &block);
+        return output;
+    }
+    format!("{}\n\n{}\n", content.trim_end(), block)
+}
+
+/// Blanks out raw text (`` `code` ``, ```` ``` ```` blocks), comments and
+/// links, keeping byte offsets.
+fn mask_non_prose(typst: &str) -> String {
+    let bytes = typst.as_bytes();
+    let mut ranges = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let rest = &typst[i..];
+        let end = if let Some(escaped) = rest.strip_prefix('\\') {
+            i += 1 + escaped.chars().next().map_or(0, char::len_utf8);
+            continue;
+        } else if rest.starts_with('`') {
+            // Raw text ends with a run of as many backticks
+            let run = rest.len() - rest.trim_start_matches('`').len();
+            let fence = &rest[..run];
+            match rest[run..].find(fence) {
+                Some(close) => i + run + close + run,
+                None => typst.len(),
+            }
+        } else if rest.starts_with("http://") || rest.starts_with("https://") {
+            // A link, which may hold `@` or `//`
+            i + rest
+                .find(|c: char| c.is_whitespace() || matches!(c, ')' | ']' | '>'))
+                .unwrap_or(rest.len())
+        } else if rest.starts_with("//") {
+            i + rest.find('\n').unwrap_or(rest.len())
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            comment
+                .find("*/")
+                .map_or(typst.len(), |close| i + 2 + close + 2)
+        } else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+        ranges.push((i, end));
+        i = end;
+    }
+
+    let mut masked = bytes.to_vec();
+    for (start, end) in ranges {
+        for byte in &mut masked[start..end] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+    }
+
+    // Ranges start and end on ASCII delimiters, so whole characters are
+    // replaced and the buffer stays valid UTF-8
+    String::from_utf8(masked).expect("masking whole characters keeps valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references_and_labels() {
+        // Given: Citations, a figure label reference and an e-mail address
+        let typst = "As shown @a @b[p. 7], see @fig:plot and me@a.org, or @a.";
+
+        // When: We scan the document, "a" and "b" being references
+        let scan = scan_typst_citations(typst, |name| name == "a" || name == "b");
+
+        // Then: Adjacent citations are grouped, labels and e-mails left alone
+        assert_eq!(scan.clusters.len(), 2);
+        assert_eq!(scan.clusters[0].items.len(), 2);
+        assert_eq!(
+            &typst[scan.clusters[0].span.0..scan.clusters[0].span.1],
+            "@a @b[p. 7]"
+        );
+        assert_eq!(scan.clusters[0].items[1].label.as_deref(), Some("page"));
+        assert_eq!(
+            &typst[scan.clusters[1].span.0..scan.clusters[1].span.1],
+            "@a"
+        );
+    }
+
+    #[test]
+    fn test_cite_call() {
+        // Given: #cite calls with a supplement and forms
+        let typst = r#"#cite(<a>, supplement: [chap. 2]) #cite(<b>, form: "prose") #cite(<c>, form: "year")"#;
+
+        // When: We scan the document
+        let scan = scan_typst_citations(typst, |_| false);
+
+        // Then: Arguments set the locator and the mode
+        assert_eq!(scan.citations.len(), 3);
+        assert_eq!(scan.citations[0].locator.as_deref(), Some("2"));
+        assert_eq!(scan.citations[0].label.as_deref(), Some("chapter"));
+        assert_eq!(scan.citations[1].mode, CitationMode::AuthorInText);
+        assert!(scan.citations[2].suppress_author);
+        assert_eq!(scan.citations[2].span.1, typst.len());
+    }
+
+    #[test]
+    fn test_raw_text_and_comments_ignored() {
+        // Given: Citations in raw text, comments and after an escape
+        let typst = "`@a` ```\n@a\n``` // @a\n/* @a */ \\@a https://x.org/@a @a";
+
+        // When: We scan the document
+        let scan = scan_typst_citations(typst, |name| name == "a");
+
+        // Then: Only the last citation is found
+        assert_eq!(scan.citations.len(), 1);
+        assert_eq!(scan.citations[0].span.1, typst.len());
+    }
+
+    #[test]
+    fn test_generate_typst_output() {
+        // Given: A document with a #bibliography call
+        let typst = "Text.\n\n#bibliography(\"refs.bib\")\n";
+
+        // When: We add a bibliography
+        let output = generate_typst_output(typst, Some("Entry."), "= References");
+
+        // Then: It replaces the call
+        assert_eq!(output, "Text.\n\n= References\n\nEntry.\n");
+    }
+}
@@ -19,6 +19,7 @@ fn vancouver_bibliography(refs_json: &str, citation_ids: &[&str]) -> String {
             label: None,
             url: None,
             span: (i * 20, i * 20 + 10),
+            ..Default::default()
         })
         .collect();
     format_bibliography(&citations, refs_json, style).unwrap()
@@ -134,6 +135,7 @@ fn test_bibliography_order_matches_citation_appearance() {
             label: None,
             url: None,
             span: (0, 10),
+            ..Default::default()
         },
         Citation {
             id: "bravo".to_string(),
@@ -141,6 +143,7 @@ fn test_bibliography_order_matches_citation_appearance() {
             label: None,
             url: None,
             span: (20, 30),
+            ..Default::default()
         },
         Citation {
             id: "charlie".to_string(),
@@ -148,6 +151,7 @@ fn test_bibliography_order_matches_citation_appearance() {
             label: None,
             url: None,
             span: (40, 50),
+            ..Default::default()
         },
     ];
 
@@ -209,6 +213,7 @@ fn test_bibliography_sort_override_by_style() {
             label: None,
             url: None,
             span: (0, 10),
+            ..Default::default()
         },
         Citation {
             id: "alpha".to_string(),
@@ -216,6 +221,7 @@ fn test_bibliography_sort_override_by_style() {
             label: None,
             url: None,
             span: (20, 30),
+            ..Default::default()
         },
     ];
 
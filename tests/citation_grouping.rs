@@ -266,6 +266,48 @@ fn test_grouped_with_locators() {
     assert_eq!(clusters[0].items[1].locator, Some("3".into()));
 }
 
+/// Test 8: Pandoc groups accept a URL, applied to each of their items
+#[test]
+fn test_pandoc_syntax_with_url() {
+    // Given: A Pandoc group followed by a link destination
+    let markdown = "See [@a; @b](https://doi.org/10.1000/xyz) for details.";
+
+    // When: We extract citation clusters
+    let clusters = extract_citation_clusters(markdown);
+
+    // Then: The URL is part of the cluster and kept on every item
+    assert_eq!(clusters.len(), 1);
+    assert_eq!(clusters[0].span, (4, 41));
+    for item in &clusters[0].items {
+        assert_eq!(item.url.as_deref(), Some("https://doi.org/10.1000/xyz"));
+    }
+}
+
+/// Test 9: Pandoc groups merge with adjacent simple citations and groups
+#[test]
+fn test_pandoc_groups_merge_with_adjacent_citations() {
+    // Given: Pandoc groups and simple citations separated by whitespace only
+    let markdown = "Studies [@a; @b] [@c] [@d; @e](url) agree, unlike [@f].";
+
+    // When: We extract citation clusters
+    let clusters = extract_citation_clusters(markdown);
+
+    // Then: The adjacent ones form a single cluster, in document order
+    assert_eq!(
+        clusters.len(),
+        2,
+        "Expected 2 clusters, got {}",
+        clusters.len()
+    );
+    let ids: Vec<&str> = clusters[0].items.iter().map(|i| i.id.as_str()).collect();
+    assert_eq!(ids, vec!["a", "b", "c", "d", "e"]);
+    assert_eq!(
+        &markdown[clusters[0].span.0..clusters[0].span.1],
+        "[@a; @b] [@c] [@d; @e](url)"
+    );
+    assert_eq!(clusters[1].items[0].id, "f");
+}
+
 // =============================================================================
 // Tests for CSL formatting with clusters (Phase 5)
 // =============================================================================
@@ -315,18 +357,21 @@ fn test_format_numeric_consecutive() {
                 locator: None,
                 label: None,
                 url: None,
+                ..Default::default()
             },
             CitationItem {
                 id: "ref-b".to_string(),
                 locator: None,
                 label: None,
                 url: None,
+                ..Default::default()
             },
             CitationItem {
                 id: "ref-c".to_string(),
                 locator: None,
                 label: None,
                 url: None,
+                ..Default::default()
             },
         ],
         span: (0, 20),
@@ -366,6 +411,7 @@ fn test_format_numeric_non_consecutive() {
                 locator: None,
                 label: None,
                 url: None,
+                ..Default::default()
             }],
             span: (0, 10),
         },
@@ -377,18 +423,21 @@ fn test_format_numeric_non_consecutive() {
                     locator: None,
                     label: None,
                     url: None,
+                    ..Default::default()
                 },
                 CitationItem {
                     id: "ref-c".to_string(),
                     locator: None,
                     label: None,
                     url: None,
+                    ..Default::default()
                 },
                 CitationItem {
                     id: "ref-d".to_string(),
                     locator: None,
                     label: None,
                     url: None,
+                    ..Default::default()
                 },
             ],
             span: (15, 40),
@@ -424,6 +473,7 @@ fn test_format_numeric_multiple_gaps() {
                 locator: None,
                 label: None,
                 url: None,
+                ..Default::default()
             }],
             span: (0, 5),
         },
@@ -435,24 +485,28 @@ fn test_format_numeric_multiple_gaps() {
                     locator: None,
                     label: None,
                     url: None,
+                    ..Default::default()
                 },
                 CitationItem {
                     id: "r2".to_string(),
                     locator: None,
                     label: None,
                     url: None,
+                    ..Default::default()
                 },
                 CitationItem {
                     id: "r4".to_string(),
                     locator: None,
                     label: None,
                     url: None,
+                    ..Default::default()
                 },
                 CitationItem {
                     id: "r5".to_string(),
                     locator: None,
                     label: None,
                     url: None,
+                    ..Default::default()
                 },
             ],
             span: (10, 40),
@@ -488,12 +542,14 @@ fn test_format_author_date_grouped() {
                 locator: None,
                 label: None,
                 url: None,
+                ..Default::default()
             },
             CitationItem {
                 id: "jones2021".to_string(),
                 locator: None,
                 label: None,
                 url: None,
+                ..Default::default()
             },
         ],
         span: (0, 30),
@@ -481,15 +481,593 @@ fn test_cli_process_grouped_citations() {
     );
 }
 
+#[test]
+fn test_cli_process_group_comma_separated() {
+    // Given: Citations separated by commas and a line break
+    let markdown = "Studies [@ref-a], [@ref-b],\n[@ref-c] show that...";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs_file = create_temp_file(GROUPED_TEST_REFS, ".json");
+    let style_file = create_temp_file(common::NUMERIC_STYLE, ".csl");
+
+    // When: We group comma-separated citations
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+            "--no-bib",
+            "--group",
+            "comma",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: The first two form one cluster, the line break ends it
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        !stdout.contains("(1), (2)") && stdout.ends_with("),\n(3) show that..."),
+        "Comma-separated citations should be grouped: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_process_wikilinks() {
+    // Given: Obsidian wikilink citations next to a bracketed one
+    let markdown = "Studies [[@ref-a]] [[@ref-b|p. 4]] [@ref-c] and [[Other note]].";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs_file = create_temp_file(GROUPED_TEST_REFS, ".json");
+    let style_file = create_temp_file(common::NUMERIC_STYLE, ".csl");
+
+    // When: We process it with wikilinks on
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+            "--no-bib",
+            "--wikilinks",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: The three citations form one cluster, the other wikilink is kept
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.starts_with("Studies (") && !stdout.contains("@ref") && !stdout.contains(") ("),
+        "Wikilink citations should be one cluster: {}",
+        stdout
+    );
+    assert!(
+        stdout.ends_with(" and [[Other note]]."),
+        "Other wikilinks should be kept: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_process_key_aliases() {
+    // Given: A manuscript citing ref-a under an old key and under its id
+    let markdown = "First [@pmid:111], then [@ref-a] and [@ref-b].";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs_file = create_temp_file(GROUPED_TEST_REFS, ".json");
+    let style_file = create_temp_file(common::NUMERIC_STYLE, ".csl");
+    let aliases_file = create_temp_file("# Old PubMed keys\n\"pmid:111\" = \"ref-a\"\n", ".toml");
+
+    // When: We process it with the alias file
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+            "--aliases",
+            aliases_file.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: Both keys cite the same reference, listed once
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.starts_with("First (1), then (1) and (2)."),
+        "The alias should cite ref-a: {}",
+        stdout
+    );
+    assert_eq!(
+        stdout.matches("AuthorA").count(),
+        1,
+        "ref-a should be listed once: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_process_latex_input() {
+    // Given: A LaTeX document with citation commands and \printbibliography
+    let tex = r"\begin{document}
+Studies \parencite{ref-a,ref-b} show that % \cite{ref-c}
+it holds \citep[p.~4]{ref-b}.
+
+\printbibliography
+\end{document}
+";
+    let tex_file = create_temp_file(tex, ".tex");
+    let refs_file = create_temp_file(GROUPED_TEST_REFS, ".json");
+    let style_file = create_temp_file(common::NUMERIC_STYLE, ".csl");
+
+    // When: We process it, the format being detected from the extension
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            tex_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: Commands are replaced and the bibliography replaces \printbibliography
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.starts_with(
+            "\\begin{document}\nStudies (1,2) show that % \\cite{ref-c}\nit holds (2)."
+        ),
+        "Citation commands should be formatted: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("\\section*{References}\n\n1. ") && !stdout.contains("csl-entry"),
+        "The bibliography should be LaTeX: {}",
+        stdout
+    );
+    assert!(
+        !stdout.contains("\\printbibliography") && stdout.ends_with("\\end{document}\n"),
+        "The bibliography should replace \\printbibliography: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_process_org_input() {
+    // Given: An Org document with org-cite citations and #+print_bibliography:
+    let org = "#+title: Notes
+
+Studies [cite:@ref-a;@ref-b] show that [cite/t:@ref-c] was right.
+
+#+begin_src org
+[cite:@missing]
+#+end_src
+
+* References
+#+print_bibliography:
+";
+    let org_file = create_temp_file(org, ".org");
+    let refs_file = create_temp_file(GROUPED_TEST_REFS, ".json");
+    let style_file = create_temp_file(common::NUMERIC_STYLE, ".csl");
+
+    // When: We process it, the format being detected from the extension
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            org_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: Citations are replaced, the source block is untouched, and the
+    // bibliography goes under the document's heading
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("Studies (1,2) show that") && stdout.contains("[cite:@missing]"),
+        "Citations should be formatted outside blocks: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("* References\n1. ") && !stdout.contains("#+print_bibliography:"),
+        "The bibliography should replace #+print_bibliography: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_process_typst_input() {
+    // Given: A Typst document with citations, a figure reference and a bibliography call
+    let typst = r#"Studies @ref-a @ref-b[p. 7] show, as in @fig:plot, that #cite(<ref-c>) holds.
+
+#bibliography("refs.bib")
+"#;
+    let typst_file = create_temp_file(typst, ".typ");
+    let refs_file = create_temp_file(GROUPED_TEST_REFS, ".json");
+    let style_file = create_temp_file(common::NUMERIC_STYLE, ".csl");
+
+    // When: We process it, the format being detected from the extension
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            typst_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: Citations are replaced, the label reference is left alone, and
+    // the bibliography replaces the call
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.starts_with("Studies (1,2) show, as in @fig:plot, that (3) holds."),
+        "Citations should be formatted, labels kept: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("= References\n\n1\\. ") && !stdout.contains("#bibliography"),
+        "The bibliography should replace #bibliography: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_process_notebook() {
+    // Given: A notebook with citations in two Markdown cells and a code cell
+    let notebook = r#"{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "id": "intro",
+   "metadata": {},
+   "source": ["As shown [@ref-a]."]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "id": "plot",
+   "metadata": {},
+   "outputs": [{"output_type": "stream", "name": "stdout", "text": ["[@ref-c]\n"]}],
+   "source": ["print('[@ref-c]')"]
+  },
+  {
+   "cell_type": "markdown",
+   "id": "results",
+   "metadata": {},
+   "source": ["Confirmed [@ref-b] and [@ref-a]."]
+  }
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}
+"#;
+    let notebook_file = create_temp_file(notebook, ".ipynb");
+    let refs_file = create_temp_file(GROUPED_TEST_REFS, ".json");
+    let style_file = create_temp_file(common::NUMERIC_STYLE, ".csl");
+
+    // When: We process it, the format being detected from the extension
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            notebook_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: Numbering runs across the Markdown cells, the code cell is kept
+    // as is and the bibliography is appended in a new cell
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let code_cell = &notebook[notebook.find("  {\n   \"cell_type\": \"code\"").unwrap()..];
+    let code_cell = &code_cell[..code_cell.find("  },").unwrap()];
+    assert!(
+        stdout.contains(code_cell),
+        "The code cell should be unchanged: {}",
+        stdout
+    );
+    let value: serde_json::Value = serde_json::from_str(&stdout).expect("Output should be JSON");
+    let cells = value["cells"].as_array().unwrap();
+    assert_eq!(cells.len(), 4);
+    assert_eq!(cells[0]["source"][0].as_str(), Some("As shown (1)."));
+    assert_eq!(
+        cells[2]["source"][0].as_str(),
+        Some("Confirmed (2) and (1).")
+    );
+    assert_eq!(cells[3]["cell_type"].as_str(), Some("markdown"));
+    assert_eq!(cells[3]["id"].as_str(), Some("bibliography"));
+    assert_eq!(cells[3]["source"][0].as_str(), Some("## References\n"));
+}
+
+#[test]
+fn test_cli_process_docx() {
+    // Given: A Word document with a citation in a bold run, and one split
+    // across runs by the spelling checker
+    let docx = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/docx/draft.docx");
+    let refs_file = create_temp_file(GROUPED_TEST_REFS, ".json");
+    let style_file = create_temp_file(common::NUMERIC_STYLE, ".csl");
+    let output_file = tempfile::Builder::new().suffix(".docx").tempfile().unwrap();
+
+    // When: We process it into a new document
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            docx.to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+            "-o",
+            output_file.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: The output is a package whose text has the citations replaced,
+    // and the bibliography appended
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let processed = csl_tools::docx::Docx::open(fs::read(output_file.path()).unwrap())
+        .expect("Output should be a DOCX package");
+    let text = processed.text();
+    assert!(
+        text.contains("Early studies (1) found the effect, later confirmed (2)."),
+        "Citations should be replaced: {}",
+        text
+    );
+    assert!(
+        text.contains("References\n\n"),
+        "The bibliography should be appended: {}",
+        text
+    );
+}
+
 #[test]
 fn test_cli_process_mixed_grouped_and_separate() {
     // Given: Markdown with some adjacent citations and some separate
     let markdown = "First group [@ref-a] [@ref-b] and then separate [@ref-c].";
     let md_file = create_temp_file(markdown, ".md");
-    let refs_file = create_temp_file(GROUPED_TEST_REFS, ".json");
-    let style_file = create_temp_file(common::NUMERIC_STYLE, ".csl");
+    let refs_file = create_temp_file(GROUPED_TEST_REFS, ".json");
+    let style_file = create_temp_file(common::NUMERIC_STYLE, ".csl");
+
+    // When: We run the process command
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: First two should be grouped, third separate
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // First group: (1-2) or (1,2)
+    let has_first_group =
+        stdout.contains("(1-2)") || stdout.contains("(1–2)") || stdout.contains("(1,2)");
+    // Third citation separate: (3)
+    let has_separate_third = stdout.contains("(3)");
+
+    assert!(
+        has_first_group,
+        "First two citations should be grouped, got: {}",
+        stdout
+    );
+    assert!(
+        has_separate_third,
+        "Third citation should be separate as (3), got: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_process_escaped_citations_are_literal() {
+    // Given: Markdown with escaped citations next to a real one
+    let markdown = r"Write \[@item-1] or \@item-1 to get [@item-1].";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs_file = create_temp_file(TEST_REFS, ".json");
+    let style_file = create_temp_file(TEST_STYLE, ".csl");
+
+    // When: We run the process command
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+            "--no-bib",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: The escaped citations are kept without their backslash
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(stdout, "Write [@item-1] or @item-1 to get (Doe, 2021).");
+}
+
+/// Note style: citations are rendered as footnotes
+const NOTE_STYLE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<style xmlns="http://purl.org/net/xbiblio/csl" class="note" version="1.0">
+  <info>
+    <title>Test Note Style</title>
+    <id>test-note-style</id>
+    <updated>2024-01-01T00:00:00+00:00</updated>
+  </info>
+  <citation>
+    <layout suffix="." delimiter="; ">
+      <names variable="author">
+        <name form="short"/>
+      </names>
+      <text prefix=", " variable="title" font-style="italic"/>
+    </layout>
+  </citation>
+</style>"#;
+
+#[test]
+fn test_cli_process_note_style_generates_footnotes() {
+    // Given: A citation in the body and one inside an existing footnote
+    let markdown = "As shown [@item-1].[^1]\n\n[^1]: See also [@item-1].\n";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs_file = create_temp_file(TEST_REFS, ".json");
+    let style_file = create_temp_file(NOTE_STYLE, ".csl");
+
+    // When: We run the process command with a note style
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+            "--no-bib",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: The body citation becomes a footnote, the other stays in its note
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.starts_with("As shown.[^c1][^1]"),
+        "Citation should become a footnote reference: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("[^1]: See also Doe"),
+        "Citation inside a footnote should be rendered in place: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("[^c1]: Doe"),
+        "A footnote definition should be generated: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_process_note_style_narrative_citation() {
+    // Given: A narrative citation in the body
+    let markdown = "As @item-1 argues, notes work.\n";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs_file = create_temp_file(TEST_REFS, ".json");
+    let style_file = create_temp_file(NOTE_STYLE, ".csl");
+
+    // When: We run the process command with a note style
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+            "--no-bib",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: The author stays in the sentence, the citation goes to the note
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.starts_with("As Doe[^c1] argues, notes work."),
+        "The author should stay in the text: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("[^c1]: Doe, <i>Test Book</i>."),
+        "The note should hold the citation: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_process_refsections_per_chapter() {
+    // Given: Two chapters, each citing a reference
+    let markdown = "# Chapter 1\n\nSee [@item-1].\n\n# Chapter 2\n\nAgain [@item-1].\n";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs_file = create_temp_file(TEST_REFS, ".json");
+    let style_file = create_temp_file(TEST_STYLE, ".csl");
 
-    // When: We run the process command
+    // When: We split reference sections at level-1 headings
     let output = Command::new(binary_path())
         .args([
             "process",
@@ -498,34 +1076,222 @@ fn test_cli_process_mixed_grouped_and_separate() {
             refs_file.path().to_str().unwrap(),
             "--csl",
             style_file.path().to_str().unwrap(),
+            "--refsection-level",
+            "1",
         ])
         .output()
         .expect("Failed to execute command");
 
-    // Then: First two should be grouped, third separate
+    // Then: Each chapter ends with its own bibliography
     let stdout = String::from_utf8_lossy(&output.stdout);
     assert!(
         output.status.success(),
         "Process should succeed. stderr: {}",
         String::from_utf8_lossy(&output.stderr)
     );
+    assert_eq!(
+        stdout.matches("## References").count(),
+        2,
+        "Each chapter should have a bibliography: {}",
+        stdout
+    );
+    let chapter_2 = stdout.find("# Chapter 2").unwrap();
+    let first_bib = stdout.find("## References").unwrap();
+    assert!(
+        first_bib < chapter_2,
+        "The first bibliography should close chapter 1: {}",
+        stdout
+    );
+}
 
-    // First group: (1-2) or (1,2)
-    let has_first_group =
-        stdout.contains("(1-2)") || stdout.contains("(1–2)") || stdout.contains("(1,2)");
-    // Third citation separate: (3)
-    let has_separate_third = stdout.contains("(3)");
+#[test]
+fn test_cli_continuous_numbering_rejects_sorted_style() {
+    // Given: Two chapters, and a style sorting its bibliography by author
+    let markdown = "# Chapter 1\n\nSee [@item-1].\n\n# Chapter 2\n\nAgain [@item-1].\n";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs_file = create_temp_file(TEST_REFS, ".json");
+    let sorted_style = TEST_STYLE.replace(
+        "<bibliography>",
+        r#"<bibliography><sort><key variable="author"/></sort>"#,
+    );
+    let style_file = create_temp_file(&sorted_style, ".csl");
 
+    // When: We ask for continuous numbering across chapters
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+            "--refsection-level",
+            "1",
+            "--refsection-numbering",
+            "continuous",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: It is rejected rather than dropping the wrong entries
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(12), "stderr: {}", stderr);
     assert!(
-        has_first_group,
-        "First two citations should be grouped, got: {}",
+        stderr.contains("sorts it") && output.stdout.is_empty(),
+        "The sorted bibliography should be reported: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_cli_process_front_matter_defaults() {
+    // Given: A document whose front matter names its bibliography, style and
+    // bibliography title, with paths relative to the document
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("refs.json"), TEST_REFS).unwrap();
+    fs::write(dir.path().join("style.csl"), TEST_STYLE).unwrap();
+    let markdown = "---\nbibliography: refs.json\ncsl: style.csl\nreference-section-title: Works Cited\n---\nAs shown [@item-1].\n";
+    let md_path = dir.path().join("paper.md");
+    fs::write(&md_path, markdown).unwrap();
+
+    // When: We process it without --bib and --csl, stripping the front matter
+    let output = Command::new(binary_path())
+        .args(["process", md_path.to_str().unwrap(), "--strip-front-matter"])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: The front matter settings are used and the block is removed
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.starts_with("As shown (Doe, 2021)."),
+        "Front matter should be stripped and the citation formatted: {}",
         stdout
     );
     assert!(
-        has_separate_third,
-        "Third citation should be separate as (3), got: {}",
+        stdout.contains("## Works Cited"),
+        "reference-section-title should set the bibliography header: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_process_quarto_document() {
+    // Given: A Quarto document naming its bibliography and style in YAML, with
+    // cross-references and an R chunk
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("refs.json"), TEST_REFS).unwrap();
+    fs::write(dir.path().join("style.csl"), TEST_STYLE).unwrap();
+    let qmd = "---\ntitle: Report\nformat: html\nbibliography: refs.json\ncsl: style.csl\n---\n\
+As @fig-plot and [@tbl-data] show [@item-1].\n\n\
+```{r}\n#| label: fig-plot\nplot(x, main = \"[@item-2]\")\n```\n";
+    let qmd_path = dir.path().join("report.qmd");
+    fs::write(&qmd_path, qmd).unwrap();
+
+    // When: We process it without --bib and --csl
+    let output = Command::new(binary_path())
+        .args(["process", qmd_path.to_str().unwrap()])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: Only the citation is formatted, cross-references and the chunk
+    // are left for Quarto
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("As @fig-plot and [@tbl-data] show (Doe, 2021)."),
+        "Cross-references should be kept: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("plot(x, main = \"[@item-2]\")"),
+        "Code chunks should not be scanned: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_cli_process_options_override_front_matter() {
+    // Given: A front matter naming a style that does not exist
+    let markdown = "---\ncsl: missing.csl\n---\nAs shown [@item-1].\n";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs_file = create_temp_file(TEST_REFS, ".json");
+    let style_file = create_temp_file(TEST_STYLE, ".csl");
+
+    // When: We give the style on the command line
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+            "--no-bib",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: The option wins and the front matter is kept in the output
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(stdout, "---\ncsl: missing.csl\n---\nAs shown (Doe, 2021).");
+}
+
+#[test]
+fn test_cli_process_nocite_appends_uncited_references() {
+    // Given: A document citing one of three references
+    let markdown = "Only [@ref-b] is cited.";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs_file = create_temp_file(GROUPED_TEST_REFS, ".json");
+    let style_file = create_temp_file(TEST_STYLE, ".csl");
+
+    // When: We add the two others with --nocite
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+            "--nocite",
+            "@ref-c",
+            "--nocite",
+            "@ref-a, @ref-b",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: They follow the cited reference in the bibliography
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let positions: Vec<usize> = ["Title B", "Title C", "Title A"]
+        .iter()
+        .map(|title| stdout.find(title).expect("reference should be listed"))
+        .collect();
+    assert!(
+        positions[0] < positions[1] && positions[1] < positions[2],
+        "Cited reference first, then nocite ones in order: {}",
         stdout
     );
+    assert_eq!(stdout.matches("Title B").count(), 1, "{}", stdout);
 }
 
 // ============================================
@@ -894,6 +1660,172 @@ fn test_error_hint_reference_not_found() {
     );
 }
 
+#[test]
+fn test_error_reference_not_found_shows_position() {
+    let markdown = "# Title\n\nAs shown by [@item-1] and [@smith2021].";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs_file = create_temp_file(TEST_REFS, ".json");
+    let style_file = create_temp_file(TEST_STYLE, ".csl");
+
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let expected = format!(
+        "{}:3:27: reference 'smith2021' not found",
+        md_file.path().display()
+    );
+    assert!(
+        stderr.contains(&expected),
+        "stderr should point at the missing key ({}), got: {}",
+        expected,
+        stderr
+    );
+}
+
+#[test]
+fn test_cli_process_mentions_are_not_citations() {
+    // Given: Mentions of people beside a narrative and a bracketed citation
+    let markdown = "Ask @jsmith or ping @team: @item-1 agrees [@item-1].";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs_file = create_temp_file(TEST_REFS, ".json");
+    let style_file = create_temp_file(TEST_STYLE, ".csl");
+
+    // When: We process it
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+            "--no-bib",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: Only the reference is cited, the mentions are kept as text
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Process should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.starts_with("Ask @jsmith or ping @team: ") && !stdout.contains("@item-1"),
+        "Mentions should be kept, citations formatted: {}",
+        stdout
+    );
+}
+
+#[test]
+fn test_error_bracketed_mention_is_reported() {
+    // Given: A bracketed citation of an unknown key
+    let markdown = "As shown [@jsmith].";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs_file = create_temp_file(TEST_REFS, ".json");
+    let style_file = create_temp_file(TEST_STYLE, ".csl");
+
+    // When: We process it
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: It is still an error
+    assert_eq!(output.status.code(), Some(13));
+}
+
+#[test]
+fn test_error_reference_not_found_in_notebook_shows_cell() {
+    // Given: A notebook whose third cell cites a missing key
+    let notebook = r#"{"cells": [
+  {"cell_type": "markdown", "id": "a", "metadata": {}, "source": ["As shown [@item-1]."]},
+  {"cell_type": "code", "id": "b", "metadata": {}, "source": ["x = 1"], "outputs": []},
+  {"cell_type": "markdown", "id": "c", "metadata": {}, "source": ["And [@smith2021]."]}
+ ], "metadata": {}, "nbformat": 4, "nbformat_minor": 5}"#;
+    let notebook_file = create_temp_file(notebook, ".ipynb");
+    let refs_file = create_temp_file(TEST_REFS, ".json");
+    let style_file = create_temp_file(TEST_STYLE, ".csl");
+
+    // When: We process it
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            notebook_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    // Then: The error names the cell, not a line of the joined cells
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let expected = format!(
+        "{}: cell 3: reference 'smith2021' not found",
+        notebook_file.path().display()
+    );
+    assert!(
+        stderr.contains(&expected),
+        "stderr should point at the cell ({}), got: {}",
+        expected,
+        stderr
+    );
+}
+
+#[test]
+fn test_error_ambiguous_doi_lists_matches() {
+    let markdown = "# Title\n\nAs shown by [@doi:10.1000/dup].";
+    let md_file = create_temp_file(markdown, ".md");
+    let refs = r#"[{"id": "first", "DOI": "10.1000/dup"}, {"id": "second", "DOI": "10.1000/DUP"}]"#;
+    let refs_file = create_temp_file(refs, ".json");
+    let style_file = create_temp_file(TEST_STYLE, ".csl");
+
+    let output = Command::new(binary_path())
+        .args([
+            "process",
+            md_file.path().to_str().unwrap(),
+            "--bib",
+            refs_file.path().to_str().unwrap(),
+            "--csl",
+            style_file.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let expected = format!(
+        "{}:3:13: 'doi:10.1000/dup' matches several references: first, second",
+        md_file.path().display()
+    );
+    assert_eq!(output.status.code(), Some(13));
+    assert!(
+        stderr.contains(&expected),
+        "stderr should list the matching references ({}), got: {}",
+        expected,
+        stderr
+    );
+}
+
 // ============================================
 // Tests for confirmation message on stderr
 // ============================================
@@ -956,4 +1888,3 @@ fn test_no_confirmation_message_on_stdout_output() {
         stderr
     );
 }
-